@@ -1,3 +1,6 @@
+// Grid positions to clip space, from `Camera2D::view_matrix`
+@group(2) @binding(0) var<uniform> camera: mat4x4<f32>;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
@@ -12,7 +15,7 @@ struct VertexOutput {
 @vertex
 fn main(input: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    out.pos = vec4f(input.position, 1.0);
+    out.pos = camera * vec4f(input.position, 1.0);
     out.tex_coords = input.tex_coords;
     return out;
 }
@@ -1,3 +1,6 @@
+// Grid positions to clip space, from `Camera2D::view_matrix`
+@group(0) @binding(0) var<uniform> camera: mat4x4<f32>;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) col: vec4<f32>,
@@ -12,7 +15,7 @@ struct VertexOutput {
 @vertex
 fn main(input: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    out.pos = vec4f(input.position, 1.0);
+    out.pos = camera * vec4f(input.position, 1.0);
     out.col = input.col;
     return out;
 }
@@ -0,0 +1,489 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use ordered_float::OrderedFloat;
+use serde::Serialize;
+
+use crate::street_graph::face_area;
+use crate::street_network::StreetNetwork;
+use crate::tensor_field::Point;
+use crate::triangulation::Triangulation;
+use crate::zoning::LandUse;
+
+/// Number of bearing bins of the orientation entropy, 10 degrees wide
+pub const ORIENTATION_BINS: usize = 36;
+
+/// Entropy of a perfect grid, whose streets all fall into four bins
+const GRID_ENTROPY: f64 = std::f64::consts::LN_2 * 2.0;
+
+/// Metrics of a street network that can be compared with the ones of real cities. Lengths are in
+/// meters and areas in square meters, except for the study area which is in square kilometers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NetworkReport {
+    /// Vertices where three or more streets meet
+    pub intersection_count: usize,
+    pub dead_end_count: usize,
+    /// Total length of all streets
+    pub street_length: f64,
+    /// Area of the convex hull of the network
+    pub area_km2: f64,
+    /// Intersections per square kilometer
+    pub intersection_density: f64,
+    /// Share of the intersections where exactly three streets meet
+    pub three_way_share: f64,
+    /// Share of the intersections where exactly four streets meet
+    pub four_way_share: f64,
+    /// Share of the intersections and dead ends that are dead ends
+    pub dead_end_ratio: f64,
+    pub block_count: usize,
+    pub mean_block_area: f64,
+    pub mean_block_perimeter: f64,
+    /// Blocks of every land use
+    pub land_uses: BTreeMap<LandUse, LandUseStats>,
+    /// Shannon entropy in nats of the street bearings, weighted by length, between ln 4 for a grid
+    /// and ln 36 for streets going every which way
+    pub orientation_entropy: f64,
+    /// 1 for a perfect grid down to 0 for uniformly spread bearings
+    pub orientation_order: f64,
+    /// How much longer the streets between intersections are than the straight lines between their
+    /// ends, 1 for straight streets
+    pub circuity: f64,
+    /// Share of the shortest paths between intersections passing through each one
+    pub betweenness: CentralityStats,
+    /// Inverse of the mean distance from each intersection to all others
+    pub closeness: CentralityStats,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CentralityStats {
+    pub mean: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LandUseStats {
+    pub block_count: usize,
+    /// Total area of the blocks
+    pub area: f64,
+}
+
+/// A street between two vertices that aren't in the middle of a street, running through any
+/// number of vertices where only two edges meet
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    ends: [usize; 2],
+    length: f64,
+}
+
+/// Computes the metrics of the network and of the blocks between its streets, whose land uses
+/// are given in the same order as the blocks.
+///
+/// Intersections, dead ends, circuity and centralities are measured on the simplified network,
+/// where vertices in the middle of a street are left out. Betweenness and closeness are
+/// normalized so that they can be compared between networks of different sizes.
+pub fn analyze_network(
+    network: &StreetNetwork,
+    blocks: &[Vec<Point>],
+    triangulations: &[Triangulation],
+    land_uses: &[LandUse],
+    meters_per_unit: f32,
+) -> NetworkReport {
+    let scale = meters_per_unit as f64;
+
+    let nodes: Vec<usize> = (0..network.vertices.len())
+        .filter(|&vertex| network.degree(vertex) != 2 && network.degree(vertex) > 0)
+        .collect();
+    let count_degree = |matches: fn(usize) -> bool| {
+        nodes
+            .iter()
+            .filter(|&&node| matches(network.degree(node)))
+            .count()
+    };
+    let intersection_count = count_degree(|degree| degree >= 3);
+    let dead_end_count = count_degree(|degree| degree == 1);
+    let share = |count: usize, total: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 / total as f64
+        }
+    };
+
+    let street_length = network
+        .edges
+        .iter()
+        .map(|edge| edge.length as f64)
+        .sum::<f64>()
+        * scale;
+    let area_km2 = face_area(&convex_hull(&network.vertices)).abs() as f64 * scale * scale / 1e6;
+
+    let block_count = blocks.len();
+    let mean_block_area = triangulations
+        .iter()
+        .map(|triangulation| triangulation.area() as f64)
+        .sum::<f64>()
+        * scale
+        * scale
+        / block_count.max(1) as f64;
+    let mean_block_perimeter = blocks
+        .iter()
+        .flat_map(|block| {
+            (0..block.len()).map(|i| (block[(i + 1) % block.len()] - block[i]).norm() as f64)
+        })
+        .sum::<f64>()
+        * scale
+        / block_count.max(1) as f64;
+    let mut land_use_stats: BTreeMap<LandUse, LandUseStats> = LandUse::ALL
+        .iter()
+        .map(|&land_use| (land_use, LandUseStats::default()))
+        .collect();
+    for (triangulation, land_use) in triangulations.iter().zip(land_uses) {
+        let stats = land_use_stats.entry(*land_use).or_default();
+        stats.block_count += 1;
+        stats.area += triangulation.area() as f64 * scale * scale;
+    }
+
+    let histogram = bearing_histogram(network, ORIENTATION_BINS);
+    let orientation_entropy: f64 = histogram
+        .iter()
+        .filter(|&&share| share > 0.0)
+        .map(|&share| -share * share.ln())
+        .sum();
+    let max_entropy = (ORIENTATION_BINS as f64).ln();
+    let orientation_order = if histogram.iter().all(|&share| share == 0.0) {
+        0.0
+    } else {
+        1.0 - ((orientation_entropy - GRID_ENTROPY) / (max_entropy - GRID_ENTROPY))
+            .clamp(0.0, 1.0)
+            .powi(2)
+    };
+
+    let segments = simplify(network);
+    let straight_length: f64 = segments
+        .iter()
+        .map(|segment| {
+            let [start, end] = segment.ends;
+            (network.vertices[end] - network.vertices[start]).norm() as f64
+        })
+        .sum();
+    let segment_length: f64 = segments.iter().map(|segment| segment.length).sum();
+    let circuity = if straight_length > 0.0 {
+        segment_length / straight_length
+    } else {
+        1.0
+    };
+
+    let (betweenness, closeness) = centralities(&nodes, &segments);
+
+    NetworkReport {
+        intersection_count,
+        dead_end_count,
+        street_length,
+        area_km2,
+        intersection_density: if area_km2 > 0.0 {
+            intersection_count as f64 / area_km2
+        } else {
+            0.0
+        },
+        three_way_share: share(count_degree(|degree| degree == 3), intersection_count),
+        four_way_share: share(count_degree(|degree| degree == 4), intersection_count),
+        dead_end_ratio: share(dead_end_count, nodes.len()),
+        block_count,
+        mean_block_area,
+        mean_block_perimeter,
+        land_uses: land_use_stats,
+        orientation_entropy,
+        orientation_order,
+        circuity,
+        betweenness,
+        closeness,
+    }
+}
+
+/// Shares of the street length going in each of `bins` directions. Bin `i` is centered on a
+/// bearing of `i * 360 / bins` degrees clockwise from north, with y pointing north. Every street
+/// counts in both directions, so opposite bins are always equal.
+pub fn bearing_histogram(network: &StreetNetwork, bins: usize) -> Vec<f64> {
+    let mut histogram = vec![0.0; bins];
+    let bin_width = 360.0 / bins as f64;
+    for edge in &network.edges {
+        let [start, end] = edge.vertices;
+        let direction = network.vertices[end] - network.vertices[start];
+        if edge.length == 0.0 {
+            continue;
+        }
+        let bearing = (direction.x as f64).atan2(direction.y as f64).to_degrees();
+        for bearing in [bearing, bearing + 180.0] {
+            let bin = ((bearing + bin_width / 2.0).rem_euclid(360.0) / bin_width) as usize;
+            histogram[bin.min(bins - 1)] += edge.length as f64;
+        }
+    }
+
+    let total: f64 = histogram.iter().sum();
+    if total > 0.0 {
+        for share in &mut histogram {
+            *share /= total;
+        }
+    }
+    histogram
+}
+
+/// Lengths of the streets between intersections and dead ends, in city units
+pub fn segment_lengths(network: &StreetNetwork) -> Vec<f64> {
+    simplify(network)
+        .into_iter()
+        .map(|segment| segment.length)
+        .collect()
+}
+
+/// Writes the report as pretty printed JSON
+pub fn write_report(report: &NetworkReport, path: impl AsRef<Path>) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, report).map_err(io::Error::other)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// The streets between the vertices that aren't in the middle of a street. Rings without any
+/// such vertex are left out.
+fn simplify(network: &StreetNetwork) -> Vec<Segment> {
+    let is_node = |vertex: usize| network.degree(vertex) != 2;
+    let mut used = vec![false; network.edges.len()];
+    let mut segments = Vec::new();
+
+    for start in (0..network.vertices.len()).filter(|&vertex| is_node(vertex)) {
+        for &(first, first_edge) in &network.adjacency[start] {
+            if used[first_edge] {
+                continue;
+            }
+            used[first_edge] = true;
+            let mut length = network.edges[first_edge].length as f64;
+            let (mut previous_edge, mut current) = (first_edge, first);
+            while !is_node(current) {
+                let Some(&(next, next_edge)) = network.adjacency[current]
+                    .iter()
+                    .find(|&&(_, edge)| edge != previous_edge)
+                else {
+                    break;
+                };
+                used[next_edge] = true;
+                length += network.edges[next_edge].length as f64;
+                (previous_edge, current) = (next_edge, next);
+            }
+            segments.push(Segment {
+                ends: [start, current],
+                length,
+            });
+        }
+    }
+
+    segments
+}
+
+/// Normalized betweenness and closeness of every node of the simplified network, with Brandes'
+/// algorithm
+fn centralities(nodes: &[usize], segments: &[Segment]) -> (CentralityStats, CentralityStats) {
+    let count = nodes.len();
+    if count < 2 {
+        return Default::default();
+    }
+    let mut index_of = HashMap::new();
+    for (i, &node) in nodes.iter().enumerate() {
+        index_of.insert(node, i);
+    }
+    let mut adjacency: Vec<Vec<(usize, f64)>> = vec![Vec::new(); count];
+    for segment in segments {
+        let (Some(&a), Some(&b)) = (
+            index_of.get(&segment.ends[0]),
+            index_of.get(&segment.ends[1]),
+        ) else {
+            continue;
+        };
+        if a != b {
+            adjacency[a].push((b, segment.length));
+            adjacency[b].push((a, segment.length));
+        }
+    }
+
+    let mut betweenness = vec![0.0; count];
+    let mut closeness = vec![0.0; count];
+    for source in 0..count {
+        let mut distances = vec![f64::INFINITY; count];
+        let mut path_counts = vec![0.0; count];
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); count];
+        let mut order = Vec::new();
+        let mut queue = BinaryHeap::from([Reverse((OrderedFloat(0.0), source))]);
+        distances[source] = 0.0;
+        path_counts[source] = 1.0;
+
+        while let Some(Reverse((OrderedFloat(distance), node))) = queue.pop() {
+            if distance > distances[node] {
+                continue;
+            }
+            order.push(node);
+            for &(neighbour, length) in &adjacency[node] {
+                let candidate = distance + length;
+                if candidate < distances[neighbour] {
+                    distances[neighbour] = candidate;
+                    path_counts[neighbour] = path_counts[node];
+                    predecessors[neighbour] = vec![node];
+                    queue.push(Reverse((OrderedFloat(candidate), neighbour)));
+                } else if candidate == distances[neighbour] {
+                    path_counts[neighbour] += path_counts[node];
+                    predecessors[neighbour].push(node);
+                }
+            }
+        }
+
+        let mut dependencies = vec![0.0; count];
+        for &node in order.iter().rev() {
+            for &predecessor in &predecessors[node] {
+                dependencies[predecessor] +=
+                    path_counts[predecessor] / path_counts[node] * (1.0 + dependencies[node]);
+            }
+            if node != source {
+                betweenness[node] += dependencies[node];
+            }
+        }
+
+        // Scaled by the share of the network that can be reached, so that nodes of small
+        // disconnected pieces don't come out as the most central
+        let reached = order.len() - 1;
+        let total_distance: f64 = order.iter().map(|&node| distances[node]).sum();
+        if reached > 0 && total_distance > 0.0 {
+            closeness[source] =
+                reached as f64 / total_distance * reached as f64 / (count - 1) as f64;
+        }
+    }
+
+    // Every pair was counted from both ends
+    let pairs = ((count - 1) * count.saturating_sub(2)) as f64;
+    let stats = |values: &[f64], scale: f64| CentralityStats {
+        mean: values.iter().sum::<f64>() / count as f64 * scale,
+        max: values.iter().copied().fold(0.0, f64::max) * scale,
+    };
+    (
+        stats(&betweenness, if pairs > 0.0 { 1.0 / pairs } else { 0.0 }),
+        stats(&closeness, 1.0),
+    )
+}
+
+/// Andrew's monotone chain, counterclockwise
+fn convex_hull(points: &[Point]) -> Vec<Point> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let cross = |o: Point, a: Point, b: Point| (a - o).perp(&(b - o));
+    let mut hull: Vec<Point> = Vec::new();
+    for pass in [sorted.clone(), sorted.into_iter().rev().collect()] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        hull.pop();
+    }
+    hull
+}
+
+#[cfg(test)]
+mod test {
+    use crate::street_network::StreetNetwork;
+    use crate::street_plan::HermiteCurve;
+    use crate::tensor_field::Point;
+    use crate::test_support::curve;
+    use crate::triangulation::triangulate;
+    use crate::zoning::LandUse;
+
+    use super::{ORIENTATION_BINS, analyze_network, bearing_histogram};
+
+    #[test]
+    fn a_grid_is_ordered_and_four_way() {
+        // Three streets each way, crossing at nine intersections and ending in twelve dead ends.
+        // The middle of every street has an extra control point that isn't an intersection.
+        let streets: Vec<HermiteCurve> = [0.0, 100.0, 200.0]
+            .into_iter()
+            .flat_map(|offset| {
+                [
+                    curve(&[(-50.0, offset), (50.0, offset), (250.0, offset)]),
+                    curve(&[(offset, -50.0), (offset, 50.0), (offset, 250.0)]),
+                ]
+            })
+            .collect();
+        let network = StreetNetwork::from_levels(&[streets]).unwrap();
+        let block = vec![
+            Point::new(10.0, 10.0),
+            Point::new(90.0, 10.0),
+            Point::new(90.0, 90.0),
+            Point::new(10.0, 90.0),
+        ];
+        let triangulation = triangulate(&block, &[]);
+
+        let report = analyze_network(&network, &[block], &[triangulation], &[LandUse::Park], 2.0);
+
+        assert_eq!(report.intersection_count, 9);
+        assert_eq!(report.dead_end_count, 12);
+        assert_eq!(report.four_way_share, 1.0);
+        assert_eq!(report.three_way_share, 0.0);
+        assert!((report.dead_end_ratio - 12.0 / 21.0).abs() < 1e-9);
+        assert!((report.street_length - 6.0 * 300.0 * 2.0).abs() < 0.1);
+        // The hull is the square with the dead ends cut off at its corners
+        assert!((report.area_km2 - (600.0 * 600.0 - 4.0 * 100.0 * 100.0 / 2.0) / 1e6).abs() < 1e-6);
+        assert!((report.mean_block_area - 160.0 * 160.0).abs() < 0.1);
+        assert!((report.mean_block_perimeter - 4.0 * 160.0).abs() < 0.1);
+        assert_eq!(report.land_uses[&LandUse::Park].block_count, 1);
+        assert!((report.land_uses[&LandUse::Park].area - 160.0 * 160.0).abs() < 0.1);
+        assert_eq!(report.land_uses[&LandUse::Residential].block_count, 0);
+        assert!((report.orientation_entropy - 4.0_f64.ln()).abs() < 1e-9);
+        assert!((report.orientation_order - 1.0).abs() < 1e-9);
+        assert!((report.circuity - 1.0).abs() < 1e-6);
+        // The center lies on the most shortest paths and is closest to everything
+        assert!(report.betweenness.max > report.betweenness.mean);
+        assert!(report.closeness.max > report.closeness.mean);
+    }
+
+    #[test]
+    fn the_center_of_a_star_is_on_every_path() {
+        let arms = [(10.0, 0.0), (0.0, 10.0), (-10.0, 0.0), (0.0, -10.0)];
+        let streets: Vec<HermiteCurve> =
+            arms.iter().map(|&arm| curve(&[(0.0, 0.0), arm])).collect();
+        let network = StreetNetwork::from_levels(&[streets]).unwrap();
+
+        let report = analyze_network(&network, &[], &[], &[], 1.0);
+
+        assert_eq!(report.intersection_count, 1);
+        assert!((report.betweenness.max - 1.0).abs() < 1e-9);
+        assert!((report.betweenness.mean - 0.2).abs() < 1e-9);
+        assert!((report.closeness.max - 0.1).abs() < 1e-9);
+        assert_eq!(report.block_count, 0);
+        assert_eq!(report.mean_block_area, 0.0);
+    }
+
+    #[test]
+    fn bearings_count_in_both_directions() {
+        let angle = 30.0_f32.to_radians();
+        let network = StreetNetwork::from_levels(&[vec![curve(&[
+            (0.0, 0.0),
+            (angle.sin() * 10.0, angle.cos() * 10.0),
+        ])]])
+        .unwrap();
+
+        let histogram = bearing_histogram(&network, ORIENTATION_BINS);
+
+        assert_eq!(histogram.len(), ORIENTATION_BINS);
+        assert!((histogram[3] - 0.5).abs() < 1e-9);
+        assert!((histogram[21] - 0.5).abs() < 1e-9);
+        assert!((histogram.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+}
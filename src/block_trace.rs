@@ -0,0 +1,183 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::BlockConfig;
+use crate::street_graph::{FaceFailure, GeometryError, trace_face};
+use crate::tensor_field::Point;
+
+/// The passes block extraction runs a street face through, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockPass {
+    /// Split where the face touches itself at degenerate points
+    DegeneratePoints,
+    /// Nearly straight runs of vertices collapsed
+    Flattened,
+    /// Pulled in from the street centerlines, with faces that became too small dropped
+    Scaled,
+    /// Self intersections left by scaling untangled
+    NonManifoldFixed,
+    /// Split at concave vertices and broken up into the resulting faces
+    Split,
+    /// Nearby vertices merged, with faces left with fewer than three vertices dropped
+    MergedPoints,
+}
+
+impl BlockPass {
+    pub const ALL: [BlockPass; 6] = [
+        BlockPass::DegeneratePoints,
+        BlockPass::Flattened,
+        BlockPass::Scaled,
+        BlockPass::NonManifoldFixed,
+        BlockPass::Split,
+        BlockPass::MergedPoints,
+    ];
+}
+
+/// The polygons a face was made up of after one pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassSnapshot {
+    pub pass: BlockPass,
+    pub polygons: Vec<Vec<Point>>,
+}
+
+/// A street face followed through block extraction
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceTrace {
+    /// The face as it came out of the street graph
+    pub face: Vec<Point>,
+    /// The passes that finished, in order
+    pub passes: Vec<PassSnapshot>,
+    pub error: Option<GeometryError>,
+}
+
+impl FaceTrace {
+    /// The pass that returned the error, if extraction failed
+    pub fn failed_pass(&self) -> Option<BlockPass> {
+        self.error.as_ref()?;
+        BlockPass::ALL.get(self.passes.len()).copied()
+    }
+}
+
+/// Everything needed to run block extraction on a single face again, written with
+/// `write_face_dump`. To turn a broken face into a test, read the dump back with
+/// `read_face_dump` and check the trace `replay` returns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaceDump {
+    pub face: Vec<Point>,
+    pub blocks: BlockConfig,
+    /// What went wrong when the dump was taken, for reference
+    pub error: Option<String>,
+}
+
+impl FaceDump {
+    pub fn from_failure(failure: &FaceFailure, blocks: &BlockConfig) -> Self {
+        Self {
+            face: failure.face.clone(),
+            blocks: blocks.clone(),
+            error: Some(failure.error.to_string()),
+        }
+    }
+
+    /// Runs block extraction on the face again, keeping its polygons after every pass
+    pub fn replay(&self) -> FaceTrace {
+        trace_face(self.face.clone(), &self.blocks)
+    }
+}
+
+/// Writes the dump as pretty printed JSON
+pub fn write_face_dump(dump: &FaceDump, path: impl AsRef<Path>) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, dump).map_err(io::Error::other)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// Writes a dump of every failed face to the directory, `failed_face_0.json` for the first one
+pub fn export_failed_faces(
+    failures: &[FaceFailure],
+    blocks: &BlockConfig,
+    directory: impl AsRef<Path>,
+) -> io::Result<()> {
+    let directory = directory.as_ref();
+    fs::create_dir_all(directory)?;
+    for (index, failure) in failures.iter().enumerate() {
+        write_face_dump(
+            &FaceDump::from_failure(failure, blocks),
+            directory.join(format!("failed_face_{index}.json")),
+        )?;
+    }
+    Ok(())
+}
+
+pub fn read_face_dump(path: impl AsRef<Path>) -> io::Result<FaceDump> {
+    let reader = BufReader::new(File::open(path)?);
+    serde_json::from_reader(reader).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::BlockConfig;
+    use crate::street_graph::GeometryError;
+    use crate::test_support::square;
+
+    use super::{BlockPass, FaceDump, FaceTrace};
+
+    #[test]
+    fn replaying_a_dump_records_every_pass() {
+        let dump = FaceDump {
+            face: square(0.0, 40.0),
+            blocks: BlockConfig::default(),
+            error: None,
+        };
+        let json = serde_json::to_string(&dump).unwrap();
+        let read_back: FaceDump = serde_json::from_str(&json).unwrap();
+        assert_eq!(read_back, dump);
+
+        let trace = read_back.replay();
+
+        assert_eq!(trace.error, None);
+        assert_eq!(trace.failed_pass(), None);
+        assert_eq!(
+            trace
+                .passes
+                .iter()
+                .map(|snapshot| snapshot.pass)
+                .collect::<Vec<_>>(),
+            BlockPass::ALL
+        );
+        // Insetting shrinks the block by the inset distance on every side
+        let scaled = &trace.passes[2].polygons;
+        assert_eq!(scaled.len(), 1);
+        assert!(
+            scaled[0]
+                .iter()
+                .all(|vertex| vertex.x > 0.5 && vertex.x < 39.5)
+        );
+        assert_eq!(trace.passes.last().unwrap().polygons.len(), 1);
+    }
+
+    #[test]
+    fn the_failed_pass_follows_the_last_finished_one() {
+        let dump = FaceDump {
+            face: square(0.0, 40.0),
+            blocks: BlockConfig::default(),
+            error: None,
+        };
+        let mut trace = dump.replay();
+        trace.passes.truncate(3);
+        assert_eq!(trace.failed_pass(), None);
+
+        trace.error = Some(GeometryError::NoFaces);
+        assert_eq!(trace.failed_pass(), Some(BlockPass::NonManifoldFixed));
+
+        let unfinished = FaceTrace {
+            face: square(0.0, 1.0),
+            passes: Vec::new(),
+            error: Some(GeometryError::NoFaces),
+        };
+        assert_eq!(unfinished.failed_pass(), Some(BlockPass::DegeneratePoints));
+    }
+}
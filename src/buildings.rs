@@ -0,0 +1,469 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::config::BuildingConfig;
+use crate::parcels::Parcel;
+use crate::predicates::segments_intersect;
+use crate::street_graph::{face_area, scale_face};
+use crate::tensor_field::Point;
+use crate::triangulation::contains_point;
+use crate::zoning::LandUse;
+
+/// How many times the footprint rectangle is shrunk toward the middle of the lot while looking
+/// for a size that fits inside the setbacks
+const FIT_ATTEMPTS: usize = 24;
+const FIT_SHRINK_FACTOR: f32 = 0.9;
+
+/// Buildings smaller than this are not worth placing
+const MIN_FOOTPRINT_AREA: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FootprintShape {
+    Rectangle,
+    /// A bar along the street with a wing running back along one side
+    L,
+    /// A bar along the street with wings running back along both sides
+    U,
+    /// A ring of building around an open yard
+    Courtyard,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Building {
+    /// Index of the parcel in `City::parcels` the building stands on
+    pub parcel: usize,
+    /// Land use of the block the parcel was cut from
+    pub land_use: LandUse,
+    pub shape: FootprintShape,
+    /// Counter-clockwise outline of the footprint
+    pub outline: Vec<Point>,
+    /// Clockwise outlines of open spaces inside the footprint, only courtyards have one
+    pub holes: Vec<Vec<Point>>,
+    pub height: f32,
+}
+
+impl Building {
+    pub fn footprint_area(&self) -> f32 {
+        face_area(&self.outline) + self.holes.iter().map(|hole| face_area(hole)).sum::<f32>()
+    }
+}
+
+/// The rectangle a footprint is laid out in, as a corner on the street side, a unit vector along
+/// the street, a unit vector into the lot and the rectangle's extent along both of them
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    origin: Point,
+    along: Point,
+    inward: Point,
+    width: f32,
+    depth: f32,
+}
+
+impl Frame {
+    fn point_at(&self, u: f32, v: f32) -> Point {
+        self.origin + self.along * u + self.inward * v
+    }
+
+    fn corners(&self) -> [Point; 4] {
+        [
+            self.point_at(0.0, 0.0),
+            self.point_at(self.width, 0.0),
+            self.point_at(self.width, self.depth),
+            self.point_at(0.0, self.depth),
+        ]
+    }
+
+    /// The same frame scaled by `factor` around its middle
+    fn shrunk(&self, factor: f32) -> Frame {
+        let middle = self.point_at(self.width / 2.0, self.depth / 2.0);
+        let (width, depth) = (self.width * factor, self.depth * factor);
+        Frame {
+            origin: middle - self.along * width / 2.0 - self.inward * depth / 2.0,
+            width,
+            depth,
+            ..*self
+        }
+    }
+}
+
+/// Places one building on every parcel that has room for one.
+///
+/// Each lot is first pulled in by `side_setback` all around and then by `front_setback` from its
+/// street, and the largest rectangle aligned with the street that fits in what's left becomes
+/// the space the footprint is laid out in. How much of the lot the building covers and how tall
+/// it may be both fall off from the values at the city center to the ones at the edge over
+/// `falloff_distance`, and the heights are scaled by the factor of the level of the street the
+/// lot faces, so buildings along major streets stand out, and by the factor of the land use of
+/// its block. Parks stay empty. Shapes are picked at random among the ones that fit, seeded per
+/// parcel so the same parcels always get the same buildings.
+///
+/// `land_uses` holds the land use of every block, parcels of blocks past its end are treated as
+/// residential.
+pub fn generate_buildings(
+    parcels: &[Parcel],
+    land_uses: &[LandUse],
+    city_center: Point,
+    config: &BuildingConfig,
+) -> Vec<Building> {
+    parcels
+        .iter()
+        .enumerate()
+        .filter_map(|(parcel_index, parcel)| {
+            let land_use = land_uses
+                .get(parcel.block)
+                .copied()
+                .unwrap_or(LandUse::Residential);
+            if land_use == LandUse::Park {
+                return None;
+            }
+
+            let mut rng = ChaCha8Rng::seed_from_u64(config.seed.wrapping_add(parcel_index as u64));
+
+            let frame = footprint_frame(parcel, config)?;
+
+            let centrality = 1.0
+                - ((centroid(&parcel.polygon) - city_center).norm() / config.falloff_distance)
+                    .clamp(0.0, 1.0);
+            let lerp = |edge: f32, center: f32| edge + (center - edge) * centrality;
+
+            let coverage = lerp(config.coverage_at_edge, config.coverage_at_center);
+            let target_area =
+                (coverage * face_area(&parcel.polygon).abs()).min(frame.width * frame.depth);
+            if target_area < MIN_FOOTPRINT_AREA {
+                return None;
+            }
+
+            let shapes: Vec<(FootprintShape, f32)> = [
+                FootprintShape::Rectangle,
+                FootprintShape::L,
+                FootprintShape::U,
+                FootprintShape::Courtyard,
+            ]
+            .into_iter()
+            .filter_map(|shape| {
+                wing_width(shape, frame.width, frame.depth, target_area)
+                    .filter(|&wing| {
+                        shape == FootprintShape::Rectangle || wing >= config.min_wing_width
+                    })
+                    .map(|wing| (shape, wing))
+            })
+            .collect();
+            let (shape, wing) = shapes[rng.random_range(0..shapes.len())];
+
+            let level_factor = config
+                .level_height_factors
+                .get(parcel.street.level)
+                .copied()
+                .unwrap_or(1.0)
+                * config
+                    .land_use_height_factors
+                    .get(&land_use)
+                    .copied()
+                    .unwrap_or(1.0);
+            let min_height = lerp(config.height_at_edge[0], config.height_at_center[0]);
+            let max_height = lerp(config.height_at_edge[1], config.height_at_center[1]);
+            let height = if max_height > min_height {
+                rng.random_range(min_height..max_height)
+            } else {
+                min_height
+            } * level_factor;
+
+            let (outline, holes) = lay_out_footprint(shape, &frame, wing);
+
+            Some(Building {
+                parcel: parcel_index,
+                land_use,
+                shape,
+                outline,
+                holes,
+                height,
+            })
+        })
+        .collect()
+}
+
+fn centroid(points: &[Point]) -> Point {
+    points.iter().sum::<Point>() / points.len() as f32
+}
+
+/// The largest street aligned rectangle, shrunk in steps toward the middle of the lot, that fits
+/// inside the setbacks
+fn footprint_frame(parcel: &Parcel, config: &BuildingConfig) -> Option<Frame> {
+    let [front_start, front_end] = parcel.front;
+    let along = (front_end - front_start).try_normalize(f32::EPSILON)?;
+
+    let area = face_area(&parcel.polygon);
+    // `scale_face` moves every edge to its left, which is inward for counter-clockwise polygons
+    let orientation = area.signum();
+    let buildable = scale_face(parcel.polygon.clone(), config.side_setback * orientation);
+    if face_area(&buildable) * orientation < MIN_FOOTPRINT_AREA {
+        return None;
+    }
+
+    let left = Point::new(-along.y, along.x);
+    let inward = if (centroid(&parcel.polygon) - front_start).dot(&left) >= 0.0 {
+        left
+    } else {
+        -left
+    };
+
+    let (mut u_min, mut u_max, mut v_max) = (f32::MAX, f32::MIN, f32::MIN);
+    for point in &buildable {
+        let offset = point - front_start;
+        u_min = u_min.min(offset.dot(&along));
+        u_max = u_max.max(offset.dot(&along));
+        v_max = v_max.max(offset.dot(&inward));
+    }
+    let v_min = config.front_setback;
+    if u_max <= u_min || v_max <= v_min {
+        return None;
+    }
+
+    let mut frame = Frame {
+        origin: front_start + along * u_min + inward * v_min,
+        along,
+        inward,
+        width: u_max - u_min,
+        depth: v_max - v_min,
+    };
+
+    for _ in 0..FIT_ATTEMPTS {
+        if rectangle_inside_polygon(&frame.corners(), &buildable) {
+            return Some(frame);
+        }
+        frame = frame.shrunk(FIT_SHRINK_FACTOR);
+    }
+
+    None
+}
+
+fn rectangle_inside_polygon(corners: &[Point; 4], polygon: &[Point]) -> bool {
+    corners
+        .iter()
+        .all(|&corner| contains_point(polygon, corner))
+        && (0..4).all(|i| {
+            let side = [corners[i], corners[(i + 1) % 4]];
+            (0..polygon.len())
+                .all(|j| !segments_intersect(side, [polygon[j], polygon[(j + 1) % polygon.len()]]))
+        })
+}
+
+/// How thick the wings of the shape have to be for it to cover `area` inside a `width` by `depth`
+/// rectangle, or `None` if the shape can't cover that area there. For rectangles this is the
+/// depth of the building instead.
+fn wing_width(shape: FootprintShape, width: f32, depth: f32, area: f32) -> Option<f32> {
+    // Smaller root of `a t^2 - b t + area = 0`
+    let smaller_root = |a: f32, b: f32| {
+        let discriminant = b * b - 4.0 * a * area;
+        (discriminant >= 0.0).then(|| (b - discriminant.sqrt()) / (2.0 * a))
+    };
+
+    match shape {
+        FootprintShape::Rectangle => Some((area / width).min(depth)),
+        // w t + t (d - t)
+        FootprintShape::L => smaller_root(1.0, width + depth).filter(|&t| t < width.min(depth)),
+        // w t + 2 t (d - t)
+        FootprintShape::U => {
+            smaller_root(2.0, width + 2.0 * depth).filter(|&t| 2.0 * t < width && t < depth)
+        }
+        // w d - (w - 2 t) (d - 2 t)
+        FootprintShape::Courtyard => {
+            smaller_root(4.0, 2.0 * (width + depth)).filter(|&t| 2.0 * t < width.min(depth))
+        }
+    }
+}
+
+/// A ring of points in the coordinates of a `Frame`
+type FrameRing = Vec<(f32, f32)>;
+
+fn lay_out_footprint(
+    shape: FootprintShape,
+    frame: &Frame,
+    wing: f32,
+) -> (Vec<Point>, Vec<Vec<Point>>) {
+    let (w, d, t) = (frame.width, frame.depth, wing);
+
+    let (outline, holes): (FrameRing, Vec<FrameRing>) = match shape {
+        FootprintShape::Rectangle => (vec![(0.0, 0.0), (w, 0.0), (w, t), (0.0, t)], Vec::new()),
+        FootprintShape::L => (
+            vec![(0.0, 0.0), (w, 0.0), (w, t), (t, t), (t, d), (0.0, d)],
+            Vec::new(),
+        ),
+        FootprintShape::U => (
+            vec![
+                (0.0, 0.0),
+                (w, 0.0),
+                (w, d),
+                (w - t, d),
+                (w - t, t),
+                (t, t),
+                (t, d),
+                (0.0, d),
+            ],
+            Vec::new(),
+        ),
+        FootprintShape::Courtyard => (
+            vec![(0.0, 0.0), (w, 0.0), (w, d), (0.0, d)],
+            vec![vec![(t, t), (t, d - t), (w - t, d - t), (w - t, t)]],
+        ),
+    };
+
+    let to_world = |ring: FrameRing| -> Vec<Point> {
+        ring.into_iter()
+            .map(|(u, v)| frame.point_at(u, v))
+            .collect()
+    };
+
+    // The rings are counter-clockwise in the frame, which is mirrored when the lot lies to the
+    // right of its street
+    let mirrored = frame.along.perp(&frame.inward) < 0.0;
+    let orient = |mut ring: Vec<Point>| {
+        if mirrored {
+            ring.reverse();
+        }
+        ring
+    };
+
+    (
+        orient(to_world(outline)),
+        holes.into_iter().map(to_world).map(orient).collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::BuildingConfig;
+    use crate::parcels::{Parcel, StreetId};
+    use crate::street_graph::face_area;
+    use crate::tensor_field::Point;
+    use crate::zoning::LandUse;
+
+    use super::{FootprintShape, generate_buildings, wing_width};
+
+    fn square_parcel(offset: Point, size: f32, clockwise: bool) -> Parcel {
+        let mut polygon = vec![
+            offset,
+            offset + Point::new(size, 0.0),
+            offset + Point::new(size, size),
+            offset + Point::new(0.0, size),
+        ];
+        if clockwise {
+            polygon.reverse();
+        }
+        Parcel {
+            polygon,
+            front: [offset, offset + Point::new(size, 0.0)],
+            block: 0,
+            street: StreetId { level: 0, index: 0 },
+            frontage: size,
+        }
+    }
+
+    #[test]
+    fn wing_widths_cover_the_requested_area() {
+        let (width, depth, area) = (20.0, 15.0, 150.0);
+
+        let l = wing_width(FootprintShape::L, width, depth, area).unwrap();
+        assert!((width * l + l * (depth - l) - area).abs() < 0.01);
+
+        let u = wing_width(FootprintShape::U, width, depth, area).unwrap();
+        assert!((width * u + 2.0 * u * (depth - u) - area).abs() < 0.01);
+
+        let ring = wing_width(FootprintShape::Courtyard, width, depth, area).unwrap();
+        assert!((width * depth - (width - 2.0 * ring) * (depth - 2.0 * ring) - area).abs() < 0.01);
+
+        assert_eq!(
+            wing_width(FootprintShape::Courtyard, width, depth, width * depth + 1.0),
+            None
+        );
+    }
+
+    #[test]
+    fn buildings_stay_inside_their_setbacks() {
+        let config = BuildingConfig::default();
+        let parcels = vec![
+            square_parcel(Point::new(0.0, 0.0), 20.0, false),
+            square_parcel(Point::new(100.0, 0.0), 20.0, true),
+        ];
+
+        let buildings = generate_buildings(
+            &parcels,
+            &[LandUse::Residential],
+            Point::new(10.0, 10.0),
+            &config,
+        );
+
+        assert_eq!(buildings.len(), 2);
+        for building in &buildings {
+            let offset = if building.parcel == 0 { 0.0 } else { 100.0 };
+            assert!(face_area(&building.outline) > 0.0);
+            for hole in &building.holes {
+                assert!(face_area(hole) < 0.0);
+            }
+            for point in &building.outline {
+                assert!(point.x >= offset + config.side_setback - 0.001);
+                assert!(point.x <= offset + 20.0 - config.side_setback + 0.001);
+                assert!(point.y >= config.front_setback - 0.001);
+                assert!(point.y <= 20.0 - config.side_setback + 0.001);
+            }
+        }
+    }
+
+    #[test]
+    fn buildings_are_taller_and_denser_in_the_center() {
+        let config = BuildingConfig::default();
+        let center = Point::new(10.0, 10.0);
+        let parcels = vec![
+            square_parcel(Point::new(0.0, 0.0), 20.0, false),
+            square_parcel(Point::new(2000.0, 0.0), 20.0, false),
+        ];
+
+        let buildings = generate_buildings(&parcels, &[LandUse::Residential], center, &config);
+
+        assert_eq!(buildings.len(), 2);
+        assert!(buildings[0].height > buildings[1].height);
+        assert!(buildings[0].footprint_area() > buildings[1].footprint_area());
+    }
+
+    #[test]
+    fn land_use_scales_heights_and_keeps_parks_empty() {
+        let config = BuildingConfig {
+            land_use_height_factors: [(LandUse::Commercial, 1.5), (LandUse::Industrial, 0.2)]
+                .into(),
+            ..BuildingConfig::default()
+        };
+        let center = Point::new(10.0, 10.0);
+        let parcels: Vec<Parcel> = (0..3)
+            .map(|block| Parcel {
+                block,
+                ..square_parcel(Point::new(0.0, 0.0), 20.0, false)
+            })
+            .collect();
+
+        let buildings = generate_buildings(
+            &parcels,
+            &[LandUse::Commercial, LandUse::Industrial, LandUse::Park],
+            center,
+            &config,
+        );
+
+        assert_eq!(buildings.len(), 2);
+        assert_eq!(buildings[0].land_use, LandUse::Commercial);
+        assert_eq!(buildings[1].land_use, LandUse::Industrial);
+        assert!(buildings[0].height > buildings[1].height);
+    }
+
+    #[test]
+    fn tiny_parcels_get_no_building() {
+        let parcels = vec![square_parcel(Point::new(0.0, 0.0), 2.0, false)];
+
+        let buildings = generate_buildings(
+            &parcels,
+            &[LandUse::Residential],
+            Point::zeros(),
+            &BuildingConfig::default(),
+        );
+
+        assert!(buildings.is_empty());
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::config::BusConfig;
+use crate::street_network::StreetNetwork;
+use crate::tensor_field::Point;
+
+/// Stops of different routes closer together than this are merged into one
+const STOP_MERGE_DISTANCE: f32 = 1.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BusStop {
+    pub position: Point,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BusRoute {
+    /// The street vertices the route runs along, from one terminal to the other
+    pub path: Vec<Point>,
+    /// Closed loops around a block at both terminals, starting and ending at the terminal, that
+    /// let buses turn around. Empty at terminals where no loop short enough was found.
+    pub terminal_loops: [Vec<Point>; 2],
+    /// Indices into `BusNetwork::stops` in the order the route serves them
+    pub stops: Vec<usize>,
+}
+
+impl BusRoute {
+    /// The whole way a bus drives: the loop at the first terminal, the route, and the loop at
+    /// the other terminal
+    pub fn shape(&self) -> Vec<Point> {
+        let mut shape = self.terminal_loops[0].clone();
+        shape.extend(self.path.iter().skip(usize::from(!shape.is_empty())));
+        shape.extend(self.terminal_loops[1].iter().skip(1));
+        shape
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BusNetwork {
+    pub stops: Vec<BusStop>,
+    pub routes: Vec<BusRoute>,
+    /// Share of the blocks within walking distance of a stop
+    pub coverage: f32,
+}
+
+/// Lays out bus routes along the streets of the first `max_street_level + 1` levels.
+///
+/// Routes are added one at a time until enough blocks are within `walk_distance` of a stop or
+/// `max_routes` is reached. Each route starts at the arterial vertex with the most blocks not yet
+/// covered around it and runs along the shortest arterial path to whichever far enough vertex
+/// covers the most new blocks, preferring ends with a block to turn around on. Stops go on
+/// intersections once `min_stop_spacing` has been driven since the last one, and in the middle of a
+/// street when `max_stop_spacing` would be passed otherwise. Both terminals get the shortest loop
+/// around a nearby block, over streets of any level, for buses to turn around on.
+///
+/// `block_centroids` are the points that count as the blocks for coverage.
+pub fn plan_bus_routes(
+    network: &StreetNetwork,
+    block_centroids: &[Point],
+    config: &BusConfig,
+) -> BusNetwork {
+    let is_arterial = |edge_level: usize| edge_level <= config.max_street_level;
+    let arterial_vertices: Vec<usize> = (0..network.vertices.len())
+        .filter(|&vertex| {
+            network.adjacency[vertex]
+                .iter()
+                .any(|&(_, edge)| is_arterial(network.edges[edge].level))
+        })
+        .collect();
+
+    let walk_squared = config.walk_distance * config.walk_distance;
+    let blocks_near = |point: Point| -> Vec<usize> {
+        (0..block_centroids.len())
+            .filter(|&block| (block_centroids[block] - point).norm_squared() <= walk_squared)
+            .collect()
+    };
+
+    let mut stops: Vec<BusStop> = Vec::new();
+    let mut routes: Vec<BusRoute> = Vec::new();
+    let mut covered = vec![false; block_centroids.len()];
+    let target = (config.target_coverage * block_centroids.len() as f32).ceil() as usize;
+    let mut covered_count = 0;
+    let mut tried_starts: HashSet<usize> = HashSet::new();
+
+    while covered_count < target && routes.len() < config.max_routes {
+        let Some(start) = arterial_vertices
+            .iter()
+            .copied()
+            .filter(|vertex| !tried_starts.contains(vertex))
+            .map(|vertex| {
+                let uncovered = blocks_near(network.vertices[vertex])
+                    .into_iter()
+                    .filter(|&block| !covered[block])
+                    .count();
+                (vertex, uncovered)
+            })
+            .filter(|&(_, uncovered)| uncovered > 0)
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(vertex, _)| vertex)
+        else {
+            break;
+        };
+        tried_starts.insert(start);
+
+        let paths = network.shortest_paths(start, |_, edge| is_arterial(edge.level));
+        let mut candidates: Vec<(Vec<usize>, Vec<Point>, HashSet<usize>)> = arterial_vertices
+            .iter()
+            .copied()
+            .filter(|&end| paths.distances[end] >= config.min_route_length)
+            .filter_map(|end| paths.path_to(end))
+            .filter(|vertex_path| vertex_path.len() >= 2)
+            .map(|vertex_path| {
+                let path: Vec<Point> = vertex_path
+                    .iter()
+                    .map(|&vertex| network.vertices[vertex])
+                    .collect();
+                let route_stops = place_stops(&path, config);
+                let newly_covered: HashSet<usize> = route_stops
+                    .iter()
+                    .flat_map(|&stop| blocks_near(stop))
+                    .filter(|&block| !covered[block])
+                    .collect();
+                (vertex_path, route_stops, newly_covered)
+            })
+            .filter(|(_, _, newly_covered)| !newly_covered.is_empty())
+            .collect();
+        candidates.sort_by(|a, b| b.2.len().cmp(&a.2.len()).then(a.0.last().cmp(&b.0.last())));
+
+        // Ends at the edge of the city often have no block to turn around on, so the route runs
+        // to the best end that has one, and only to a dead end when none does
+        let end_loop = |vertex_path: &[usize]| {
+            terminal_loop(
+                network,
+                vertex_path[vertex_path.len() - 1],
+                Some(vertex_path[vertex_path.len() - 2]),
+                config,
+            )
+        };
+        let Some((chosen, end_loop)) = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, (vertex_path, _, _))| (i, end_loop(vertex_path)))
+            .find(|(_, end_loop)| !end_loop.is_empty())
+            .or_else(|| {
+                let first = candidates.first()?;
+                Some((0, end_loop(&first.0)))
+            })
+        else {
+            continue;
+        };
+        let (vertex_path, route_stops, newly_covered) = candidates.swap_remove(chosen);
+
+        for block in newly_covered {
+            covered[block] = true;
+            covered_count += 1;
+        }
+
+        let route_stop_indices = route_stops
+            .into_iter()
+            .map(|position| {
+                stops
+                    .iter()
+                    .position(|stop| (stop.position - position).norm() < STOP_MERGE_DISTANCE)
+                    .unwrap_or_else(|| {
+                        stops.push(BusStop { position });
+                        stops.len() - 1
+                    })
+            })
+            .collect();
+
+        routes.push(BusRoute {
+            terminal_loops: [
+                terminal_loop(network, start, Some(vertex_path[1]), config),
+                end_loop,
+            ],
+            path: vertex_path
+                .iter()
+                .map(|&vertex| network.vertices[vertex])
+                .collect(),
+            stops: route_stop_indices,
+        });
+    }
+
+    BusNetwork {
+        stops,
+        routes,
+        coverage: if block_centroids.is_empty() {
+            0.0
+        } else {
+            covered_count as f32 / block_centroids.len() as f32
+        },
+    }
+}
+
+/// Stop positions along the path, both terminals included
+fn place_stops(path: &[Point], config: &BusConfig) -> Vec<Point> {
+    let Some(&first) = path.first() else {
+        return Vec::new();
+    };
+    let mut stops = vec![first];
+    let mut since_last = 0.0;
+    // Distance between the last two stops
+    let mut last_gap = 0.0;
+
+    for window in path.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        let length = (end - start).norm();
+        let mut travelled = 0.0;
+        while since_last + (length - travelled) > config.max_stop_spacing {
+            travelled += config.max_stop_spacing - since_last;
+            stops.push(start + (end - start) * (travelled / length));
+            (last_gap, since_last) = (config.max_stop_spacing, 0.0);
+        }
+        since_last += length - travelled;
+        if since_last >= config.min_stop_spacing {
+            stops.push(end);
+            (last_gap, since_last) = (since_last, 0.0);
+        }
+    }
+
+    let last = path[path.len() - 1];
+    if stops.last() != Some(&last) {
+        // The last stop before the terminal moves to the terminal when it would be too close,
+        // unless that leaves too long a gap behind it
+        if stops.len() > 1
+            && since_last < config.min_stop_spacing
+            && last_gap + since_last <= config.max_stop_spacing
+        {
+            stops.pop();
+        }
+        stops.push(last);
+    }
+    stops
+}
+
+/// The shortest closed walk from the terminal that leaves along a street other than the one the
+/// route arrives on and comes back along another one, or an empty loop if there is none within
+/// `max_loop_length`
+fn terminal_loop(
+    network: &StreetNetwork,
+    terminal: usize,
+    route_neighbour: Option<usize>,
+    config: &BusConfig,
+) -> Vec<Point> {
+    let mut best: Option<(f32, Vec<usize>)> = None;
+    for &(first, first_edge) in &network.adjacency[terminal] {
+        if Some(first) == route_neighbour {
+            continue;
+        }
+        let paths = network.shortest_paths(first, |edge_index, _| edge_index != first_edge);
+        let length = paths.distances[terminal] + network.edges[first_edge].length;
+        if length > config.max_loop_length || best.as_ref().is_some_and(|(best, _)| length >= *best)
+        {
+            continue;
+        }
+        let Some(back) = paths.path_to(terminal) else {
+            continue;
+        };
+        // Coming back the way the loop left isn't a loop
+        if back.len() < 3 {
+            continue;
+        }
+        let mut walk = vec![terminal];
+        walk.extend(back);
+        best = Some((length, walk));
+    }
+
+    best.map(|(_, walk)| {
+        walk.into_iter()
+            .map(|vertex| network.vertices[vertex])
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Writes the routes as CSV files in the layout of a GTFS feed, with positions in city
+/// coordinates instead of latitudes and longitudes: `stops.txt`, `routes.txt`, `shapes.txt` and
+/// `route_stops.txt` with the order every route serves its stops in.
+pub fn export_bus_feed(network: &BusNetwork, directory: impl AsRef<Path>) -> io::Result<()> {
+    let directory = directory.as_ref();
+    fs::create_dir_all(directory)?;
+    let create = |name: &str| File::create(directory.join(name)).map(BufWriter::new);
+
+    let mut stops = create("stops.txt")?;
+    writeln!(stops, "stop_id,stop_name,stop_x,stop_y")?;
+    for (i, stop) in network.stops.iter().enumerate() {
+        writeln!(
+            stops,
+            "S{i},Stop {},{:.3},{:.3}",
+            i + 1,
+            stop.position.x,
+            stop.position.y
+        )?;
+    }
+    stops.flush()?;
+
+    let mut routes = create("routes.txt")?;
+    // 3 is the GTFS route type of buses
+    writeln!(routes, "route_id,route_short_name,route_type,shape_id")?;
+    for i in 0..network.routes.len() {
+        writeln!(routes, "R{i},{},3,SH{i}", i + 1)?;
+    }
+    routes.flush()?;
+
+    let mut route_stops = create("route_stops.txt")?;
+    writeln!(route_stops, "route_id,stop_sequence,stop_id")?;
+    for (i, route) in network.routes.iter().enumerate() {
+        for (sequence, stop) in route.stops.iter().enumerate() {
+            writeln!(route_stops, "R{i},{sequence},S{stop}")?;
+        }
+    }
+    route_stops.flush()?;
+
+    let mut shapes = create("shapes.txt")?;
+    writeln!(
+        shapes,
+        "shape_id,shape_pt_x,shape_pt_y,shape_pt_sequence,shape_dist_traveled"
+    )?;
+    for (i, route) in network.routes.iter().enumerate() {
+        let mut travelled = 0.0;
+        let shape = route.shape();
+        for (sequence, point) in shape.iter().enumerate() {
+            if sequence > 0 {
+                travelled += (point - shape[sequence - 1]).norm();
+            }
+            writeln!(
+                shapes,
+                "SH{i},{:.3},{:.3},{sequence},{travelled:.3}",
+                point.x, point.y
+            )?;
+        }
+    }
+    shapes.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::BusConfig;
+    use crate::street_network::StreetNetwork;
+    use crate::tensor_field::Point;
+    use crate::test_support::curve;
+
+    use super::{place_stops, plan_bus_routes};
+
+    #[test]
+    fn stops_keep_their_spacing() {
+        let config = BusConfig {
+            min_stop_spacing: 10.0,
+            max_stop_spacing: 25.0,
+            ..BusConfig::default()
+        };
+        let path = [
+            Point::new(0.0, 0.0),
+            Point::new(4.0, 0.0),
+            Point::new(15.0, 0.0),
+            Point::new(70.0, 0.0),
+            Point::new(75.0, 0.0),
+        ];
+
+        let stops = place_stops(&path, &config);
+
+        assert_eq!(stops.first(), Some(&path[0]));
+        assert_eq!(stops.last(), Some(&path[4]));
+        for window in stops.windows(2) {
+            let gap = (window[1] - window[0]).norm();
+            assert!(gap >= config.min_stop_spacing - 0.001, "{stops:?}");
+            assert!(gap <= config.max_stop_spacing + 0.001, "{stops:?}");
+        }
+    }
+
+    #[test]
+    fn routes_follow_arterials_and_loop_at_the_ends() {
+        // A major avenue with a ring of minor streets around a block at either end
+        let levels = vec![
+            vec![curve(&[(0.0, 0.0), (200.0, 0.0)])],
+            vec![
+                curve(&[
+                    (0.0, 0.0),
+                    (0.0, 20.0),
+                    (-20.0, 20.0),
+                    (-20.0, 0.0),
+                    (0.0, 0.0),
+                ]),
+                curve(&[
+                    (200.0, 0.0),
+                    (200.0, 20.0),
+                    (220.0, 20.0),
+                    (220.0, 0.0),
+                    (200.0, 0.0),
+                ]),
+            ],
+        ];
+        let network = StreetNetwork::from_levels(&levels).unwrap();
+        let blocks: Vec<Point> = (0..10)
+            .map(|i| Point::new(i as f32 * 20.0 + 10.0, 10.0))
+            .collect();
+        let config = BusConfig {
+            min_route_length: 150.0,
+            ..BusConfig::default()
+        };
+
+        let buses = plan_bus_routes(&network, &blocks, &config);
+
+        assert_eq!(buses.routes.len(), 1);
+        assert_eq!(buses.coverage, 1.0);
+        let route = &buses.routes[0];
+        assert!(route.path.iter().all(|point| point.y == 0.0));
+        for terminal_loop in &route.terminal_loops {
+            assert_eq!(terminal_loop.len(), 5);
+            assert_eq!(terminal_loop.first(), terminal_loop.last());
+        }
+        let shape = route.shape();
+        assert_eq!(shape.len(), 5 + route.path.len() - 1 + 4);
+    }
+}
@@ -0,0 +1,496 @@
+use std::collections::HashMap;
+
+use crate::block_trace::FaceTrace;
+use crate::buildings::{Building, generate_buildings};
+use crate::bus_routes::{BusNetwork, plan_bus_routes};
+use crate::config::{CityConfig, SeedConfig};
+use crate::history::{GenerationHistory, LevelHistory};
+use crate::metro::{Metro, plan_metro};
+use crate::parcels::{Parcel, subdivide_blocks};
+use crate::road_surfaces::{RoadSurfaces, build_road_surfaces};
+use crate::street_graph::{Blocks, FaceFailure, GeometryError, face_area, path_to_graph};
+use crate::street_network::StreetNetwork;
+use crate::street_plan::{
+    HermiteCurve, SeedPoint, TraceSeeds, debug_seed_points, follows_major_eigenvectors,
+    merge_road_endings_onto, prioritize_points, trace_street_plan,
+};
+use crate::tensor_field::{Point, TensorField};
+use crate::triangulation::{Triangulation, contains_point, triangulate};
+use crate::zoning::{LandUse, zone_blocks};
+
+const SURFACE_BUCKET_SIZE: f32 = 16.0;
+
+#[derive(Debug)]
+pub struct City {
+    pub tensor_field: TensorField,
+    pub city_center: Point,
+    /// The merged streets of every level in the config, in the same order as `CityConfig::levels`.
+    /// Fixed streets the city was generated around come first in the first level.
+    pub levels: Vec<Vec<HermiteCurve>>,
+    /// The points every level started tracing from, in the same order as `levels`
+    pub seeds: Vec<Vec<Point>>,
+    /// The streets of every level as a graph split at every crossing
+    pub street_network: StreetNetwork,
+    pub blocks: Vec<Vec<Point>>,
+    /// The filled area of every block, in the same order as `blocks`
+    pub block_triangulations: Vec<Triangulation>,
+    /// The land use of every block, in the same order as `blocks`
+    pub land_uses: Vec<LandUse>,
+    /// Roads, junctions and sidewalks tiling the space between the streets
+    pub road_surfaces: RoadSurfaces,
+    /// The block in `blocks` that every block of `road_surfaces` lies on, in the same order as
+    /// `RoadSurfaces::blocks`
+    pub surface_block_indices: Vec<Option<usize>>,
+    /// The lots every block was cut into, each one facing a street
+    pub parcels: Vec<Parcel>,
+    /// At most one building per parcel
+    pub buildings: Vec<Building>,
+    pub metro: Metro,
+    /// Bus routes along the major streets
+    pub buses: BusNetwork,
+    /// Street faces that were skipped because block extraction failed on them
+    pub failed_faces: Vec<FaceFailure>,
+    /// Parts of the city that could not be built and were left empty
+    pub failed_stages: Vec<StageFailure>,
+    /// Every street face followed through block extraction, empty unless
+    /// `BlockConfig::trace_passes` is set
+    pub block_traces: Vec<FaceTrace>,
+}
+
+/// The parts of the city that are built from the arrangement of all of the streets. When that
+/// arrangement can't be built the part is left empty and the rest of the city is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineStage {
+    /// `City::street_network`, along with the bus routes planned on it
+    StreetNetwork,
+    /// `City::road_surfaces`
+    RoadSurfaces,
+}
+
+impl std::fmt::Display for PipelineStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineStage::StreetNetwork => write!(f, "street network"),
+            PipelineStage::RoadSurfaces => write!(f, "road surfaces"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StageFailure {
+    pub stage: PipelineStage,
+    pub error: GeometryError,
+}
+
+impl City {
+    pub fn streets(&self) -> impl Iterator<Item = &HermiteCurve> {
+        self.levels.iter().flatten()
+    }
+
+    /// Index of the block in `blocks` that covers the point
+    pub fn block_at(&self, point: Point) -> Option<usize> {
+        self.block_triangulations
+            .iter()
+            .position(|triangulation| triangulation.contains(point))
+    }
+}
+
+/// Runs the whole pipeline: builds the tensor field, traces every street level on top of the
+/// previous ones, extracts, triangulates and zones the blocks between all of the streets, plans
+/// the metro and the bus routes, lays out the road surfaces, cuts the blocks into parcels and
+/// places the buildings. The config is expected to have been validated already, which
+/// `CityConfig::load` takes care of.
+pub fn generate_city(config: &CityConfig) -> Result<City, GeometryError> {
+    build_city(config, &[], None)
+}
+
+/// Generates the city like `generate_city` around streets that are already there, such as the
+/// ones imported from OpenStreetMap with `osm::highway_curves`. Every level is traced with the
+/// fixed streets as previous curves and may join its street ends onto them, but they are never
+/// moved themselves.
+pub fn generate_city_around(
+    config: &CityConfig,
+    fixed_streets: &[HermiteCurve],
+) -> Result<City, GeometryError> {
+    build_city(config, fixed_streets, None)
+}
+
+/// Generates the city like `generate_city_around` while recording every iteration of street
+/// tracing, so the viewer can step through how the streets came about
+pub fn generate_city_with_history(
+    config: &CityConfig,
+    fixed_streets: &[HermiteCurve],
+) -> Result<(City, GenerationHistory), GeometryError> {
+    let mut history = GenerationHistory::default();
+    let city = build_city(config, fixed_streets, Some(&mut history))?;
+    Ok((city, history))
+}
+
+fn build_city(
+    config: &CityConfig,
+    fixed_streets: &[HermiteCurve],
+    history: Option<&mut GenerationHistory>,
+) -> Result<City, GeometryError> {
+    let city_center = config.city_center();
+
+    let tensor_field = TensorField::new(
+        config.field.design_elements.clone(),
+        config.field.decay_constant,
+    );
+
+    let (levels, seeds) = trace_levels(&tensor_field, config, city_center, fixed_streets, history);
+
+    let all_curves: Vec<HermiteCurve> = levels.iter().rev().flatten().cloned().collect();
+
+    let mut blocks = path_to_graph(&all_curves, &config.blocks)?;
+    blocks.faces.retain(|face| config.mask.allows_block(face));
+    let block_triangulations: Vec<Triangulation> = blocks
+        .faces
+        .iter()
+        .map(|face| triangulate(face, &[]))
+        .collect();
+    let contents = build_on_blocks(config, &levels, &blocks.faces, &block_triangulations);
+
+    Ok(assemble_city(
+        config,
+        tensor_field,
+        levels,
+        seeds,
+        blocks,
+        block_triangulations,
+        contents,
+    ))
+}
+
+/// The land uses, parcels and buildings on the blocks of a city
+pub(crate) struct BlockContents {
+    /// The land use of every block, in the same order as the blocks
+    pub(crate) land_uses: Vec<LandUse>,
+    pub(crate) parcels: Vec<Parcel>,
+    pub(crate) buildings: Vec<Building>,
+}
+
+/// Zones the blocks, cuts them into parcels and places the buildings. Every block is handled on
+/// its own, so the blocks can be a part of the city's, with `Parcel::block` and
+/// `Building::parcel` counting from the start of `blocks`.
+pub(crate) fn build_on_blocks(
+    config: &CityConfig,
+    levels: &[Vec<HermiteCurve>],
+    blocks: &[Vec<Point>],
+    block_triangulations: &[Triangulation],
+) -> BlockContents {
+    let city_center = config.city_center();
+    let land_uses = zone_blocks(
+        blocks,
+        block_triangulations,
+        levels,
+        city_center,
+        config.blocks.inset_distance,
+        &config.zoning,
+    );
+    let parcels = subdivide_blocks(
+        blocks,
+        levels,
+        &config.parcels,
+        config.blocks.inset_distance,
+    );
+    let buildings = generate_buildings(&parcels, &land_uses, city_center, &config.buildings);
+    BlockContents {
+        land_uses,
+        parcels,
+        buildings,
+    }
+}
+
+/// Plans everything that sits on top of the streets and the zoned blocks: the metro, the street
+/// network, the bus routes and the road surfaces. A street network or road surfaces that can't
+/// be built are left empty and recorded in `City::failed_stages`, the same way failed street
+/// faces only lose their own blocks.
+pub(crate) fn assemble_city(
+    config: &CityConfig,
+    tensor_field: TensorField,
+    levels: Vec<Vec<HermiteCurve>>,
+    seeds: Vec<Vec<Point>>,
+    blocks: Blocks,
+    block_triangulations: Vec<Triangulation>,
+    contents: BlockContents,
+) -> City {
+    let city_center = config.city_center();
+    let Blocks {
+        faces,
+        failures,
+        traces,
+    } = blocks;
+    let BlockContents {
+        land_uses,
+        parcels,
+        buildings,
+    } = contents;
+
+    let metro = plan_metro(
+        &block_triangulations,
+        &land_uses,
+        city_center,
+        &config.metro,
+    );
+
+    let mut failed_stages = Vec::new();
+    let mut recover = |stage: PipelineStage, error: GeometryError| {
+        failed_stages.push(StageFailure { stage, error });
+    };
+
+    let street_network = StreetNetwork::from_levels(&levels).unwrap_or_else(|error| {
+        recover(PipelineStage::StreetNetwork, error);
+        StreetNetwork::default()
+    });
+    let block_centroids: Vec<Point> = block_triangulations
+        .iter()
+        .filter_map(Triangulation::centroid)
+        .collect();
+    let buses = plan_bus_routes(&street_network, &block_centroids, &config.buses);
+
+    let road_surfaces = build_road_surfaces(&levels, &config.roads).unwrap_or_else(|error| {
+        recover(PipelineStage::RoadSurfaces, error);
+        RoadSurfaces::default()
+    });
+    let surface_block_indices = surface_block_indices(&road_surfaces.blocks, &block_triangulations);
+
+    City {
+        tensor_field,
+        city_center,
+        levels,
+        seeds,
+        street_network,
+        blocks: faces,
+        block_triangulations,
+        land_uses,
+        road_surfaces,
+        surface_block_indices,
+        parcels,
+        buildings,
+        metro,
+        buses,
+        failed_faces: failures,
+        failed_stages,
+        block_traces: traces,
+    }
+}
+
+pub(crate) fn trace_levels(
+    tensor_field: &TensorField,
+    config: &CityConfig,
+    city_center: Point,
+    fixed_streets: &[HermiteCurve],
+    mut history: Option<&mut GenerationHistory>,
+) -> (Vec<Vec<HermiteCurve>>, Vec<Vec<Point>>) {
+    let (mut previous_major_curves, mut previous_minor_curves): (Vec<_>, Vec<_>) = fixed_streets
+        .iter()
+        .cloned()
+        .partition(|curve| follows_major_eigenvectors(curve, tensor_field));
+    let fixed_curves: Vec<&HermiteCurve> = fixed_streets.iter().collect();
+    let mut levels: Vec<Vec<HermiteCurve>> = Vec::new();
+    let mut level_seeds: Vec<Vec<Point>> = Vec::new();
+
+    for level in &config.levels {
+        let seeds = match level.seeds {
+            SeedConfig::Random => TraceSeeds::Random,
+            SeedConfig::PreviousLevel => TraceSeeds::Specific(previous_level_seeds(
+                &previous_major_curves,
+                &previous_minor_curves,
+            )),
+        };
+        let seeds = if config.mask.is_empty() {
+            seeds
+        } else {
+            let seeds = match seeds {
+                TraceSeeds::Random => {
+                    prioritize_points(&debug_seed_points(), city_center, tensor_field).into_vec()
+                }
+                TraceSeeds::Specific(seeds) => seeds,
+            };
+            TraceSeeds::Specific(
+                seeds
+                    .into_iter()
+                    .filter(|seed| config.mask.allows(seed.seed))
+                    .collect(),
+            )
+        };
+
+        level_seeds.push(match &seeds {
+            TraceSeeds::Random => debug_seed_points(),
+            TraceSeeds::Specific(seeds) => seeds.iter().map(|seed| seed.seed).collect(),
+        });
+
+        let mut level_history = history.is_some().then(LevelHistory::default);
+        let (major_curves_unconnected, minor_curves_unconnected) = trace_street_plan(
+            tensor_field,
+            seeds,
+            city_center,
+            level.d_sep,
+            level.iterations,
+            previous_major_curves.clone(),
+            previous_minor_curves.clone(),
+            &config.tracing,
+            &config.smoothing,
+            level_history
+                .as_mut()
+                .map(|level_history| &mut level_history.iterations),
+        );
+
+        let major_curves_unconnected = config.mask.clip_curves(major_curves_unconnected);
+        let major_curves_len = major_curves_unconnected.len();
+
+        let curves_unconnected: Vec<HermiteCurve> = major_curves_unconnected
+            .into_iter()
+            .chain(config.mask.clip_curves(minor_curves_unconnected))
+            .collect();
+        let curves =
+            merge_road_endings_onto(&curves_unconnected, &fixed_curves, level.merge_distance);
+
+        if let (Some(history), Some(mut level_history)) = (history.as_deref_mut(), level_history) {
+            level_history.merged = curves.clone();
+            history.levels.push(level_history);
+        }
+
+        previous_major_curves.extend_from_slice(&curves[..major_curves_len]);
+        previous_minor_curves.extend_from_slice(&curves[major_curves_len..]);
+        if levels.is_empty() {
+            levels.push(fixed_streets.iter().cloned().chain(curves).collect());
+        } else {
+            levels.push(curves);
+        }
+    }
+
+    (levels, level_seeds)
+}
+
+/// Matches the blocks of the road surfaces, which follow the street graph, with the zoned blocks
+/// they hold. Each zoned block is placed by the center of its largest triangle and a surface
+/// block takes the largest of the zoned blocks placed in it. The surface blocks are bucketed by
+/// their bounds so every zoned block is only tested against the ones around it.
+fn surface_block_indices(
+    surface_blocks: &[Vec<Point>],
+    block_triangulations: &[Triangulation],
+) -> Vec<Option<usize>> {
+    let bucket_key = |point: Point| {
+        (
+            (point.x / SURFACE_BUCKET_SIZE).floor() as i64,
+            (point.y / SURFACE_BUCKET_SIZE).floor() as i64,
+        )
+    };
+    let mut buckets: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (surface_index, ring) in surface_blocks.iter().enumerate() {
+        let min = bucket_key(
+            ring.iter()
+                .fold(Point::repeat(f32::MAX), |min, p| min.inf(p)),
+        );
+        let max = bucket_key(
+            ring.iter()
+                .fold(Point::repeat(f32::MIN), |max, p| max.sup(p)),
+        );
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                buckets.entry((x, y)).or_default().push(surface_index);
+            }
+        }
+    }
+
+    let mut owners: Vec<Option<(f32, usize)>> = vec![None; surface_blocks.len()];
+    for (block_index, triangulation) in block_triangulations.iter().enumerate() {
+        let Some([a, b, c]) = triangulation
+            .triangle_points()
+            .max_by(|x, y| face_area(x).total_cmp(&face_area(y)))
+        else {
+            continue;
+        };
+        let probe = (a + b + c) / 3.0;
+        let Some(&surface_index) = buckets
+            .get(&bucket_key(probe))
+            .into_iter()
+            .flatten()
+            .find(|&&surface_index| contains_point(&surface_blocks[surface_index], probe))
+        else {
+            continue;
+        };
+
+        let area = triangulation.area();
+        let owner = &mut owners[surface_index];
+        if owner.is_none_or(|(owner_area, _)| area > owner_area) {
+            *owner = Some((area, block_index));
+        }
+    }
+
+    owners
+        .into_iter()
+        .map(|owner| owner.map(|(_, block_index)| block_index))
+        .collect()
+}
+
+/// Seeds at the midpoints between the control points of the earlier streets. Seeds on streets
+/// along the major eigenvectors start streets crossing them along the minor ones and the other
+/// way around.
+pub(crate) fn previous_level_seeds(
+    previous_major_curves: &[HermiteCurve],
+    previous_minor_curves: &[HermiteCurve],
+) -> Vec<SeedPoint> {
+    previous_major_curves
+        .iter()
+        .map(|curve| (curve, false))
+        .chain(previous_minor_curves.iter().map(|curve| (curve, true)))
+        .flat_map(|(curve, follow_major_eigenvectors)| {
+            curve.windows(2).map(move |pair| SeedPoint {
+                seed: (pair[0].position + pair[1].position) / 2.0,
+                priority: 0.0,
+                follow_major_eigenvectors,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tensor_field::Point;
+    use crate::test_support::curve;
+
+    use crate::test_support::square;
+    use crate::triangulation::triangulate;
+
+    use super::{previous_level_seeds, surface_block_indices};
+
+    #[test]
+    fn surface_blocks_take_the_largest_block_on_them() {
+        let surface_blocks = [square(0.0, 10.0), square(20.0, 30.0), square(40.0, 50.0)];
+        let blocks = [
+            square(21.0, 25.0),
+            square(1.0, 9.0),
+            square(24.0, 29.0),
+            square(60.0, 70.0),
+        ];
+        let triangulations: Vec<_> = blocks.iter().map(|block| triangulate(block, &[])).collect();
+
+        assert_eq!(
+            surface_block_indices(&surface_blocks, &triangulations),
+            [Some(1), Some(2), None]
+        );
+    }
+
+    #[test]
+    fn seeds_cross_the_street_they_sit_on() {
+        let major = [curve(&[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0)])];
+        let minor = [curve(&[(0.0, 0.0), (0.0, 10.0)])];
+
+        let seeds = previous_level_seeds(&major, &minor);
+
+        assert_eq!(
+            seeds
+                .iter()
+                .map(|seed| (seed.seed, seed.follow_major_eigenvectors))
+                .collect::<Vec<_>>(),
+            [
+                (Point::new(5.0, 0.0), false),
+                (Point::new(15.0, 0.0), false),
+                (Point::new(0.0, 5.0), true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1331 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::gtfs::{is_gtfs_date, parse_gtfs_time};
+use crate::schematic::parse_hex_color;
+use crate::tensor_field::{DesignElement, GRID_SIZE, Point};
+use crate::zoning::{LandUse, ZoningRegion};
+
+/// The config format version this build understands. Bump this whenever a field is renamed or its
+/// meaning changes so that old files are rejected instead of silently misread.
+///
+/// Version 2 moved `export.curve_samples` to `roads.curve_samples`, which defaults to 1. Version 3
+/// moved the origin and `meters_per_unit` out of `gtfs` into `projection`. Version 4 dropped the
+/// unused `count` of random seeds.
+pub const CONFIG_VERSION: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CityConfig {
+    pub version: u32,
+    pub field: FieldConfig,
+    /// Defaults to the center of the first radial design element when left out
+    #[serde(default)]
+    pub city_center: Option<Point>,
+    pub levels: Vec<LevelConfig>,
+    #[serde(default)]
+    pub tracing: TracingConfig,
+    #[serde(default)]
+    pub smoothing: SmoothingConfig,
+    #[serde(default)]
+    pub blocks: BlockConfig,
+    #[serde(default)]
+    pub zoning: ZoningConfig,
+    #[serde(default)]
+    pub parcels: ParcelConfig,
+    #[serde(default)]
+    pub buildings: BuildingConfig,
+    #[serde(default)]
+    pub roads: RoadConfig,
+    #[serde(default)]
+    pub metro: MetroConfig,
+    #[serde(default)]
+    pub buses: BusConfig,
+    #[serde(default)]
+    pub schematic: SchematicConfig,
+    #[serde(default)]
+    pub projection: ProjectionConfig,
+    #[serde(default)]
+    pub gtfs: GtfsConfig,
+    #[serde(default)]
+    pub export: ExportConfig,
+    #[serde(default)]
+    pub tiles: TileConfig,
+    #[serde(default)]
+    pub osm: OsmConfig,
+    #[serde(default)]
+    pub mask: MaskConfig,
+    #[serde(default)]
+    pub geojson: GeoJsonConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldConfig {
+    pub decay_constant: f32,
+    pub design_elements: Vec<DesignElement>,
+}
+
+/// One level of the street hierarchy. Every level is traced on top of the streets of all the
+/// levels before it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelConfig {
+    pub name: String,
+    pub seeds: SeedConfig,
+    pub d_sep: f32,
+    pub iterations: usize,
+    pub merge_distance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SeedConfig {
+    /// Seed from the fixed seed set of `street_plan::debug_seed_points`
+    Random,
+    /// Seed from the midpoints between the control points of the streets of every level before
+    /// this one, along with the fixed streets the city is generated around
+    PreviousLevel,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TracingConfig {
+    /// RK4 step size
+    pub h: f32,
+    pub max_len: f32,
+    /// Clipped streets shorter than `d_sep * min_length_factor` are discarded
+    pub min_length_factor: f32,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            h: 0.2,
+            max_len: 200.0,
+            min_length_factor: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmoothingConfig {
+    pub alpha: f32,
+    pub beta: f32,
+    pub point_side_padding: usize,
+    pub blend_factor: f32,
+}
+
+impl Default for SmoothingConfig {
+    fn default() -> Self {
+        Self {
+            alpha: 0.03,
+            beta: 0.3,
+            point_side_padding: 20,
+            blend_factor: 0.7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlockConfig {
+    pub min_face_area: f32,
+    pub flatten_threshold: f32,
+    pub area_difference_threshold: f32,
+    /// How far each block is pulled in from the street centerlines
+    pub inset_distance: f32,
+    pub merge_distance: f32,
+    /// Keep every street face's polygons after each extraction pass in `Blocks::traces`
+    pub trace_passes: bool,
+}
+
+impl Default for BlockConfig {
+    fn default() -> Self {
+        Self {
+            min_face_area: 20.0,
+            flatten_threshold: 0.98,
+            area_difference_threshold: 0.07,
+            inset_distance: 1.0,
+            merge_distance: 1.0,
+            trace_passes: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ZoningConfig {
+    /// Blocks this close to the city center are commercial
+    pub commercial_radius: f32,
+    /// Blocks facing a street of the first level are commercial up to this far from the center
+    pub major_street_commercial_radius: f32,
+    /// Blocks at least this far from the center and at least `industrial_min_area` large are
+    /// industrial
+    pub industrial_radius: f32,
+    pub industrial_min_area: f32,
+    /// Blocks up to this large become parks
+    pub park_max_area: f32,
+    /// Blocks less compact than this become parks. Compactness is 1 for a circle and close to 0
+    /// for slivers.
+    pub park_max_compactness: f32,
+    /// Blocks within this distance of the center that are at least `civic_min_compactness`
+    /// compact and whose area falls inside `civic_area` are civic
+    pub civic_radius: f32,
+    pub civic_min_compactness: f32,
+    pub civic_area: [f32; 2],
+    /// Outlines of rivers, lakes and the sea
+    pub water: Vec<Vec<Point>>,
+    /// Blocks this close to water are water adjacent
+    pub water_distance: f32,
+    /// Painted regions override every other rule, later regions win over earlier ones
+    pub regions: Vec<ZoningRegion>,
+}
+
+impl Default for ZoningConfig {
+    fn default() -> Self {
+        Self {
+            commercial_radius: 60.0,
+            major_street_commercial_radius: 150.0,
+            industrial_radius: 230.0,
+            industrial_min_area: 150.0,
+            park_max_area: 25.0,
+            park_max_compactness: 0.15,
+            civic_radius: 100.0,
+            civic_min_compactness: 0.7,
+            civic_area: [150.0, 600.0],
+            water: Vec::new(),
+            water_distance: 10.0,
+            regions: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParcelConfig {
+    /// Lots are only split while both halves keep at least this much street frontage
+    pub min_frontage: f32,
+    /// How far a lot may reach into its block, measured from its street
+    pub max_depth: f32,
+    /// How much deeper than wide a lot may be
+    pub max_aspect_ratio: f32,
+}
+
+impl Default for ParcelConfig {
+    fn default() -> Self {
+        Self {
+            min_frontage: 3.0,
+            max_depth: 8.0,
+            max_aspect_ratio: 3.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildingConfig {
+    /// Mixed with the parcel index to seed the choice of shape and height of each building
+    pub seed: u64,
+    /// Distance between a building and the street it faces
+    pub front_setback: f32,
+    /// Distance between a building and the other edges of its lot
+    pub side_setback: f32,
+    /// L, U and courtyard shapes are only used when their wings can be at least this thick
+    pub min_wing_width: f32,
+    /// Share of the lot area covered by buildings at the city center
+    pub coverage_at_center: f32,
+    /// Share of the lot area covered by buildings at `falloff_distance` from the center and beyond
+    pub coverage_at_edge: f32,
+    /// Lowest and highest building at the city center
+    pub height_at_center: [f32; 2],
+    pub height_at_edge: [f32; 2],
+    /// Distance from the city center over which coverage and heights go from their center values
+    /// to their edge values
+    pub falloff_distance: f32,
+    /// Heights are scaled by the factor of the level of the street each building faces. Levels
+    /// past the end of the list use a factor of 1.
+    pub level_height_factors: Vec<f32>,
+    /// Heights are also scaled by the factor of the land use of the block. Land uses missing
+    /// from the table use a factor of 1, parks never get buildings.
+    pub land_use_height_factors: BTreeMap<LandUse, f32>,
+}
+
+impl Default for BuildingConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            front_setback: 0.5,
+            side_setback: 0.25,
+            min_wing_width: 1.5,
+            coverage_at_center: 0.85,
+            coverage_at_edge: 0.45,
+            height_at_center: [20.0, 80.0],
+            height_at_edge: [4.0, 12.0],
+            falloff_distance: 300.0,
+            level_height_factors: vec![1.5, 1.0],
+            land_use_height_factors: BTreeMap::from([
+                (LandUse::Commercial, 1.5),
+                (LandUse::Industrial, 0.4),
+                (LandUse::Civic, 0.6),
+            ]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoadConfig {
+    /// Full width of the roads of every street level. Levels past the end of the list use the
+    /// last width.
+    pub level_widths: Vec<f32>,
+    pub sidewalk_width: f32,
+    /// Radius of the curb around the corners of junctions
+    pub corner_radius: f32,
+    /// Points sampled along every spline of a street when its road is built. With 1 the roads
+    /// follow the control points, the same way the blocks do, more samples follow the curves
+    /// more closely.
+    pub curve_samples: u32,
+}
+
+impl Default for RoadConfig {
+    fn default() -> Self {
+        Self {
+            level_widths: vec![2.0, 2.0],
+            sidewalk_width: 0.3,
+            corner_radius: 0.6,
+            curve_samples: 1,
+        }
+    }
+}
+
+impl RoadConfig {
+    pub fn width_of_level(&self, level: usize) -> f32 {
+        self.level_widths
+            .get(level)
+            .or(self.level_widths.last())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetroConfig {
+    pub station_count: usize,
+    pub line_count: usize,
+    pub min_station_spacing: f32,
+    /// Longest distance between two consecutive stations of a line
+    pub max_station_spacing: f32,
+    /// Blocks within this distance of a station count toward its demand
+    pub catchment_radius: f32,
+    /// Largest change of direction of a line at a station, in radians
+    pub max_turn_angle: f32,
+    /// Demand is doubled at the city center and falls off to the plain block demand over this
+    /// distance
+    pub falloff_distance: f32,
+    /// Demand per unit of block area of every land use. Land uses missing from the table use a
+    /// demand of 1.
+    pub land_use_demand: BTreeMap<LandUse, f32>,
+}
+
+impl Default for MetroConfig {
+    fn default() -> Self {
+        Self {
+            station_count: 30,
+            line_count: 4,
+            min_station_spacing: 25.0,
+            max_station_spacing: 90.0,
+            catchment_radius: 30.0,
+            max_turn_angle: std::f32::consts::FRAC_PI_4,
+            falloff_distance: 300.0,
+            land_use_demand: BTreeMap::from([
+                (LandUse::Residential, 1.0),
+                (LandUse::Commercial, 2.0),
+                (LandUse::Industrial, 0.5),
+                (LandUse::Park, 0.1),
+                (LandUse::Civic, 1.5),
+                (LandUse::WaterAdjacent, 1.0),
+            ]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BusConfig {
+    /// Routes run along the streets of this level and the ones before it
+    pub max_street_level: usize,
+    pub max_routes: usize,
+    /// Distance driven before the next intersection may get a stop
+    pub min_stop_spacing: f32,
+    /// Distance after which a stop is placed even between intersections
+    pub max_stop_spacing: f32,
+    /// Blocks whose centroid is within this distance of a stop are covered by it
+    pub walk_distance: f32,
+    /// Share of the blocks to cover before no more routes are added
+    pub target_coverage: f32,
+    pub min_route_length: f32,
+    /// Longest loop around a block a route may turn around on at its terminals
+    pub max_loop_length: f32,
+}
+
+impl Default for BusConfig {
+    fn default() -> Self {
+        Self {
+            max_street_level: 0,
+            max_routes: 8,
+            min_stop_spacing: 15.0,
+            max_stop_spacing: 40.0,
+            walk_distance: 40.0,
+            target_coverage: 0.8,
+            min_route_length: 100.0,
+            max_loop_length: 120.0,
+        }
+    }
+}
+
+/// Sizes of the schematic metro map, in pixels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchematicConfig {
+    /// Length of one step of the octilinear grid the stations are put on
+    pub station_spacing: f32,
+    pub line_width: f32,
+    pub station_radius: f32,
+    /// Height of the station labels
+    pub label_size: f32,
+    pub margin: f32,
+    /// Colors of the lines as `#rrggbb`, used in order and repeated when there are more lines
+    pub line_colors: Vec<String>,
+}
+
+impl Default for SchematicConfig {
+    fn default() -> Self {
+        Self {
+            station_spacing: 48.0,
+            line_width: 6.0,
+            station_radius: 5.0,
+            label_size: 14.0,
+            margin: 24.0,
+            line_colors: [
+                "#e32017", "#0098d4", "#00782a", "#ffd300", "#9b0056", "#f3a9bb", "#a0a5a9",
+                "#ee7c0e",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+/// Where the city lies on Earth and how large it is. Used by the GTFS feed, the network report,
+/// the diagnostic charts and the OpenStreetMap and GeoJSON imports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectionConfig {
+    /// Latitude of the origin of the city coordinates, in degrees
+    pub origin_latitude: f64,
+    /// Longitude of the origin of the city coordinates, in degrees
+    pub origin_longitude: f64,
+    /// Length of one unit of city coordinates in meters
+    pub meters_per_unit: f32,
+}
+
+impl Default for ProjectionConfig {
+    fn default() -> Self {
+        Self {
+            origin_latitude: 45.0,
+            origin_longitude: 0.0,
+            meters_per_unit: 1.0,
+        }
+    }
+}
+
+/// Operator and timetable of the GTFS feed of the metro and the buses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GtfsConfig {
+    pub agency_name: String,
+    pub agency_url: String,
+    /// Time zone of the timetable, as a name from the tz database
+    pub agency_timezone: String,
+    /// First day of service, written as `YYYYMMDD`
+    pub start_date: String,
+    /// Last day of service, written as `YYYYMMDD`
+    pub end_date: String,
+    /// Departure of the first trips, written as `HH:MM:SS`
+    pub service_start: String,
+    /// Latest departure of a trip, written as `HH:MM:SS`. Hours past 24 are on the next day.
+    pub service_end: String,
+    /// Average speed of the metro between stations, in km/h
+    pub metro_speed: f32,
+    /// Average speed of the buses between stops, in km/h
+    pub bus_speed: f32,
+    /// Minutes between two metro trips in the same direction
+    pub metro_headway: f32,
+    /// Minutes between two bus trips in the same direction
+    pub bus_headway: f32,
+    /// Seconds spent at every stop
+    pub dwell_time: f32,
+}
+
+impl Default for GtfsConfig {
+    fn default() -> Self {
+        Self {
+            agency_name: "Metro Modeler Transit".to_string(),
+            agency_url: "https://example.com".to_string(),
+            agency_timezone: "Etc/UTC".to_string(),
+            start_date: "20250101".to_string(),
+            end_date: "20251231".to_string(),
+            service_start: "05:00:00".to_string(),
+            service_end: "24:00:00".to_string(),
+            metro_speed: 35.0,
+            bus_speed: 18.0,
+            metro_headway: 5.0,
+            bus_headway: 10.0,
+            dwell_time: 20.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportConfig {
+    /// Blocks and sidewalks are exported as slabs this high above the roads
+    pub sidewalk_height: f32,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            sidewalk_height: 0.15,
+        }
+    }
+}
+
+/// Layout of a city generated as a grid of tiles with `generate_tiled_city`. Every tile is traced
+/// on its own tensor field grid and covers the middle of it, leaving out `overlap` on every side,
+/// with the first tile at the origin and the others continuing along x and y.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TileConfig {
+    pub columns: u32,
+    pub rows: u32,
+    /// How far the grid of a tile reaches into its neighbours, so that streets run all the way
+    /// to the tile borders
+    pub overlap: f32,
+    /// Street ends on both sides of a tile border closer than this are joined on the border
+    pub stitch_distance: f32,
+}
+
+impl Default for TileConfig {
+    fn default() -> Self {
+        Self {
+            columns: 1,
+            rows: 1,
+            overlap: 32.0,
+            stitch_distance: 8.0,
+        }
+    }
+}
+
+/// Which roads of an OpenStreetMap file are imported as fixed streets. Coordinates are placed with
+/// the origin and scale in `projection`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OsmConfig {
+    /// Values of the `highway` tag of the ways that are imported
+    pub highways: Vec<String>,
+    /// Imported highways that also get a polyline design element, so that the generated streets
+    /// line up with them
+    pub major_highways: Vec<String>,
+    /// Whether the polyline design elements are added to the tensor field
+    pub design_elements: bool,
+    /// Decay constant of the polyline design elements
+    pub design_element_decay: f32,
+}
+
+impl Default for OsmConfig {
+    fn default() -> Self {
+        Self {
+            highways: [
+                "motorway",
+                "trunk",
+                "primary",
+                "secondary",
+                "tertiary",
+                "unclassified",
+                "residential",
+                "living_street",
+            ]
+            .map(String::from)
+            .to_vec(),
+            major_highways: ["motorway", "trunk", "primary", "secondary"]
+                .map(String::from)
+                .to_vec(),
+            design_elements: false,
+            design_element_decay: 0.01,
+        }
+    }
+}
+
+/// Areas streets are kept out of. Both lists are sets of rings combined with the even-odd rule,
+/// so a ring inside another one cuts a hole into it, like an island in a lake.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaskConfig {
+    /// Water, where no seeds, streets or blocks go
+    pub water: Vec<Vec<Point>>,
+    /// The city limits. Seeds, streets and blocks outside of them are dropped. Leaving this empty
+    /// puts no limit on the city.
+    pub boundary: Vec<Vec<Point>>,
+}
+
+/// How features of a GeoJSON file are told apart. A feature's kind is the value of `property`
+/// among its properties. Coordinates are placed with the origin and scale in `projection`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeoJsonConfig {
+    pub property: String,
+    /// Kinds of polygons that are added to the water of the mask and of the zoning
+    pub water: Vec<String>,
+    /// Kinds of lines that are added to the tensor field as polyline design elements
+    pub rivers: Vec<String>,
+    /// Kinds of polygons that are added to the boundary of the mask
+    pub boundary: Vec<String>,
+    /// Decay constant of the river design elements
+    pub river_decay: f32,
+}
+
+impl Default for GeoJsonConfig {
+    fn default() -> Self {
+        Self {
+            property: "kind".to_string(),
+            water: ["water", "lake", "sea"].map(String::from).to_vec(),
+            rivers: ["river", "stream", "canal"].map(String::from).to_vec(),
+            boundary: ["boundary"].map(String::from).to_vec(),
+            river_decay: 0.01,
+        }
+    }
+}
+
+impl Default for CityConfig {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            field: FieldConfig {
+                decay_constant: 0.0004,
+                design_elements: vec![
+                    DesignElement::Grid {
+                        center: Point::new(100.0, 100.0),
+                        theta: -std::f32::consts::FRAC_PI_3 * 2.0,
+                        length: 500.0,
+                    },
+                    DesignElement::Radial {
+                        center: Point::new(200.0, 200.0),
+                    },
+                    DesignElement::Grid {
+                        center: Point::new(300.0, 400.0),
+                        theta: 0.1,
+                        length: 200.0,
+                    },
+                    DesignElement::Grid {
+                        center: Point::new(0.0, 400.0),
+                        theta: 0.7,
+                        length: 10.0,
+                    },
+                ],
+            },
+            city_center: None,
+            levels: vec![
+                LevelConfig {
+                    name: "major".to_string(),
+                    seeds: SeedConfig::Random,
+                    d_sep: 30.0,
+                    iterations: 5,
+                    merge_distance: 5.0,
+                },
+                LevelConfig {
+                    name: "minor".to_string(),
+                    seeds: SeedConfig::PreviousLevel,
+                    d_sep: 5.0,
+                    iterations: 3,
+                    merge_distance: 3.0,
+                },
+            ],
+            tracing: TracingConfig::default(),
+            smoothing: SmoothingConfig::default(),
+            blocks: BlockConfig::default(),
+            zoning: ZoningConfig::default(),
+            parcels: ParcelConfig::default(),
+            buildings: BuildingConfig::default(),
+            roads: RoadConfig::default(),
+            metro: MetroConfig::default(),
+            buses: BusConfig::default(),
+            schematic: SchematicConfig::default(),
+            projection: ProjectionConfig::default(),
+            gtfs: GtfsConfig::default(),
+            export: ExportConfig::default(),
+            tiles: TileConfig::default(),
+            osm: OsmConfig::default(),
+            mask: MaskConfig::default(),
+            geojson: GeoJsonConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse(toml::de::Error),
+    UnsupportedVersion {
+        found: u32,
+    },
+    Invalid {
+        field: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => {
+                write!(f, "could not read config file {}: {source}", path.display())
+            }
+            ConfigError::Parse(err) => write!(f, "could not parse config file: {err}"),
+            ConfigError::UnsupportedVersion { found } => write!(
+                f,
+                "config version {found} is not supported, expected version {CONFIG_VERSION}"
+            ),
+            ConfigError::Invalid { field, reason } => write!(f, "invalid `{field}`: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(field: impl Into<String>, reason: impl Into<String>) -> ConfigError {
+    ConfigError::Invalid {
+        field: field.into(),
+        reason: reason.into(),
+    }
+}
+
+fn ensure_positive(field: &str, value: f32) -> Result<(), ConfigError> {
+    if value.is_finite() && value > 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(
+            field,
+            format!("must be a positive number, got {value}"),
+        ))
+    }
+}
+
+fn ensure_in_range(field: &str, value: f32, min: f32, max: f32) -> Result<(), ConfigError> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(invalid(
+            field,
+            format!("must be between {min} and {max}, got {value}"),
+        ))
+    }
+}
+
+fn ensure_finite_point(field: &str, point: Point) -> Result<(), ConfigError> {
+    if point.iter().all(|coord| coord.is_finite()) {
+        Ok(())
+    } else {
+        Err(invalid(
+            field,
+            format!("must be a finite point, got {:?}", (point.x, point.y)),
+        ))
+    }
+}
+
+impl CityConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<CityConfig, ConfigError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Self::from_toml_str(&contents)
+    }
+
+    pub fn from_toml_str(contents: &str) -> Result<CityConfig, ConfigError> {
+        let config: CityConfig = toml::from_str(contents).map_err(ConfigError::Parse)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn to_toml_string(&self) -> String {
+        toml::to_string_pretty(self).expect("City configs are always representable as TOML")
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.version != CONFIG_VERSION {
+            return Err(ConfigError::UnsupportedVersion {
+                found: self.version,
+            });
+        }
+
+        ensure_positive("field.decay_constant", self.field.decay_constant)?;
+        if self.field.design_elements.is_empty() {
+            return Err(invalid(
+                "field.design_elements",
+                "at least one design element is needed to build a tensor field",
+            ));
+        }
+
+        for (i, element) in self.field.design_elements.iter().enumerate() {
+            let field = format!("field.design_elements[{i}]");
+            match element {
+                DesignElement::Grid { center, length, .. } => {
+                    ensure_finite_point(&format!("{field}.center"), *center)?;
+                    ensure_positive(&format!("{field}.length"), *length)?;
+                }
+                DesignElement::Radial { center } => {
+                    ensure_finite_point(&format!("{field}.center"), *center)?;
+                }
+                DesignElement::PolyLine {
+                    points,
+                    decay_constant,
+                } => {
+                    if points.len() < 2 {
+                        return Err(invalid(
+                            format!("{field}.points"),
+                            "a poly line needs at least two points",
+                        ));
+                    }
+                    for (j, point) in points.iter().enumerate() {
+                        ensure_finite_point(&format!("{field}.points[{j}]"), *point)?;
+                    }
+                    ensure_positive(&format!("{field}.decay_constant"), *decay_constant)?;
+                }
+            }
+        }
+
+        if let Some(center) = self.city_center {
+            ensure_finite_point("city_center", center)?;
+        } else if self
+            .field
+            .design_elements
+            .iter()
+            .all(|element| !matches!(element, DesignElement::Radial { .. }))
+        {
+            return Err(invalid(
+                "city_center",
+                "must be given when there is no radial design element to take it from",
+            ));
+        }
+
+        if self.levels.is_empty() {
+            return Err(invalid("levels", "at least one street level is needed"));
+        }
+
+        for (i, level) in self.levels.iter().enumerate() {
+            let field = format!("levels[{i}]");
+            if level.seeds == SeedConfig::PreviousLevel && i == 0 {
+                return Err(invalid(
+                    format!("{field}.seeds"),
+                    "the first level has no previous level to seed from",
+                ));
+            }
+            ensure_positive(&format!("{field}.d_sep"), level.d_sep)?;
+            if level.iterations == 0 {
+                return Err(invalid(format!("{field}.iterations"), "must be at least 1"));
+            }
+            ensure_positive(&format!("{field}.merge_distance"), level.merge_distance)?;
+        }
+
+        ensure_in_range("tracing.h", self.tracing.h, f32::EPSILON, 1.0)?;
+        ensure_positive("tracing.max_len", self.tracing.max_len)?;
+        ensure_positive("tracing.min_length_factor", self.tracing.min_length_factor)?;
+
+        ensure_positive("smoothing.alpha", self.smoothing.alpha)?;
+        ensure_in_range("smoothing.beta", self.smoothing.beta, 0.0, 1.0)?;
+        if self.smoothing.point_side_padding == 0 {
+            return Err(invalid(
+                "smoothing.point_side_padding",
+                "must be at least 1",
+            ));
+        }
+        ensure_in_range(
+            "smoothing.blend_factor",
+            self.smoothing.blend_factor,
+            0.0,
+            1.0,
+        )?;
+
+        ensure_positive("blocks.min_face_area", self.blocks.min_face_area)?;
+        ensure_in_range(
+            "blocks.flatten_threshold",
+            self.blocks.flatten_threshold,
+            0.0,
+            1.0,
+        )?;
+        ensure_in_range(
+            "blocks.area_difference_threshold",
+            self.blocks.area_difference_threshold,
+            0.0,
+            1.0,
+        )?;
+        ensure_in_range(
+            "blocks.inset_distance",
+            self.blocks.inset_distance,
+            0.0,
+            GRID_SIZE as f32,
+        )?;
+        ensure_positive("blocks.merge_distance", self.blocks.merge_distance)?;
+
+        let zoning = &self.zoning;
+        for (field, value) in [
+            ("zoning.commercial_radius", zoning.commercial_radius),
+            (
+                "zoning.major_street_commercial_radius",
+                zoning.major_street_commercial_radius,
+            ),
+            ("zoning.industrial_radius", zoning.industrial_radius),
+            ("zoning.industrial_min_area", zoning.industrial_min_area),
+            ("zoning.park_max_area", zoning.park_max_area),
+            ("zoning.civic_radius", zoning.civic_radius),
+            ("zoning.water_distance", zoning.water_distance),
+        ] {
+            ensure_in_range(field, value, 0.0, f32::MAX)?;
+        }
+        ensure_in_range(
+            "zoning.park_max_compactness",
+            zoning.park_max_compactness,
+            0.0,
+            1.0,
+        )?;
+        ensure_in_range(
+            "zoning.civic_min_compactness",
+            zoning.civic_min_compactness,
+            0.0,
+            1.0,
+        )?;
+        let [min_civic_area, max_civic_area] = zoning.civic_area;
+        ensure_in_range("zoning.civic_area", min_civic_area, 0.0, f32::MAX)?;
+        ensure_in_range("zoning.civic_area", max_civic_area, 0.0, f32::MAX)?;
+        if min_civic_area > max_civic_area {
+            return Err(invalid(
+                "zoning.civic_area",
+                "the smallest area is above the largest one",
+            ));
+        }
+        let polygons =
+            zoning
+                .water
+                .iter()
+                .enumerate()
+                .map(|(i, polygon)| (format!("zoning.water[{i}]"), polygon))
+                .chain(
+                    zoning.regions.iter().enumerate().map(|(i, region)| {
+                        (format!("zoning.regions[{i}].polygon"), &region.polygon)
+                    }),
+                );
+        for (field, polygon) in polygons {
+            if polygon.len() < 3 {
+                return Err(invalid(field, "a polygon needs at least three points"));
+            }
+            for (j, point) in polygon.iter().enumerate() {
+                ensure_finite_point(&format!("{field}[{j}]"), *point)?;
+            }
+        }
+
+        ensure_positive("parcels.min_frontage", self.parcels.min_frontage)?;
+        ensure_positive("parcels.max_depth", self.parcels.max_depth)?;
+        if !self.parcels.max_aspect_ratio.is_finite() || self.parcels.max_aspect_ratio < 1.0 {
+            return Err(invalid("parcels.max_aspect_ratio", "must be at least 1"));
+        }
+
+        let buildings = &self.buildings;
+        ensure_in_range(
+            "buildings.front_setback",
+            buildings.front_setback,
+            0.0,
+            f32::MAX,
+        )?;
+        ensure_in_range(
+            "buildings.side_setback",
+            buildings.side_setback,
+            0.0,
+            f32::MAX,
+        )?;
+        ensure_positive("buildings.min_wing_width", buildings.min_wing_width)?;
+        ensure_in_range(
+            "buildings.coverage_at_center",
+            buildings.coverage_at_center,
+            0.0,
+            1.0,
+        )?;
+        ensure_in_range(
+            "buildings.coverage_at_edge",
+            buildings.coverage_at_edge,
+            0.0,
+            1.0,
+        )?;
+        for (field, [min, max]) in [
+            ("buildings.height_at_center", buildings.height_at_center),
+            ("buildings.height_at_edge", buildings.height_at_edge),
+        ] {
+            ensure_positive(field, min)?;
+            ensure_positive(field, max)?;
+            if min > max {
+                return Err(invalid(field, "the lowest height is above the highest one"));
+            }
+        }
+        ensure_positive("buildings.falloff_distance", buildings.falloff_distance)?;
+        for (i, factor) in buildings.level_height_factors.iter().enumerate() {
+            ensure_positive(&format!("buildings.level_height_factors[{i}]"), *factor)?;
+        }
+        for (land_use, factor) in &buildings.land_use_height_factors {
+            ensure_positive(
+                &format!("buildings.land_use_height_factors.{land_use}"),
+                *factor,
+            )?;
+        }
+
+        if self.roads.level_widths.is_empty() {
+            return Err(invalid("roads.level_widths", "needs at least one width"));
+        }
+        for (i, width) in self.roads.level_widths.iter().enumerate() {
+            ensure_positive(&format!("roads.level_widths[{i}]"), *width)?;
+        }
+        ensure_in_range(
+            "roads.sidewalk_width",
+            self.roads.sidewalk_width,
+            0.0,
+            f32::MAX,
+        )?;
+        ensure_in_range(
+            "roads.corner_radius",
+            self.roads.corner_radius,
+            0.0,
+            f32::MAX,
+        )?;
+        if self.roads.curve_samples == 0 {
+            return Err(invalid("roads.curve_samples", "must be at least 1"));
+        }
+
+        let metro = &self.metro;
+        ensure_positive("metro.min_station_spacing", metro.min_station_spacing)?;
+        ensure_positive("metro.max_station_spacing", metro.max_station_spacing)?;
+        if metro.min_station_spacing > metro.max_station_spacing {
+            return Err(invalid(
+                "metro.max_station_spacing",
+                "must not be below metro.min_station_spacing",
+            ));
+        }
+        ensure_positive("metro.catchment_radius", metro.catchment_radius)?;
+        ensure_in_range(
+            "metro.max_turn_angle",
+            metro.max_turn_angle,
+            0.0,
+            std::f32::consts::PI,
+        )?;
+        ensure_positive("metro.falloff_distance", metro.falloff_distance)?;
+        for (land_use, demand) in &metro.land_use_demand {
+            ensure_in_range(
+                &format!("metro.land_use_demand.{land_use}"),
+                *demand,
+                0.0,
+                f32::MAX,
+            )?;
+        }
+
+        let buses = &self.buses;
+        if buses.max_street_level >= self.levels.len() {
+            return Err(invalid(
+                "buses.max_street_level",
+                format!("must be below the number of levels, {}", self.levels.len()),
+            ));
+        }
+        ensure_positive("buses.min_stop_spacing", buses.min_stop_spacing)?;
+        ensure_positive("buses.max_stop_spacing", buses.max_stop_spacing)?;
+        if buses.min_stop_spacing > buses.max_stop_spacing {
+            return Err(invalid(
+                "buses.max_stop_spacing",
+                "must not be below buses.min_stop_spacing",
+            ));
+        }
+        ensure_positive("buses.walk_distance", buses.walk_distance)?;
+        ensure_in_range("buses.target_coverage", buses.target_coverage, 0.0, 1.0)?;
+        ensure_in_range(
+            "buses.min_route_length",
+            buses.min_route_length,
+            0.0,
+            f32::MAX,
+        )?;
+        ensure_positive("buses.max_loop_length", buses.max_loop_length)?;
+
+        let schematic = &self.schematic;
+        ensure_positive("schematic.station_spacing", schematic.station_spacing)?;
+        ensure_positive("schematic.line_width", schematic.line_width)?;
+        ensure_positive("schematic.station_radius", schematic.station_radius)?;
+        ensure_positive("schematic.label_size", schematic.label_size)?;
+        ensure_in_range("schematic.margin", schematic.margin, 0.0, f32::MAX)?;
+        if schematic.line_colors.is_empty() {
+            return Err(invalid("schematic.line_colors", "needs at least one color"));
+        }
+        for (i, color) in schematic.line_colors.iter().enumerate() {
+            if parse_hex_color(color).is_none() {
+                return Err(invalid(
+                    format!("schematic.line_colors[{i}]"),
+                    format!("expected a color written as #rrggbb, got {color:?}"),
+                ));
+            }
+        }
+
+        let projection = &self.projection;
+        if !(-90.0..=90.0).contains(&projection.origin_latitude) {
+            return Err(invalid(
+                "projection.origin_latitude",
+                format!(
+                    "must be between -90 and 90, got {}",
+                    projection.origin_latitude
+                ),
+            ));
+        }
+        if !(-180.0..=180.0).contains(&projection.origin_longitude) {
+            return Err(invalid(
+                "projection.origin_longitude",
+                format!(
+                    "must be between -180 and 180, got {}",
+                    projection.origin_longitude
+                ),
+            ));
+        }
+        ensure_positive("projection.meters_per_unit", projection.meters_per_unit)?;
+
+        let gtfs = &self.gtfs;
+        for (field, value) in [
+            ("gtfs.agency_name", &gtfs.agency_name),
+            ("gtfs.agency_url", &gtfs.agency_url),
+            ("gtfs.agency_timezone", &gtfs.agency_timezone),
+        ] {
+            if value.trim().is_empty() {
+                return Err(invalid(field, "must not be empty"));
+            }
+        }
+        for (field, date) in [
+            ("gtfs.start_date", &gtfs.start_date),
+            ("gtfs.end_date", &gtfs.end_date),
+        ] {
+            if !is_gtfs_date(date) {
+                return Err(invalid(
+                    field,
+                    format!("expected a date written as YYYYMMDD, got {date:?}"),
+                ));
+            }
+        }
+        if gtfs.start_date > gtfs.end_date {
+            return Err(invalid(
+                "gtfs.end_date",
+                "must not be before gtfs.start_date",
+            ));
+        }
+        let mut service_times = [0; 2];
+        for (time, (field, value)) in service_times.iter_mut().zip([
+            ("gtfs.service_start", &gtfs.service_start),
+            ("gtfs.service_end", &gtfs.service_end),
+        ]) {
+            *time = parse_gtfs_time(value).ok_or_else(|| {
+                invalid(
+                    field,
+                    format!("expected a time written as HH:MM:SS, got {value:?}"),
+                )
+            })?;
+        }
+        if service_times[0] > service_times[1] {
+            return Err(invalid(
+                "gtfs.service_end",
+                "must not be before gtfs.service_start",
+            ));
+        }
+        ensure_positive("gtfs.metro_speed", gtfs.metro_speed)?;
+        ensure_positive("gtfs.bus_speed", gtfs.bus_speed)?;
+        ensure_positive("gtfs.metro_headway", gtfs.metro_headway)?;
+        ensure_positive("gtfs.bus_headway", gtfs.bus_headway)?;
+        ensure_in_range("gtfs.dwell_time", gtfs.dwell_time, 0.0, f32::MAX)?;
+
+        ensure_in_range(
+            "export.sidewalk_height",
+            self.export.sidewalk_height,
+            0.0,
+            f32::MAX,
+        )?;
+
+        if self.tiles.columns == 0 || self.tiles.rows == 0 {
+            return Err(invalid("tiles", "needs at least one column and one row"));
+        }
+        ensure_in_range(
+            "tiles.overlap",
+            self.tiles.overlap,
+            0.0,
+            GRID_SIZE as f32 / 4.0,
+        )?;
+        ensure_positive("tiles.stitch_distance", self.tiles.stitch_distance)?;
+
+        if let Some(highway) = self
+            .osm
+            .major_highways
+            .iter()
+            .find(|highway| !self.osm.highways.contains(highway))
+        {
+            return Err(invalid(
+                "osm.major_highways",
+                format!("`{highway}` is not one of the imported osm.highways"),
+            ));
+        }
+        ensure_positive("osm.design_element_decay", self.osm.design_element_decay)?;
+
+        let rings = self
+            .mask
+            .water
+            .iter()
+            .enumerate()
+            .map(|(i, ring)| (format!("mask.water[{i}]"), ring))
+            .chain(
+                self.mask
+                    .boundary
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ring)| (format!("mask.boundary[{i}]"), ring)),
+            );
+        for (field, ring) in rings {
+            if ring.len() < 3 {
+                return Err(invalid(field, "a polygon needs at least three points"));
+            }
+            for (j, point) in ring.iter().enumerate() {
+                ensure_finite_point(&format!("{field}[{j}]"), *point)?;
+            }
+        }
+
+        if self.geojson.property.is_empty() {
+            return Err(invalid("geojson.property", "must not be empty"));
+        }
+        ensure_positive("geojson.river_decay", self.geojson.river_decay)?;
+
+        Ok(())
+    }
+
+    pub fn city_center(&self) -> Point {
+        self.city_center.unwrap_or_else(|| {
+            self.field
+                .design_elements
+                .iter()
+                .find_map(|element| match element {
+                    DesignElement::Radial { center } => Some(*center),
+                    _ => None,
+                })
+                .expect("Validated configs always have a city center")
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CONFIG_VERSION, CityConfig, ConfigError, SeedConfig};
+    use crate::tensor_field::Point;
+    use crate::zoning::{LandUse, ZoningRegion};
+
+    #[test]
+    fn bundled_default_config_matches_builtin_default() {
+        let config = CityConfig::from_toml_str(include_str!("../cities/default.toml")).unwrap();
+
+        assert_eq!(config, CityConfig::default());
+    }
+
+    #[test]
+    fn default_config_round_trips_through_toml() {
+        let config = CityConfig::default();
+
+        let reloaded = CityConfig::from_toml_str(&config.to_toml_string()).unwrap();
+
+        assert_eq!(reloaded, config);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let config = CityConfig {
+            version: CONFIG_VERSION + 1,
+            ..CityConfig::default()
+        };
+
+        assert!(matches!(
+            CityConfig::from_toml_str(&config.to_toml_string()),
+            Err(ConfigError::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn first_level_cannot_seed_from_previous_level() {
+        let mut config = CityConfig::default();
+        config.levels[0].seeds = SeedConfig::PreviousLevel;
+
+        match config.validate() {
+            Err(ConfigError::Invalid { field, .. }) => assert_eq!(field, "levels[0].seeds"),
+            other => panic!("Expected an invalid seed error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn zoning_regions_are_parsed_and_checked() {
+        let contents = CityConfig::default().to_toml_string().replace(
+            "regions = []",
+            "regions = [{ land_use = \"park\", polygon = [[0.0, 0.0], [10.0, 0.0]] }]",
+        );
+
+        match CityConfig::from_toml_str(&contents) {
+            Err(ConfigError::Invalid { field, .. }) => {
+                assert_eq!(field, "zoning.regions[0].polygon")
+            }
+            other => panic!("Expected an invalid region error, got {other:?}"),
+        }
+
+        let mut config = CityConfig::default();
+        config.zoning.regions.push(ZoningRegion {
+            land_use: LandUse::Park,
+            polygon: vec![
+                Point::new(0.0, 0.0),
+                Point::new(10.0, 0.0),
+                Point::new(10.0, 10.0),
+            ],
+        });
+        let parsed = CityConfig::from_toml_str(&config.to_toml_string()).unwrap();
+        assert_eq!(parsed.zoning.regions, config.zoning.regions);
+    }
+
+    #[test]
+    fn unknown_fields_are_reported() {
+        let contents = CityConfig::default()
+            .to_toml_string()
+            .replace("[tracing]", "[tracing]\nstep = 0.1");
+
+        assert!(matches!(
+            CityConfig::from_toml_str(&contents),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+use crate::editor::{Editor, EditorChanges, EditorInput, EditorKey};
+use crate::history::{GenerationHistory, Playback};
+use crate::tensor_field::Point;
+use crate::view::{Camera2D, Layer, LayerVisibility};
+
+/// What happened in the viewer window during one frame, with positions in window pixels counted
+/// from the top left corner. The viewer fills this in from the window events.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameInput {
+    pub window_size: Point,
+    /// `None` while the cursor is outside the window
+    pub cursor: Option<Point>,
+    /// How far the cursor moved since the last frame
+    pub cursor_moved: Point,
+    pub primary_pressed: bool,
+    pub primary_released: bool,
+    /// Dragging with the secondary button held pans the view
+    pub secondary_held: bool,
+    /// Steps the mouse wheel turned, positive away from the user
+    pub scroll: f32,
+    /// Keys pressed during the frame, as the characters they type. Enter, Escape and Delete come
+    /// in as `'\r'`, `'\u{1b}'` and `'\u{7f}'`.
+    pub keys: Vec<char>,
+}
+
+/// What a frame of input changed, for the viewer to redraw
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameChanges {
+    pub editor: EditorChanges,
+    pub camera_moved: bool,
+    /// The playback moved to another step of the generation history
+    pub playback_moved: bool,
+    /// Layers whose visibility flipped, in the order of their keys
+    pub toggled_layers: Vec<Layer>,
+}
+
+/// Routes the input of the viewer window: secondary drags and the wheel move the camera, number
+/// keys toggle layers, `,` and `.` step through the generation history, and the primary button and
+/// the other keys go to the editor
+#[derive(Debug, Clone)]
+pub struct ViewerControls {
+    pub editor: Editor,
+    pub camera: Camera2D,
+    pub layers: LayerVisibility,
+    history: GenerationHistory,
+    playback: Playback,
+}
+
+impl ViewerControls {
+    pub fn new(editor: Editor) -> Self {
+        let history = GenerationHistory::default();
+        Self {
+            editor,
+            camera: Camera2D::default(),
+            layers: LayerVisibility::default(),
+            playback: Playback::new(&history),
+            history,
+        }
+    }
+
+    /// Plays back `history` from its first step, in place of the one before
+    pub fn set_history(&mut self, history: GenerationHistory) {
+        self.playback = Playback::new(&history);
+        self.history = history;
+    }
+
+    /// The lines of the current step of the history, as `Playback::colored_lines` gives them
+    pub fn history_lines(&self) -> Vec<(Vec<Point>, [f32; 4])> {
+        self.playback.colored_lines(&self.history)
+    }
+
+    pub fn handle(&mut self, input: &FrameInput) -> FrameChanges {
+        let mut camera_moved = false;
+        if input.secondary_held && input.cursor_moved != Point::zeros() {
+            self.camera.pan(input.cursor_moved, input.window_size);
+            camera_moved = true;
+        }
+        if let Some(cursor) = input.cursor.filter(|_| input.scroll != 0.0) {
+            let zoom = self.camera.zoom;
+            self.camera.zoom_at(cursor, input.scroll, input.window_size);
+            camera_moved |= self.camera.zoom != zoom;
+        }
+
+        if let Some(cursor) = input.cursor {
+            let point = self.camera.screen_to_grid(cursor, input.window_size);
+            if input.primary_pressed {
+                self.editor.handle(EditorInput::PointerPressed(point));
+            }
+            if input.cursor_moved != Point::zeros() {
+                self.editor.handle(EditorInput::PointerMoved(point));
+            }
+            if input.primary_released {
+                self.editor.handle(EditorInput::PointerReleased(point));
+            }
+        }
+
+        let mut toggled_layers = Vec::new();
+        let mut playback_moved = false;
+        for &key in &input.keys {
+            if let Some(layer) = self.layers.handle_key(key) {
+                toggled_layers.push(layer);
+            } else if matches!(key, ',' | '.') {
+                playback_moved |= self.playback.handle_key(key);
+            } else if let Some(editor_key) = EditorKey::from_key(key) {
+                self.editor.handle(EditorInput::Key(editor_key));
+            }
+        }
+
+        FrameChanges {
+            editor: self.editor.take_changes(),
+            camera_moved,
+            playback_moved,
+            toggled_layers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::editor::{Editor, EditorChanges};
+    use crate::history::{GenerationHistory, IterationHistory, LevelHistory};
+    use crate::tensor_field::{DesignElement, GRID_SIZE, Point};
+    use crate::view::Layer;
+
+    use super::{FrameInput, ViewerControls};
+
+    #[test]
+    fn clicks_and_keys_edit_the_field() {
+        let window_size = Point::new(GRID_SIZE as f32, GRID_SIZE as f32);
+        let mut controls = ViewerControls::new(Editor::new(Vec::new(), 0.01));
+
+        let changes = controls.handle(&FrameInput {
+            window_size,
+            cursor: Some(Point::new(100.0, 50.0)),
+            primary_pressed: true,
+            keys: vec!['r'],
+            ..FrameInput::default()
+        });
+        // The key comes after the click, so nothing was placed yet
+        assert_eq!(changes.editor, EditorChanges::default());
+
+        let changes = controls.handle(&FrameInput {
+            window_size,
+            cursor: Some(Point::new(100.0, 50.0)),
+            primary_pressed: true,
+            primary_released: true,
+            ..FrameInput::default()
+        });
+        assert!(changes.editor.field);
+        // Window pixels count down from the top, the grid counts up from the bottom
+        assert_eq!(
+            controls.editor.elements(),
+            [DesignElement::Radial {
+                center: Point::new(100.0, GRID_SIZE as f32 - 50.0),
+            }]
+        );
+
+        let changes = controls.handle(&FrameInput {
+            window_size,
+            keys: vec!['t'],
+            ..FrameInput::default()
+        });
+        assert!(changes.editor.retrace);
+        assert!(!changes.editor.field);
+    }
+
+    #[test]
+    fn the_camera_follows_the_mouse_and_numbers_toggle_layers() {
+        let window_size = Point::new(GRID_SIZE as f32, GRID_SIZE as f32);
+        let cursor = Point::new(300.0, 200.0);
+        let mut controls = ViewerControls::new(Editor::new(Vec::new(), 0.01));
+
+        let changes = controls.handle(&FrameInput {
+            window_size,
+            cursor: Some(cursor),
+            scroll: 2.0,
+            keys: vec!['3', 'r'],
+            ..FrameInput::default()
+        });
+        assert!(changes.camera_moved);
+        assert!(controls.camera.zoom > 1.0);
+        assert_eq!(changes.toggled_layers, [Layer::MajorNetwork]);
+        assert!(!controls.layers.is_visible(Layer::MajorNetwork));
+
+        let changes = controls.handle(&FrameInput {
+            window_size,
+            cursor: Some(cursor + Point::new(40.0, 0.0)),
+            cursor_moved: Point::new(40.0, 0.0),
+            secondary_held: true,
+            ..FrameInput::default()
+        });
+        assert!(changes.camera_moved);
+        assert!(changes.toggled_layers.is_empty());
+
+        // Clicks land where the moved camera shows them
+        let cursor = cursor + Point::new(40.0, 0.0);
+        let under_cursor = controls.camera.screen_to_grid(cursor, window_size);
+        controls.handle(&FrameInput {
+            window_size,
+            cursor: Some(cursor),
+            primary_pressed: true,
+            primary_released: true,
+            ..FrameInput::default()
+        });
+        assert_eq!(
+            controls.editor.elements(),
+            [DesignElement::Radial {
+                center: under_cursor,
+            }]
+        );
+    }
+
+    #[test]
+    fn playback_keys_step_through_the_history() {
+        let seed = Point::new(10.0, 10.0);
+        let mut controls = ViewerControls::new(Editor::new(Vec::new(), 0.01));
+        controls.set_history(GenerationHistory {
+            levels: vec![LevelHistory {
+                iterations: vec![IterationHistory {
+                    follow_major_eigenvectors: true,
+                    seeds: vec![seed],
+                    traces: vec![vec![seed, seed + Point::new(50.0, 0.0)]],
+                    smoothed: Vec::new(),
+                    clipped: Vec::new(),
+                }],
+                merged: Vec::new(),
+            }],
+        });
+        let seed_lines = controls.history_lines();
+
+        let changes = controls.handle(&FrameInput {
+            keys: vec![','],
+            ..FrameInput::default()
+        });
+        assert!(!changes.playback_moved);
+
+        let changes = controls.handle(&FrameInput {
+            keys: vec!['.'],
+            ..FrameInput::default()
+        });
+        assert!(changes.playback_moved);
+        assert_eq!(changes.editor, EditorChanges::default());
+        assert_ne!(controls.history_lines(), seed_lines);
+        assert_eq!(controls.history_lines().len(), 1);
+    }
+}
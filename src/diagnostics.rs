@@ -0,0 +1,242 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use image::{Rgba, RgbaImage};
+
+use crate::analytics::{ORIENTATION_BINS, bearing_histogram, segment_lengths};
+use crate::city::City;
+use crate::config::CityConfig;
+use crate::schematic::{BACKGROUND, INK, blend, draw_label, fill_capsule, fill_rect, label_width};
+use crate::tensor_field::Point;
+
+const ROSE_SIZE: u32 = 480;
+const HISTOGRAM_WIDTH: u32 = 640;
+const HISTOGRAM_HEIGHT: u32 = 360;
+const HISTOGRAM_BINS: usize = 30;
+/// Segments the rings of the rose are drawn with
+const RING_STEPS: usize = 128;
+
+/// Space around the plots, which holds the labels of the histograms
+const MARGIN: f32 = 32.0;
+const LABEL_SIZE: f32 = 14.0;
+const LABEL_GAP: f32 = 6.0;
+
+const BAR: Rgba<u8> = Rgba([0, 152, 212, 255]);
+const GUIDE: Rgba<u8> = Rgba([200, 200, 200, 255]);
+
+/// Draws the shares of a bearing histogram, as returned by `bearing_histogram`, as a polar
+/// histogram with north up. Wedge areas are proportional to their share, and the rings mark a
+/// quarter, half, three quarters and all of the largest share, which is labeled in percent next to
+/// the outer ring.
+pub fn render_orientation_rose(histogram: &[f64]) -> RgbaImage {
+    let mut image = RgbaImage::from_pixel(ROSE_SIZE, ROSE_SIZE, BACKGROUND);
+    let center = Point::new(ROSE_SIZE as f32, ROSE_SIZE as f32) / 2.0;
+    let radius = ROSE_SIZE as f32 / 2.0 - MARGIN;
+
+    let largest = histogram.iter().copied().fold(0.0, f64::max);
+    if largest > 0.0 {
+        let bin_width = 360.0 / histogram.len() as f32;
+        for y in 0..ROSE_SIZE {
+            for x in 0..ROSE_SIZE {
+                let offset = Point::new(x as f32 + 0.5, y as f32 + 0.5) - center;
+                // Bearings go clockwise from north, which is up in the image
+                let bearing = offset.x.atan2(-offset.y).to_degrees();
+                let bin = ((bearing + bin_width / 2.0).rem_euclid(360.0) / bin_width) as usize;
+                let share = histogram[bin.min(histogram.len() - 1)];
+                let wedge_radius = radius * (share / largest).sqrt() as f32;
+                let coverage = (wedge_radius - offset.norm() + 0.5).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    blend(image.get_pixel_mut(x, y), BAR, coverage);
+                }
+            }
+        }
+    }
+
+    for ring in 1..=4 {
+        let ring_radius = radius * (ring as f32 / 4.0).sqrt();
+        let at = |step: usize| {
+            let angle = std::f32::consts::TAU * step as f32 / RING_STEPS as f32;
+            center + Point::new(angle.cos(), angle.sin()) * ring_radius
+        };
+        for step in 0..RING_STEPS {
+            fill_capsule(&mut image, [at(step), at(step + 1)], 0.5, GUIDE);
+        }
+    }
+    for direction in [Point::new(0.0, radius), Point::new(radius, 0.0)] {
+        fill_capsule(
+            &mut image,
+            [center - direction, center + direction],
+            0.5,
+            GUIDE,
+        );
+    }
+
+    if largest <= 0.0 {
+        return image;
+    }
+    let label = format!("{}", (largest * 100.0).round());
+    draw_label(
+        &mut image,
+        &label,
+        center + Point::new(LABEL_GAP, -radius - LABEL_GAP - LABEL_SIZE),
+        LABEL_SIZE,
+    );
+    image
+}
+
+/// Draws a histogram of the values, with bins from 0 to the largest value. The largest value is
+/// labeled at the right end of the x axis and the count of the fullest bin at the top of the y
+/// axis.
+pub fn render_histogram(values: &[f64]) -> RgbaImage {
+    let mut image = RgbaImage::from_pixel(HISTOGRAM_WIDTH, HISTOGRAM_HEIGHT, BACKGROUND);
+    let plot_min = Point::new(MARGIN * 2.0, MARGIN);
+    let plot_max = Point::new(
+        HISTOGRAM_WIDTH as f32 - MARGIN,
+        HISTOGRAM_HEIGHT as f32 - MARGIN,
+    );
+    let plot_size = plot_max - plot_min;
+
+    let largest = values.iter().copied().fold(0.0, f64::max);
+    let mut counts = vec![0_usize; HISTOGRAM_BINS];
+    if largest > 0.0 {
+        for &value in values {
+            let bin = (value / largest * HISTOGRAM_BINS as f64) as usize;
+            counts[bin.min(HISTOGRAM_BINS - 1)] += 1;
+        }
+    }
+    let fullest = counts.iter().copied().max().unwrap_or_default();
+
+    if fullest > 0 {
+        let bar_width = plot_size.x / HISTOGRAM_BINS as f32;
+        for (bin, &count) in counts.iter().enumerate() {
+            let height = plot_size.y * count as f32 / fullest as f32;
+            fill_rect(
+                &mut image,
+                Point::new(
+                    plot_min.x + bin as f32 * bar_width + 1.0,
+                    plot_max.y - height,
+                ),
+                Point::new(plot_min.x + (bin + 1) as f32 * bar_width - 1.0, plot_max.y),
+                BAR,
+            );
+        }
+    }
+
+    fill_capsule(
+        &mut image,
+        [plot_min, Point::new(plot_min.x, plot_max.y)],
+        0.75,
+        INK,
+    );
+    fill_capsule(
+        &mut image,
+        [Point::new(plot_min.x, plot_max.y), plot_max],
+        0.75,
+        INK,
+    );
+
+    let below_axis = plot_max.y + LABEL_GAP;
+    draw_label(
+        &mut image,
+        "0",
+        Point::new(plot_min.x, below_axis),
+        LABEL_SIZE,
+    );
+    let largest_label = format!("{}", largest.round());
+    draw_label(
+        &mut image,
+        &largest_label,
+        Point::new(
+            plot_max.x - label_width(&largest_label, LABEL_SIZE),
+            below_axis,
+        ),
+        LABEL_SIZE,
+    );
+    let fullest_label = fullest.to_string();
+    draw_label(
+        &mut image,
+        &fullest_label,
+        Point::new(
+            plot_min.x - LABEL_GAP - label_width(&fullest_label, LABEL_SIZE),
+            plot_min.y,
+        ),
+        LABEL_SIZE,
+    );
+    image
+}
+
+/// Writes `orientation_rose.png` with the bearings of the streets weighted by length,
+/// `block_areas.png` with the areas of the blocks in square meters and `segment_lengths.png` with
+/// the lengths of the streets between intersections in meters to the directory
+pub fn export_diagnostics(
+    city: &City,
+    config: &CityConfig,
+    directory: impl AsRef<Path>,
+) -> io::Result<()> {
+    let directory = directory.as_ref();
+    fs::create_dir_all(directory)?;
+    let scale = config.projection.meters_per_unit as f64;
+
+    let block_areas: Vec<f64> = city
+        .block_triangulations
+        .iter()
+        .map(|triangulation| triangulation.area() as f64 * scale * scale)
+        .collect();
+    let lengths: Vec<f64> = segment_lengths(&city.street_network)
+        .into_iter()
+        .map(|length| length * scale)
+        .collect();
+
+    for (name, image) in [
+        (
+            "orientation_rose.png",
+            render_orientation_rose(&bearing_histogram(&city.street_network, ORIENTATION_BINS)),
+        ),
+        ("block_areas.png", render_histogram(&block_areas)),
+        ("segment_lengths.png", render_histogram(&lengths)),
+    ] {
+        image.save(directory.join(name)).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::schematic::BACKGROUND;
+
+    use super::{BAR, ROSE_SIZE, render_histogram, render_orientation_rose};
+
+    #[test]
+    fn rose_wedges_point_along_the_streets() {
+        // Everything runs north to south
+        let mut histogram = vec![0.0; 36];
+        histogram[0] = 0.5;
+        histogram[18] = 0.5;
+
+        let image = render_orientation_rose(&histogram);
+
+        let middle = ROSE_SIZE / 2;
+        assert_eq!(*image.get_pixel(middle + 3, middle - 100), BAR);
+        assert_eq!(*image.get_pixel(middle - 3, middle + 100), BAR);
+        assert_eq!(*image.get_pixel(middle + 100, middle - 3), BACKGROUND);
+    }
+
+    #[test]
+    fn the_fullest_bin_reaches_the_top() {
+        let mut values = vec![1.0; 10];
+        values.push(100.0);
+
+        let image = render_histogram(&values);
+
+        // The first bin holds ten values and the last one a single value
+        let column_of = |bin: f32| (64.0 + (bin + 0.5) * (640.0 - 96.0) / 30.0) as u32;
+        assert_eq!(*image.get_pixel(column_of(0.0), 40), BAR);
+        assert_eq!(*image.get_pixel(column_of(29.0), 40), BACKGROUND);
+        assert_eq!(*image.get_pixel(column_of(29.0), 320), BAR);
+        assert_eq!(*image.get_pixel(column_of(15.0), 320), BACKGROUND);
+
+        let empty = render_histogram(&[]);
+        assert_eq!(empty.dimensions(), image.dimensions());
+    }
+}
@@ -0,0 +1,388 @@
+use crate::config::{CityConfig, ConfigError};
+use crate::parcels::distance_to_segment;
+use crate::tensor_field::{DesignElement, Point};
+
+/// How close to an element, in grid cells, the pointer has to be to pick it
+const PICK_RADIUS: f32 = 12.0;
+/// Angle a grid turns by for every rotate key press
+const ROTATION_STEP: f32 = std::f32::consts::PI / 24.0;
+/// Factor the length of a grid grows or shrinks by for every scale key press
+const SCALE_STEP: f32 = 1.25;
+/// Length of newly placed grids
+const NEW_GRID_LENGTH: f32 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    /// Picks elements and drags them around
+    Select,
+    PlaceGrid,
+    PlaceRadial,
+    /// Adds a point to the poly line being drawn with every click
+    DrawPolyLine,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorKey {
+    Tool(Tool),
+    RotateClockwise,
+    RotateCounterclockwise,
+    Grow,
+    Shrink,
+    /// Turns the points drawn so far into a poly line element
+    FinishPolyLine,
+    /// Drops the poly line being drawn, or else the selection
+    Cancel,
+    DeleteSelected,
+    Retrace,
+}
+
+impl EditorKey {
+    /// The editor key a key of the viewer stands for. Enter, Escape and Delete come in as
+    /// `'\r'`, `'\u{1b}'` and `'\u{7f}'`.
+    pub fn from_key(key: char) -> Option<EditorKey> {
+        Some(match key {
+            's' => EditorKey::Tool(Tool::Select),
+            'g' => EditorKey::Tool(Tool::PlaceGrid),
+            'r' => EditorKey::Tool(Tool::PlaceRadial),
+            'p' => EditorKey::Tool(Tool::DrawPolyLine),
+            'x' => EditorKey::Tool(Tool::Delete),
+            'e' => EditorKey::RotateClockwise,
+            'q' => EditorKey::RotateCounterclockwise,
+            '+' | '=' => EditorKey::Grow,
+            '-' => EditorKey::Shrink,
+            '\r' => EditorKey::FinishPolyLine,
+            '\u{1b}' => EditorKey::Cancel,
+            '\u{7f}' => EditorKey::DeleteSelected,
+            't' => EditorKey::Retrace,
+            _ => return None,
+        })
+    }
+}
+
+/// Input of the editor, with positions in grid coordinates. The viewer translates window events
+/// into these.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditorInput {
+    PointerPressed(Point),
+    PointerMoved(Point),
+    PointerReleased(Point),
+    Key(EditorKey),
+}
+
+/// What changed since the viewer last asked, see `Editor::take_changes`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EditorChanges {
+    /// The design elements changed, so the tensor field and its glyphs need to be rebuilt
+    pub field: bool,
+    /// The streets should be traced again from the current design elements
+    pub retrace: bool,
+}
+
+/// Edits the design elements of the tensor field from pointer and key input
+#[derive(Debug, Clone, PartialEq)]
+pub struct Editor {
+    elements: Vec<DesignElement>,
+    tool: Tool,
+    selected: Option<usize>,
+    /// Offset from the pointer to the element being dragged
+    drag_offset: Option<Point>,
+    /// Points of the poly line being drawn
+    draft: Vec<Point>,
+    /// Decay constant of newly drawn poly lines
+    poly_line_decay: f32,
+    changes: EditorChanges,
+}
+
+impl Editor {
+    pub fn new(elements: Vec<DesignElement>, poly_line_decay: f32) -> Self {
+        Self {
+            elements,
+            tool: Tool::Select,
+            selected: None,
+            drag_offset: None,
+            draft: Vec::new(),
+            poly_line_decay,
+            changes: EditorChanges::default(),
+        }
+    }
+
+    pub fn elements(&self) -> &[DesignElement] {
+        &self.elements
+    }
+
+    pub fn tool(&self) -> Tool {
+        self.tool
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Points of the poly line being drawn, for the viewer to preview
+    pub fn draft(&self) -> &[Point] {
+        &self.draft
+    }
+
+    /// Returns what changed since the last call and starts over
+    pub fn take_changes(&mut self) -> EditorChanges {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// A copy of the config with the edited design elements, validated so that it can be traced
+    pub fn apply_to(&self, config: &CityConfig) -> Result<CityConfig, ConfigError> {
+        let mut config = config.clone();
+        config.field.design_elements = self.elements.clone();
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn handle(&mut self, input: EditorInput) {
+        match input {
+            EditorInput::PointerPressed(point) => self.press(point),
+            EditorInput::PointerMoved(point) => {
+                if let (Some(index), Some(offset)) = (self.selected, self.drag_offset) {
+                    let target = point + offset;
+                    if let Some(anchor) = anchor(&self.elements[index]) {
+                        translate(&mut self.elements[index], target - anchor);
+                        self.changes.field = true;
+                    }
+                }
+            }
+            EditorInput::PointerReleased(_) => self.drag_offset = None,
+            EditorInput::Key(key) => self.key(key),
+        }
+    }
+
+    /// Index of the element closest to the point within the pick radius. Grids and radial
+    /// elements are picked by their center and poly lines anywhere along them.
+    pub fn element_at(&self, point: Point) -> Option<usize> {
+        self.elements
+            .iter()
+            .enumerate()
+            .map(|(i, element)| (i, distance_to_element(element, point)))
+            .filter(|&(_, distance)| distance <= PICK_RADIUS)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    fn press(&mut self, point: Point) {
+        match self.tool {
+            Tool::Select => {
+                self.selected = self.element_at(point);
+                self.drag_offset = self
+                    .selected
+                    .and_then(|index| anchor(&self.elements[index]))
+                    .map(|anchor| anchor - point);
+            }
+            Tool::PlaceGrid => self.push(DesignElement::Grid {
+                center: point,
+                theta: 0.0,
+                length: NEW_GRID_LENGTH,
+            }),
+            Tool::PlaceRadial => self.push(DesignElement::Radial { center: point }),
+            Tool::DrawPolyLine => {
+                if self.draft.last() != Some(&point) {
+                    self.draft.push(point);
+                }
+            }
+            Tool::Delete => {
+                if let Some(index) = self.element_at(point) {
+                    self.remove(index);
+                }
+            }
+        }
+    }
+
+    fn key(&mut self, key: EditorKey) {
+        match key {
+            EditorKey::Tool(tool) => {
+                self.tool = tool;
+                self.draft.clear();
+                self.drag_offset = None;
+            }
+            EditorKey::RotateClockwise => self.edit_grid(|theta, _| *theta -= ROTATION_STEP),
+            EditorKey::RotateCounterclockwise => self.edit_grid(|theta, _| *theta += ROTATION_STEP),
+            EditorKey::Grow => self.edit_grid(|_, length| *length *= SCALE_STEP),
+            EditorKey::Shrink => self.edit_grid(|_, length| *length /= SCALE_STEP),
+            EditorKey::FinishPolyLine => {
+                if self.draft.len() >= 2 {
+                    let points = std::mem::take(&mut self.draft);
+                    self.push(DesignElement::PolyLine {
+                        points,
+                        decay_constant: self.poly_line_decay,
+                    });
+                }
+            }
+            EditorKey::Cancel => {
+                if self.draft.is_empty() {
+                    self.selected = None;
+                } else {
+                    self.draft.clear();
+                }
+            }
+            EditorKey::DeleteSelected => {
+                if let Some(index) = self.selected {
+                    self.remove(index);
+                }
+            }
+            EditorKey::Retrace => self.changes.retrace = true,
+        }
+    }
+
+    fn push(&mut self, element: DesignElement) {
+        self.elements.push(element);
+        self.selected = Some(self.elements.len() - 1);
+        self.changes.field = true;
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.elements.remove(index);
+        self.selected = match self.selected {
+            Some(selected) if selected == index => None,
+            Some(selected) if selected > index => Some(selected - 1),
+            selected => selected,
+        };
+        self.drag_offset = None;
+        self.changes.field = true;
+    }
+
+    fn edit_grid(&mut self, edit: impl FnOnce(&mut f32, &mut f32)) {
+        if let Some(DesignElement::Grid { theta, length, .. }) =
+            self.selected.map(|index| &mut self.elements[index])
+        {
+            edit(theta, length);
+            self.changes.field = true;
+        }
+    }
+}
+
+/// The point an element is dragged by: the center, or the first point of a poly line
+fn anchor(element: &DesignElement) -> Option<Point> {
+    match element {
+        DesignElement::PolyLine { points, .. } => points.first().copied(),
+        _ => element.center(),
+    }
+}
+
+fn translate(element: &mut DesignElement, offset: Point) {
+    match element {
+        DesignElement::Grid { center, .. } | DesignElement::Radial { center } => *center += offset,
+        DesignElement::PolyLine { points, .. } => {
+            for point in points {
+                *point += offset;
+            }
+        }
+    }
+}
+
+fn distance_to_element(element: &DesignElement, point: Point) -> f32 {
+    match element {
+        DesignElement::Grid { center, .. } | DesignElement::Radial { center } => {
+            (center - point).norm()
+        }
+        DesignElement::PolyLine { points, .. } => points
+            .windows(2)
+            .map(|window| distance_to_segment(point, [window[0], window[1]]))
+            .fold(f32::MAX, f32::min),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::CityConfig;
+    use crate::tensor_field::{DesignElement, Point};
+
+    use super::{Editor, EditorChanges, EditorInput, EditorKey, NEW_GRID_LENGTH, Tool};
+
+    #[test]
+    fn elements_are_placed_dragged_turned_and_deleted() {
+        let mut editor = Editor::new(
+            vec![DesignElement::Radial {
+                center: Point::new(100.0, 100.0),
+            }],
+            0.01,
+        );
+
+        editor.handle(EditorInput::Key(EditorKey::Tool(Tool::PlaceGrid)));
+        editor.handle(EditorInput::PointerPressed(Point::new(300.0, 300.0)));
+        editor.handle(EditorInput::PointerReleased(Point::new(300.0, 300.0)));
+        assert_eq!(editor.selected(), Some(1));
+        editor.handle(EditorInput::Key(EditorKey::RotateCounterclockwise));
+        editor.handle(EditorInput::Key(EditorKey::Grow));
+        let DesignElement::Grid { theta, length, .. } = editor.elements()[1] else {
+            panic!("expected a grid");
+        };
+        assert!(theta > 0.0);
+        assert!(length > NEW_GRID_LENGTH);
+
+        // Grabbing the radial element slightly off center keeps that offset while dragging
+        editor.handle(EditorInput::Key(EditorKey::Tool(Tool::Select)));
+        editor.handle(EditorInput::PointerPressed(Point::new(105.0, 100.0)));
+        editor.handle(EditorInput::PointerMoved(Point::new(155.0, 120.0)));
+        editor.handle(EditorInput::PointerReleased(Point::new(155.0, 120.0)));
+        editor.handle(EditorInput::PointerMoved(Point::new(0.0, 0.0)));
+        assert_eq!(
+            editor.elements()[0],
+            DesignElement::Radial {
+                center: Point::new(150.0, 120.0)
+            }
+        );
+        assert_eq!(
+            editor.take_changes(),
+            EditorChanges {
+                field: true,
+                retrace: false
+            }
+        );
+        assert_eq!(editor.take_changes(), EditorChanges::default());
+
+        editor.handle(EditorInput::Key(EditorKey::Tool(Tool::Delete)));
+        editor.handle(EditorInput::PointerPressed(Point::new(150.0, 125.0)));
+        assert_eq!(editor.elements().len(), 1);
+        assert!(matches!(editor.elements()[0], DesignElement::Grid { .. }));
+        assert!(editor.take_changes().field);
+    }
+
+    #[test]
+    fn poly_lines_are_drawn_point_by_point() {
+        let mut editor = Editor::new(Vec::new(), 0.01);
+        editor.handle(EditorInput::Key(EditorKey::Tool(Tool::DrawPolyLine)));
+        editor.handle(EditorInput::PointerPressed(Point::new(10.0, 10.0)));
+        editor.handle(EditorInput::Key(EditorKey::FinishPolyLine));
+        assert!(editor.elements().is_empty());
+
+        editor.handle(EditorInput::PointerPressed(Point::new(50.0, 10.0)));
+        editor.handle(EditorInput::PointerPressed(Point::new(50.0, 60.0)));
+        assert_eq!(editor.draft().len(), 3);
+        editor.handle(EditorInput::Key(EditorKey::FinishPolyLine));
+
+        assert_eq!(
+            editor.elements(),
+            [DesignElement::PolyLine {
+                points: vec![
+                    Point::new(10.0, 10.0),
+                    Point::new(50.0, 10.0),
+                    Point::new(50.0, 60.0)
+                ],
+                decay_constant: 0.01,
+            }]
+        );
+        assert!(editor.draft().is_empty());
+        // Picked anywhere along the line, not just at its points
+        assert_eq!(editor.element_at(Point::new(52.0, 35.0)), Some(0));
+    }
+
+    #[test]
+    fn retracing_uses_a_validated_config() {
+        let config = CityConfig::default();
+        let mut editor = Editor::new(config.field.design_elements.clone(), 0.01);
+        editor.handle(EditorInput::Key(EditorKey::Retrace));
+        assert!(editor.take_changes().retrace);
+        assert_eq!(editor.apply_to(&config).unwrap(), config);
+
+        let mut empty = Editor::new(Vec::new(), 0.01);
+        empty.handle(EditorInput::Key(EditorKey::Retrace));
+        assert!(empty.apply_to(&config).is_err());
+    }
+}
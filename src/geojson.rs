@@ -0,0 +1,324 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::config::{CityConfig, ProjectionConfig};
+use crate::gtfs::from_lat_lon;
+use crate::tensor_field::{DesignElement, Point};
+
+/// The shape of a feature, with every position as a latitude and longitude in degrees. Multi
+/// geometries are split into one feature per part.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Geometry {
+    /// The outer ring followed by the holes
+    Polygon(Vec<Vec<(f64, f64)>>),
+    LineString(Vec<(f64, f64)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoFeature {
+    pub properties: Map<String, Value>,
+    pub geometry: Geometry,
+}
+
+#[derive(Debug)]
+pub enum GeoJsonError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Json(serde_json::Error),
+    /// The feature at the index has coordinates that don't fit its geometry type
+    InvalidGeometry {
+        feature: usize,
+    },
+}
+
+impl std::fmt::Display for GeoJsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeoJsonError::Io { path, source } => {
+                write!(
+                    f,
+                    "could not read GeoJSON file {}: {source}",
+                    path.display()
+                )
+            }
+            GeoJsonError::Json(err) => write!(f, "could not parse GeoJSON file: {err}"),
+            GeoJsonError::InvalidGeometry { feature } => {
+                write!(f, "feature {feature} has invalid coordinates")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GeoJsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GeoJsonError::Io { source, .. } => Some(source),
+            GeoJsonError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+pub fn read_geojson(path: impl AsRef<Path>) -> Result<Vec<GeoFeature>, GeoJsonError> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path).map_err(|source| GeoJsonError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_geojson(&contents)
+}
+
+/// The polygon and line string features of a feature collection or a single feature. Features
+/// with other geometries, such as points, are left out.
+pub fn parse_geojson(contents: &str) -> Result<Vec<GeoFeature>, GeoJsonError> {
+    let document: Value = serde_json::from_str(contents).map_err(GeoJsonError::Json)?;
+    let features = match document.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => document
+            .get("features")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default(),
+        _ => vec![document],
+    };
+
+    let mut parsed = Vec::new();
+    for (index, feature) in features.iter().enumerate() {
+        let Some(geometry) = feature
+            .get("geometry")
+            .filter(|geometry| !geometry.is_null())
+        else {
+            continue;
+        };
+        let properties = feature
+            .get("properties")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default();
+        let invalid = || GeoJsonError::InvalidGeometry { feature: index };
+        let coordinates = geometry.get("coordinates");
+        let geometries: Vec<Geometry> = match geometry.get("type").and_then(Value::as_str) {
+            Some("Polygon") => vec![Geometry::Polygon(polygon(coordinates).ok_or_else(invalid)?)],
+            Some("MultiPolygon") => children(coordinates)
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|part| polygon(Some(part)).map(Geometry::Polygon))
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?,
+            Some("LineString") => {
+                vec![Geometry::LineString(line(coordinates).ok_or_else(invalid)?)]
+            }
+            Some("MultiLineString") => children(coordinates)
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|part| line(Some(part)).map(Geometry::LineString))
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?,
+            _ => continue,
+        };
+        parsed.extend(geometries.into_iter().map(|geometry| GeoFeature {
+            properties: properties.clone(),
+            geometry,
+        }));
+    }
+    Ok(parsed)
+}
+
+fn children(value: Option<&Value>) -> Option<&Vec<Value>> {
+    value?.as_array()
+}
+
+fn polygon(value: Option<&Value>) -> Option<Vec<Vec<(f64, f64)>>> {
+    children(value)?
+        .iter()
+        .map(|ring| line(Some(ring)))
+        .collect()
+}
+
+/// GeoJSON positions are written longitude first
+fn line(value: Option<&Value>) -> Option<Vec<(f64, f64)>> {
+    children(value)?
+        .iter()
+        .map(|position| {
+            let position = position.as_array()?;
+            Some((position.get(1)?.as_f64()?, position.first()?.as_f64()?))
+        })
+        .collect()
+}
+
+/// Adds the features to the config by their kind in `geojson`: water polygons to the water of
+/// the mask and of the zoning, boundary polygons to the boundary of the mask and rivers to the
+/// design elements. Features of other kinds are left out.
+pub fn import_features(features: &[GeoFeature], config: &mut CityConfig) {
+    for feature in features {
+        let Some(kind) = feature
+            .properties
+            .get(&config.geojson.property)
+            .and_then(Value::as_str)
+        else {
+            continue;
+        };
+        let is = |kinds: &[String]| kinds.iter().any(|candidate| candidate == kind);
+        match &feature.geometry {
+            Geometry::Polygon(rings) => {
+                let rings: Vec<Vec<Point>> = rings
+                    .iter()
+                    .map(|ring| project_ring(ring, &config.projection))
+                    .filter(|ring| ring.len() >= 3)
+                    .collect();
+                if is(&config.geojson.water) {
+                    config.mask.water.extend(rings.iter().cloned());
+                    config.zoning.water.extend(rings);
+                } else if is(&config.geojson.boundary) {
+                    config.mask.boundary.extend(rings);
+                }
+            }
+            Geometry::LineString(positions) => {
+                let mut points = project(positions, &config.projection);
+                points.dedup();
+                if is(&config.geojson.rivers) && points.len() >= 2 {
+                    config.field.design_elements.push(DesignElement::PolyLine {
+                        points,
+                        decay_constant: config.geojson.river_decay,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn project(positions: &[(f64, f64)], projection: &ProjectionConfig) -> Vec<Point> {
+    positions
+        .iter()
+        .map(|&(latitude, longitude)| from_lat_lon(latitude, longitude, projection))
+        .collect()
+}
+
+/// GeoJSON rings repeat their first position at the end, which the mask's rings don't
+fn project_ring(positions: &[(f64, f64)], projection: &ProjectionConfig) -> Vec<Point> {
+    let mut ring = project(positions, projection);
+    ring.dedup();
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    ring
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::CityConfig;
+    use crate::gtfs::to_lat_lon;
+    use crate::tensor_field::{DesignElement, Point};
+
+    use super::{GeoJsonError, Geometry, import_features, parse_geojson};
+
+    fn position(point: Point) -> String {
+        let (latitude, longitude) = to_lat_lon(point, &CityConfig::default().projection);
+        format!("[{longitude}, {latitude}]")
+    }
+
+    fn ring(points: &[(f32, f32)]) -> String {
+        let positions: Vec<String> = points
+            .iter()
+            .chain(points.first())
+            .map(|&(x, y)| position(Point::new(x, y)))
+            .collect();
+        format!("[{}]", positions.join(", "))
+    }
+
+    fn feature(kind: &str, geometry_type: &str, coordinates: &str) -> String {
+        format!(
+            r#"{{"type": "Feature", "properties": {{"kind": "{kind}"}},
+                "geometry": {{"type": "{geometry_type}", "coordinates": {coordinates}}}}}"#
+        )
+    }
+
+    fn collection() -> String {
+        let lake = format!(
+            "[{}, {}]",
+            ring(&[
+                (100.0, 100.0),
+                (200.0, 100.0),
+                (200.0, 200.0),
+                (100.0, 200.0)
+            ]),
+            ring(&[
+                (140.0, 140.0),
+                (160.0, 140.0),
+                (160.0, 160.0),
+                (140.0, 160.0)
+            ])
+        );
+        let limits = format!(
+            "[[{}], [{}]]",
+            ring(&[(0.0, 0.0), (300.0, 0.0), (300.0, 300.0), (0.0, 300.0)]),
+            ring(&[(400.0, 0.0), (500.0, 0.0), (500.0, 100.0)])
+        );
+        let river = format!(
+            "[{}, {}, {}]",
+            position(Point::new(0.0, 250.0)),
+            position(Point::new(150.0, 260.0)),
+            position(Point::new(300.0, 250.0))
+        );
+        let features = [
+            feature("lake", "Polygon", &lake),
+            feature("boundary", "MultiPolygon", &limits),
+            feature("river", "LineString", &river),
+            feature("road", "LineString", &river),
+            feature("river", "Point", &position(Point::new(5.0, 5.0))),
+        ];
+        format!(
+            r#"{{"type": "FeatureCollection", "features": [{}]}}"#,
+            features.join(", ")
+        )
+    }
+
+    #[test]
+    fn polygons_and_lines_are_read_longitude_first() {
+        let features = parse_geojson(&collection()).unwrap();
+
+        assert_eq!(features.len(), 5);
+        let Geometry::Polygon(rings) = &features[0].geometry else {
+            panic!("Expected the lake to be a polygon");
+        };
+        assert_eq!(rings.len(), 2);
+        assert_eq!(rings[0].len(), 5);
+        assert_eq!(
+            rings[0][0],
+            to_lat_lon(Point::new(100.0, 100.0), &CityConfig::default().projection)
+        );
+        assert!(matches!(features[3].geometry, Geometry::LineString(ref line) if line.len() == 3));
+
+        assert!(matches!(
+            parse_geojson(&feature("lake", "Polygon", "[[1.0, 2.0]]")),
+            Err(GeoJsonError::InvalidGeometry { feature: 0 })
+        ));
+        assert!(matches!(parse_geojson("{"), Err(GeoJsonError::Json(_))));
+    }
+
+    #[test]
+    fn features_are_added_to_the_config_by_kind() {
+        let mut config = CityConfig::default();
+        let design_element_count = config.field.design_elements.len();
+
+        import_features(&parse_geojson(&collection()).unwrap(), &mut config);
+
+        assert_eq!(config.mask.water.len(), 2);
+        assert_eq!(config.zoning.water, config.mask.water);
+        assert_eq!(config.mask.water[0].len(), 4);
+        assert!((config.mask.water[0][2] - Point::new(200.0, 200.0)).norm() < 1e-3);
+        assert_eq!(config.mask.boundary.len(), 2);
+        assert_eq!(config.field.design_elements.len(), design_element_count + 1);
+        assert!(matches!(
+            config.field.design_elements.last(),
+            Some(DesignElement::PolyLine { points, .. }) if points.len() == 3
+        ));
+        config.validate().unwrap();
+
+        assert!(config.mask.allows(Point::new(150.0, 150.0)));
+        assert!(!config.mask.allows(Point::new(120.0, 150.0)));
+        assert!(!config.mask.allows(Point::new(350.0, 150.0)));
+    }
+}
@@ -0,0 +1,519 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+use crate::bus_routes::{BusNetwork, BusRoute, BusStop};
+use crate::city::City;
+use crate::config::{CityConfig, ProjectionConfig};
+use crate::metro::{Metro, MetroLine, Station};
+use crate::tensor_field::Point;
+
+/// Mean radius of the earth in meters
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// GTFS route types
+const SUBWAY: u32 = 1;
+const BUS: u32 = 3;
+
+/// The only service of the feed, running every day between the calendar dates
+const SERVICE_ID: &str = "daily";
+const AGENCY_ID: &str = "agency";
+
+/// One route of the feed, with the way it is driven in both directions
+struct FeedRoute {
+    id: String,
+    short_name: String,
+    route_type: u32,
+    /// `rrggbb` without the leading `#`
+    color: Option<String>,
+    /// Meters per second
+    speed: f32,
+    /// Seconds between two trips in the same direction
+    headway: u32,
+    directions: [Pattern; 2],
+}
+
+/// The shape of one direction of a route and the stops along it
+struct Pattern {
+    shape: Vec<Point>,
+    /// Stop ids along with their distance along the shape in city units
+    stops: Vec<(String, f32)>,
+}
+
+/// Builds a static GTFS feed of the metro and the bus routes of the city, as the contents of every
+/// file of the feed by name.
+///
+/// City coordinates are mapped to latitudes and longitudes around the origin in `projection`,
+/// with x pointing east and y pointing north. Every route runs in both
+/// directions from `gtfs.service_start` to `gtfs.service_end`, with a trip leaving either terminal
+/// every headway. Trips take as long as driving the shape at the speed of the mode, plus
+/// `gtfs.dwell_time` at every stop in between. Buses turn around on the loop at the terminal they
+/// arrive at, which is part of the shape of the trip but has no stops.
+///
+/// The config is expected to have been validated already.
+pub fn gtfs_feed(
+    metro: &Metro,
+    buses: &BusNetwork,
+    config: &CityConfig,
+) -> Vec<(&'static str, String)> {
+    let gtfs = &config.gtfs;
+    let projection = &config.projection;
+    let metro_stop_id = |station: usize| format!("M{station}");
+    let bus_stop_id = |stop: usize| format!("B{stop}");
+
+    let mut routes: Vec<FeedRoute> = Vec::new();
+    for (i, line) in metro.lines.iter().enumerate() {
+        let color = &config.schematic.line_colors[i % config.schematic.line_colors.len()];
+        routes.push(FeedRoute {
+            id: format!("M{i}"),
+            short_name: format!("M{}", i + 1),
+            route_type: SUBWAY,
+            color: Some(color.trim_start_matches('#').to_lowercase()),
+            speed: gtfs.metro_speed / 3.6,
+            headway: (gtfs.metro_headway * 60.0).round() as u32,
+            directions: metro_patterns(line, &metro.stations, metro_stop_id),
+        });
+    }
+    for (i, route) in buses.routes.iter().enumerate() {
+        routes.push(FeedRoute {
+            id: format!("B{i}"),
+            short_name: (i + 1).to_string(),
+            route_type: BUS,
+            color: None,
+            speed: gtfs.bus_speed / 3.6,
+            headway: (gtfs.bus_headway * 60.0).round() as u32,
+            directions: bus_patterns(route, &buses.stops, bus_stop_id),
+        });
+    }
+
+    let coordinates = |point: Point| {
+        let (latitude, longitude) = to_lat_lon(point, projection);
+        format!("{latitude:.7},{longitude:.7}")
+    };
+    let meters = |distance: f32| distance * projection.meters_per_unit;
+
+    let mut agency = String::from("agency_id,agency_name,agency_url,agency_timezone\n");
+    agency += &format!(
+        "{AGENCY_ID},{},{},{}\n",
+        csv_field(&gtfs.agency_name),
+        csv_field(&gtfs.agency_url),
+        csv_field(&gtfs.agency_timezone)
+    );
+
+    let mut stops = String::from("stop_id,stop_name,stop_lat,stop_lon\n");
+    for (i, station) in metro.stations.iter().enumerate() {
+        stops += &format!(
+            "{},Station {},{}\n",
+            metro_stop_id(i),
+            i + 1,
+            coordinates(station.position)
+        );
+    }
+    for (i, stop) in buses.stops.iter().enumerate() {
+        stops += &format!(
+            "{},Bus stop {},{}\n",
+            bus_stop_id(i),
+            i + 1,
+            coordinates(stop.position)
+        );
+    }
+
+    let mut route_file =
+        String::from("route_id,agency_id,route_short_name,route_type,route_color\n");
+    for route in &routes {
+        route_file += &format!(
+            "{},{AGENCY_ID},{},{},{}\n",
+            route.id,
+            route.short_name,
+            route.route_type,
+            route.color.as_deref().unwrap_or_default()
+        );
+    }
+
+    let mut shapes =
+        String::from("shape_id,shape_pt_lat,shape_pt_lon,shape_pt_sequence,shape_dist_traveled\n");
+    for route in &routes {
+        for (direction, pattern) in route.directions.iter().enumerate() {
+            let mut travelled = 0.0;
+            for (sequence, &point) in pattern.shape.iter().enumerate() {
+                if sequence > 0 {
+                    travelled += (point - pattern.shape[sequence - 1]).norm();
+                }
+                shapes += &format!(
+                    "{}_{direction},{},{sequence},{:.1}\n",
+                    route.id,
+                    coordinates(point),
+                    meters(travelled)
+                );
+            }
+        }
+    }
+
+    let service_start = parse_gtfs_time(&gtfs.service_start).unwrap_or_default();
+    let service_end = parse_gtfs_time(&gtfs.service_end).unwrap_or_default();
+    let dwell_time = gtfs.dwell_time.round() as u32;
+
+    let mut trips = String::from("route_id,service_id,trip_id,direction_id,shape_id\n");
+    let mut stop_times = String::from(
+        "trip_id,arrival_time,departure_time,stop_id,stop_sequence,shape_dist_traveled\n",
+    );
+    for route in &routes {
+        for (direction, pattern) in route.directions.iter().enumerate() {
+            if pattern.stops.len() < 2 {
+                continue;
+            }
+            let departures = (service_start..=service_end).step_by(route.headway.max(1) as usize);
+            for (trip, departure) in departures.enumerate() {
+                let trip_id = format!("{}_{direction}_{trip}", route.id);
+                trips += &format!(
+                    "{},{SERVICE_ID},{trip_id},{direction},{}_{direction}\n",
+                    route.id, route.id
+                );
+                for (sequence, (stop_id, distance)) in pattern.stops.iter().enumerate() {
+                    let driving = (meters(*distance) / route.speed).round() as u32;
+                    let arrival = departure + driving + dwell_time * sequence as u32;
+                    let leaving = if sequence + 1 < pattern.stops.len() {
+                        arrival + dwell_time
+                    } else {
+                        arrival
+                    };
+                    stop_times += &format!(
+                        "{trip_id},{},{},{stop_id},{sequence},{:.1}\n",
+                        format_gtfs_time(arrival),
+                        format_gtfs_time(leaving),
+                        meters(*distance)
+                    );
+                }
+            }
+        }
+    }
+
+    let mut calendar = String::from(
+        "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n",
+    );
+    calendar += &format!(
+        "{SERVICE_ID},1,1,1,1,1,1,1,{},{}\n",
+        gtfs.start_date, gtfs.end_date
+    );
+
+    vec![
+        ("agency.txt", agency),
+        ("stops.txt", stops),
+        ("routes.txt", route_file),
+        ("trips.txt", trips),
+        ("stop_times.txt", stop_times),
+        ("shapes.txt", shapes),
+        ("calendar.txt", calendar),
+    ]
+}
+
+/// Writes the GTFS feed of the city to `path`, as a zip archive when it ends in `.zip` and as a
+/// directory of text files otherwise
+pub fn export_gtfs(city: &City, config: &CityConfig, path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    let files = gtfs_feed(&city.metro, &city.buses, config);
+
+    let is_zip = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"));
+    if is_zip {
+        let mut archive = ZipWriter::new(BufWriter::new(File::create(path)?));
+        for (name, contents) in files {
+            archive
+                .start_file(name, SimpleFileOptions::default())
+                .map_err(io::Error::other)?;
+            archive.write_all(contents.as_bytes())?;
+        }
+        archive.finish().map_err(io::Error::other)?.flush()
+    } else {
+        fs::create_dir_all(path)?;
+        for (name, contents) in files {
+            fs::write(path.join(name), contents)?;
+        }
+        Ok(())
+    }
+}
+
+fn metro_patterns(
+    line: &MetroLine,
+    stations: &[Station],
+    stop_id: impl Fn(usize) -> String,
+) -> [Pattern; 2] {
+    let pattern = |shape: Vec<Point>, line_stations: Vec<usize>| {
+        let points: Vec<Point> = line_stations
+            .iter()
+            .map(|&station| stations[station].position)
+            .collect();
+        let distances = distances_along(&shape, &points);
+        Pattern {
+            stops: line_stations
+                .into_iter()
+                .map(&stop_id)
+                .zip(distances)
+                .collect(),
+            shape,
+        }
+    };
+    [
+        pattern(line.polyline.clone(), line.stations.clone()),
+        pattern(
+            line.polyline.iter().rev().copied().collect(),
+            line.stations.iter().rev().copied().collect(),
+        ),
+    ]
+}
+
+fn bus_patterns(
+    route: &BusRoute,
+    stops: &[BusStop],
+    stop_id: impl Fn(usize) -> String,
+) -> [Pattern; 2] {
+    let pattern = |path: Vec<Point>, terminal_loop: &[Point], route_stops: Vec<usize>| {
+        let points: Vec<Point> = route_stops
+            .iter()
+            .map(|&stop| stops[stop].position)
+            .collect();
+        // Stops are only on the path, the loop after it is where the bus turns around
+        let distances = distances_along(&path, &points);
+        let mut shape = path;
+        shape.extend(terminal_loop.iter().skip(1));
+        Pattern {
+            stops: route_stops
+                .into_iter()
+                .map(&stop_id)
+                .zip(distances)
+                .collect(),
+            shape,
+        }
+    };
+    [
+        pattern(
+            route.path.clone(),
+            &route.terminal_loops[1],
+            route.stops.clone(),
+        ),
+        pattern(
+            route.path.iter().rev().copied().collect(),
+            &route.terminal_loops[0],
+            route.stops.iter().rev().copied().collect(),
+        ),
+    ]
+}
+
+/// Distance along the polyline of every point, which are expected to lie on it in order
+fn distances_along(polyline: &[Point], points: &[Point]) -> Vec<f32> {
+    let mut segments = Vec::new();
+    let mut travelled = 0.0;
+    for window in polyline.windows(2) {
+        segments.push((window[0], window[1], travelled));
+        travelled += (window[1] - window[0]).norm();
+    }
+
+    let mut first_segment = 0;
+    points
+        .iter()
+        .map(|&point| {
+            let closest = (first_segment..segments.len())
+                .map(|i| {
+                    let (start, end, start_distance) = segments[i];
+                    let length = (end - start).norm();
+                    if length == 0.0 {
+                        return (i, start_distance, (start - point).norm_squared());
+                    }
+                    let along = ((point - start).dot(&(end - start)) / length).clamp(0.0, length);
+                    let projected = start + (end - start) * (along / length);
+                    (
+                        i,
+                        start_distance + along,
+                        (projected - point).norm_squared(),
+                    )
+                })
+                .min_by(|a, b| a.2.total_cmp(&b.2));
+            let Some((segment, distance, _)) = closest else {
+                return 0.0;
+            };
+            first_segment = segment;
+            distance
+        })
+        .collect()
+}
+
+/// Latitude and longitude of a point in city coordinates, on a sphere around the origin
+pub fn to_lat_lon(point: Point, config: &ProjectionConfig) -> (f64, f64) {
+    let east = (point.x * config.meters_per_unit) as f64;
+    let north = (point.y * config.meters_per_unit) as f64;
+    let latitude = config.origin_latitude + (north / EARTH_RADIUS).to_degrees();
+    let longitude = config.origin_longitude
+        + (east / (EARTH_RADIUS * config.origin_latitude.to_radians().cos())).to_degrees();
+    (latitude, longitude)
+}
+
+/// The point in city coordinates at a latitude and longitude, the inverse of `to_lat_lon`
+pub fn from_lat_lon(latitude: f64, longitude: f64, config: &ProjectionConfig) -> Point {
+    let north = (latitude - config.origin_latitude).to_radians() * EARTH_RADIUS;
+    let east = (longitude - config.origin_longitude).to_radians()
+        * EARTH_RADIUS
+        * config.origin_latitude.to_radians().cos();
+    Point::new(east as f32, north as f32) / config.meters_per_unit
+}
+
+/// Seconds since midnight of a GTFS time written as `HH:MM:SS`. Hours may go past 24 for service
+/// running after midnight.
+pub(crate) fn parse_gtfs_time(time: &str) -> Option<u32> {
+    let mut parts = time.split(':');
+    let mut next = |max: u32| {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 2 || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        part.parse::<u32>().ok().filter(|&value| value < max)
+    };
+    let (hours, minutes, seconds) = (next(48)?, next(60)?, next(60)?);
+    parts
+        .next()
+        .is_none()
+        .then_some(hours * 3600 + minutes * 60 + seconds)
+}
+
+fn format_gtfs_time(seconds: u32) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Whether the date is a GTFS date written as `YYYYMMDD`
+pub(crate) fn is_gtfs_date(date: &str) -> bool {
+    if date.len() != 8 || !date.bytes().all(|byte| byte.is_ascii_digit()) {
+        return false;
+    }
+    let month: u32 = date[4..6].parse().unwrap_or_default();
+    let day: u32 = date[6..8].parse().unwrap_or_default();
+    (1..=12).contains(&month) && (1..=31).contains(&day)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bus_routes::BusNetwork;
+    use crate::config::{CityConfig, GtfsConfig, ProjectionConfig};
+    use crate::metro::{Metro, MetroLine, Station};
+    use crate::tensor_field::Point;
+
+    use super::{from_lat_lon, gtfs_feed, is_gtfs_date, parse_gtfs_time, to_lat_lon};
+
+    #[test]
+    fn times_and_dates_are_checked() {
+        assert_eq!(parse_gtfs_time("05:00:00"), Some(5 * 3600));
+        assert_eq!(parse_gtfs_time("25:30:15"), Some(25 * 3600 + 30 * 60 + 15));
+        assert_eq!(parse_gtfs_time("5:00"), None);
+        assert_eq!(parse_gtfs_time("12:60:00"), None);
+        assert_eq!(parse_gtfs_time("12:00:00:00"), None);
+
+        assert!(is_gtfs_date("20250228"));
+        assert!(!is_gtfs_date("2025-02-28"));
+        assert!(!is_gtfs_date("20251301"));
+    }
+
+    #[test]
+    fn coordinates_are_offset_from_the_origin() {
+        let config = ProjectionConfig {
+            origin_latitude: 60.0,
+            origin_longitude: 10.0,
+            meters_per_unit: 2.0,
+        };
+
+        let (latitude, longitude) = to_lat_lon(Point::new(0.0, 500.0), &config);
+        assert!((latitude - 60.008993).abs() < 1e-5);
+        assert_eq!(longitude, 10.0);
+
+        // A degree of longitude is half as long at 60 degrees north
+        let (latitude, longitude) = to_lat_lon(Point::new(500.0, 0.0), &config);
+        assert_eq!(latitude, 60.0);
+        assert!((longitude - 10.017986).abs() < 1e-5);
+
+        let point = Point::new(-120.0, 340.0);
+        let (latitude, longitude) = to_lat_lon(point, &config);
+        assert!((from_lat_lon(latitude, longitude, &config) - point).norm() < 1e-3);
+    }
+
+    #[test]
+    fn trips_run_every_headway_at_the_line_speed() {
+        let config = CityConfig {
+            gtfs: GtfsConfig {
+                service_start: "05:00:00".to_string(),
+                service_end: "06:00:00".to_string(),
+                metro_speed: 36.0,
+                metro_headway: 5.0,
+                dwell_time: 30.0,
+                ..GtfsConfig::default()
+            },
+            ..CityConfig::default()
+        };
+        let stations: Vec<Station> = (0..3)
+            .map(|i| Station {
+                position: Point::new(i as f32 * 1000.0, 0.0),
+                block: i,
+                demand: 1.0,
+            })
+            .collect();
+        let metro = Metro {
+            lines: vec![MetroLine {
+                stations: vec![0, 1, 2],
+                polyline: stations.iter().map(|station| station.position).collect(),
+            }],
+            stations,
+        };
+
+        let feed = gtfs_feed(&metro, &BusNetwork::default(), &config);
+        let file = |name: &str| {
+            feed.iter()
+                .find(|(file_name, _)| *file_name == name)
+                .map(|(_, contents)| contents.clone())
+                .unwrap()
+        };
+
+        let names: Vec<&str> = feed.iter().map(|(name, _)| *name).collect();
+        for name in [
+            "agency.txt",
+            "stops.txt",
+            "routes.txt",
+            "trips.txt",
+            "stop_times.txt",
+            "shapes.txt",
+            "calendar.txt",
+        ] {
+            assert!(names.contains(&name), "{name} is missing");
+        }
+
+        // 13 departures an hour in both directions, plus the header
+        assert_eq!(file("trips.txt").lines().count(), 2 * 13 + 1);
+        let first_trip: Vec<String> = file("stop_times.txt")
+            .lines()
+            .filter(|line| line.starts_with("M0_0_0,"))
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            first_trip,
+            [
+                "M0_0_0,05:00:00,05:00:30,M0,0,0.0",
+                "M0_0_0,05:02:10,05:02:40,M1,1,1000.0",
+                "M0_0_0,05:04:20,05:04:20,M2,2,2000.0",
+            ]
+        );
+        assert!(file("stop_times.txt").contains("M0_1_12,06:00:00,06:00:30,M2,0,0.0"));
+    }
+}
@@ -0,0 +1,325 @@
+use crate::street_plan::{HermiteCurve, resample_curve};
+use crate::tensor_field::Point;
+
+/// Samples per Hermite segment when a recorded curve is turned into a polyline
+const SAMPLES_PER_SPLINE: i32 = 20;
+/// Half the width of the cross a seed is drawn with, in grid units
+const SEED_MARKER_SIZE: f32 = 2.0;
+/// Color of the streets that were finished before the step being shown
+const FINISHED_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 0.6];
+
+/// What one iteration of `trace_street_plan` did
+#[derive(Debug, Clone, Default)]
+pub struct IterationHistory {
+    pub follow_major_eigenvectors: bool,
+    /// The seeds that started a trace, in the order they were taken from the queue
+    pub seeds: Vec<Point>,
+    /// The raw trace of every seed, in the same order as `seeds`
+    pub traces: Vec<Vec<Point>>,
+    /// The traces after smoothing into Hermite curves
+    pub smoothed: Vec<HermiteCurve>,
+    /// The smoothed curves that `clip_pass` kept, cut short where they ran into other streets
+    pub clipped: Vec<HermiteCurve>,
+}
+
+/// What tracing one level of the config did
+#[derive(Debug, Clone, Default)]
+pub struct LevelHistory {
+    pub iterations: Vec<IterationHistory>,
+    /// The streets of the level after `merge_road_endings` joined their ends, which happens once
+    /// after the last iteration
+    pub merged: Vec<HermiteCurve>,
+}
+
+/// Everything street tracing went through, in the same order as `CityConfig::levels`
+#[derive(Debug, Clone, Default)]
+pub struct GenerationHistory {
+    pub levels: Vec<LevelHistory>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Seeds,
+    Traced,
+    Smoothed,
+    Clipped,
+    Merged,
+}
+
+impl Stage {
+    /// The stages of a single iteration, in the order they happen
+    pub const ITERATION: [Stage; 4] =
+        [Stage::Seeds, Stage::Traced, Stage::Smoothed, Stage::Clipped];
+
+    pub fn color(self) -> [f32; 4] {
+        match self {
+            Stage::Seeds => [1.0, 0.8, 0.0, 1.0],
+            Stage::Traced => [1.0, 0.3, 0.3, 1.0],
+            Stage::Smoothed => [0.7, 0.3, 1.0, 1.0],
+            Stage::Clipped => [0.0, 0.6, 1.0, 1.0],
+            Stage::Merged => [0.0, 0.8, 0.3, 1.0],
+        }
+    }
+}
+
+/// One stage of one iteration, or the merge at the end of a level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub level: usize,
+    /// `None` for the merge, which belongs to the whole level
+    pub iteration: Option<usize>,
+    pub stage: Stage,
+}
+
+impl GenerationHistory {
+    /// Every step in the order generation went through them
+    pub fn steps(&self) -> Vec<Step> {
+        self.levels
+            .iter()
+            .enumerate()
+            .flat_map(|(level, history)| {
+                (0..history.iterations.len())
+                    .flat_map(move |iteration| {
+                        Stage::ITERATION.map(|stage| Step {
+                            level,
+                            iteration: Some(iteration),
+                            stage,
+                        })
+                    })
+                    .chain(std::iter::once(Step {
+                        level,
+                        iteration: None,
+                        stage: Stage::Merged,
+                    }))
+            })
+            .collect()
+    }
+
+    /// The polylines a step produced. Seeds are drawn as small crosses.
+    pub fn step_lines(&self, step: Step) -> Vec<Vec<Point>> {
+        let level = &self.levels[step.level];
+        let iteration = step
+            .iteration
+            .and_then(|iteration| level.iterations.get(iteration));
+        match (step.stage, iteration) {
+            (Stage::Merged, _) => curve_lines(&level.merged),
+            (_, None) => Vec::new(),
+            (Stage::Seeds, Some(iteration)) => iteration
+                .seeds
+                .iter()
+                .flat_map(|&seed| {
+                    [Point::x(), Point::y()].map(|axis| {
+                        vec![
+                            seed - axis * SEED_MARKER_SIZE,
+                            seed + axis * SEED_MARKER_SIZE,
+                        ]
+                    })
+                })
+                .collect(),
+            (Stage::Traced, Some(iteration)) => iteration.traces.clone(),
+            (Stage::Smoothed, Some(iteration)) => curve_lines(&iteration.smoothed),
+            (Stage::Clipped, Some(iteration)) => curve_lines(&iteration.clipped),
+        }
+    }
+
+    /// The streets that were already in place when the step began: the merged streets of the
+    /// earlier levels and, within an iteration, the clipped streets of the earlier iterations
+    pub fn finished_before(&self, step: Step) -> Vec<Vec<Point>> {
+        let earlier_levels = self.levels[..step.level]
+            .iter()
+            .flat_map(|level| curve_lines(&level.merged));
+        let earlier_iterations = step
+            .iteration
+            .map(|iteration| &self.levels[step.level].iterations[..iteration])
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|iteration| curve_lines(&iteration.clipped));
+        earlier_levels.chain(earlier_iterations).collect()
+    }
+}
+
+fn curve_lines(curves: &[HermiteCurve]) -> Vec<Vec<Point>> {
+    curves
+        .iter()
+        .filter(|curve| curve.len() >= 2)
+        .map(|curve| resample_curve(curve, SAMPLES_PER_SPLINE))
+        .collect()
+}
+
+/// Steps through a generation history in the viewer, `.` going forward and `,` going back
+#[derive(Debug, Clone)]
+pub struct Playback {
+    steps: Vec<Step>,
+    current: usize,
+}
+
+impl Playback {
+    /// Starts at the first step
+    pub fn new(history: &GenerationHistory) -> Self {
+        Self {
+            steps: history.steps(),
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> Option<Step> {
+        self.steps.get(self.current).copied()
+    }
+
+    /// Moves to the next step, returning whether there was one
+    pub fn step_forward(&mut self) -> bool {
+        let moved = self.current + 1 < self.steps.len();
+        if moved {
+            self.current += 1;
+        }
+        moved
+    }
+
+    /// Moves to the previous step, returning whether there was one
+    pub fn step_back(&mut self) -> bool {
+        let moved = self.current > 0;
+        if moved {
+            self.current -= 1;
+        }
+        moved
+    }
+
+    /// Steps forward or back for `.` and `,`, returning whether the current step changed
+    pub fn handle_key(&mut self, key: char) -> bool {
+        match key {
+            '.' => self.step_forward(),
+            ',' => self.step_back(),
+            _ => false,
+        }
+    }
+
+    /// The lines to draw for the current step with their colors: the finished streets first,
+    /// followed by whatever the step produced in the color of its stage
+    pub fn colored_lines(&self, history: &GenerationHistory) -> Vec<(Vec<Point>, [f32; 4])> {
+        let Some(step) = self.current() else {
+            return Vec::new();
+        };
+        history
+            .finished_before(step)
+            .into_iter()
+            .map(|line| (line, FINISHED_COLOR))
+            .chain(
+                history
+                    .step_lines(step)
+                    .into_iter()
+                    .map(|line| (line, step.stage.color())),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::street_plan::{ControlPoint, HermiteCurve};
+    use crate::tensor_field::Point;
+
+    use super::{
+        FINISHED_COLOR, GenerationHistory, IterationHistory, LevelHistory, Playback, Stage, Step,
+    };
+
+    fn straight_curve(from: Point, to: Point) -> HermiteCurve {
+        [from, to]
+            .map(|position| ControlPoint {
+                position,
+                velocity: to - from,
+            })
+            .to_vec()
+    }
+
+    fn iteration(seed: Point) -> IterationHistory {
+        let end = seed + Point::new(50.0, 0.0);
+        IterationHistory {
+            follow_major_eigenvectors: true,
+            seeds: vec![seed],
+            traces: vec![vec![seed, end]],
+            smoothed: vec![straight_curve(seed, end)],
+            clipped: vec![straight_curve(seed, end)],
+        }
+    }
+
+    fn history() -> GenerationHistory {
+        let first = iteration(Point::new(10.0, 10.0));
+        let second = iteration(Point::new(10.0, 100.0));
+        let merged = first
+            .clipped
+            .iter()
+            .chain(&second.clipped)
+            .cloned()
+            .collect();
+        GenerationHistory {
+            levels: vec![
+                LevelHistory {
+                    iterations: vec![first, second],
+                    merged,
+                },
+                LevelHistory {
+                    iterations: vec![iteration(Point::new(200.0, 200.0))],
+                    merged: Vec::new(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn steps_go_through_every_stage_and_end_each_level_with_the_merge() {
+        let steps = history().steps();
+
+        assert_eq!(steps.len(), 2 * 4 + 1 + 4 + 1);
+        assert_eq!(
+            steps[..5].iter().map(|step| step.stage).collect::<Vec<_>>(),
+            [
+                Stage::Seeds,
+                Stage::Traced,
+                Stage::Smoothed,
+                Stage::Clipped,
+                Stage::Seeds
+            ]
+        );
+        assert_eq!(
+            steps[8],
+            Step {
+                level: 0,
+                iteration: None,
+                stage: Stage::Merged
+            }
+        );
+        assert_eq!(steps[9].level, 1);
+    }
+
+    #[test]
+    fn playback_steps_both_ways_and_shows_finished_streets() {
+        let history = history();
+        let mut playback = Playback::new(&history);
+
+        assert!(!playback.handle_key(','));
+        let seeds = playback.colored_lines(&history);
+        // A cross for the single seed and nothing finished yet
+        assert_eq!(seeds.len(), 2);
+        assert!(
+            seeds
+                .iter()
+                .all(|(_, color)| *color == Stage::Seeds.color())
+        );
+
+        for _ in 0..5 {
+            assert!(playback.handle_key('.'));
+        }
+        assert_eq!(playback.current().unwrap().iteration, Some(1));
+        assert_eq!(playback.current().unwrap().stage, Stage::Traced);
+        let lines = playback.colored_lines(&history);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].1, FINISHED_COLOR);
+        assert_eq!(lines[1].1, Stage::Traced.color());
+
+        assert!(playback.step_back());
+        assert_eq!(playback.current().unwrap().stage, Stage::Seeds);
+        while playback.step_forward() {}
+        assert_eq!(playback.current().unwrap().stage, Stage::Merged);
+        assert!(!playback.handle_key('x'));
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use ordered_float::OrderedFloat;
+
+use crate::predicates::{line_intersection, segments_intersect};
+use crate::snap_rounding::{SnappedFragment, snap_round};
+use crate::street_graph::{
+    GeometryError, IntersectionPoint, Segment, find_interesctions,
+    split_segments_at_intersections_with_parents,
+};
+use crate::tensor_field::Point;
+
+// Everything in here runs the sweep over snap rounded segments, the same way the street graph is
+// built, so layers overlaid with these functions agree with the blocks on where things meet.
+// Points closer together than the snapping pixel count as shared.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedSegment<T> {
+    pub segment: Segment,
+    pub tag: T,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentIntersection<T> {
+    pub position: Point,
+    /// Indices of the two intersecting segments in the input, the lower one first
+    pub segment_indices: [usize; 2],
+    pub tags: [T; 2],
+}
+
+/// Finds every pair of segments that cross or touch without splitting anything. Segments that
+/// only share an endpoint, like consecutive segments of a polyline, are reported too.
+///
+/// Positions of proper crossings are exact up to `f32` rounding, pairs that only nearly touch are
+/// reported at the snapped point they were joined at.
+pub fn report_intersections<T: Clone>(
+    segments: &[TaggedSegment<T>],
+) -> Result<Vec<SegmentIntersection<T>>, GeometryError> {
+    let plain_segments: Vec<Segment> = segments.iter().map(|tagged| tagged.segment).collect();
+    let fragments = snap_round(&plain_segments);
+    let fragment_segments: Vec<Segment> =
+        fragments.iter().map(|fragment| fragment.segment).collect();
+
+    let mut seen_pairs: HashSet<[usize; 2]> = HashSet::new();
+    let mut intersections = Vec::new();
+
+    for vertex in find_interesctions(&fragment_segments, true)? {
+        let sources: BTreeSet<usize> = vertex
+            .intersecting_segment_indices
+            .iter()
+            .flat_map(|&fragment_index| &fragments[fragment_index].sources)
+            .copied()
+            .collect();
+
+        for (i, &first) in sources.iter().enumerate() {
+            for &second in sources.iter().skip(i + 1) {
+                if !seen_pairs.insert([first, second]) {
+                    continue;
+                }
+
+                let (segment_0, segment_1) = (plain_segments[first], plain_segments[second]);
+                let position = if segments_intersect(segment_0, segment_1) {
+                    line_intersection(segment_0, segment_1).unwrap_or(vertex.position())
+                } else {
+                    vertex.position()
+                };
+
+                intersections.push(SegmentIntersection {
+                    position,
+                    segment_indices: [first, second],
+                    tags: [segments[first].tag.clone(), segments[second].tag.clone()],
+                });
+            }
+        }
+    }
+
+    Ok(intersections)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrangementEdge<T> {
+    pub vertices: [usize; 2],
+    /// Indices of every input segment that runs along this edge
+    pub segment_indices: Vec<usize>,
+    pub tags: Vec<T>,
+}
+
+/// The planar graph made by splitting every segment at every point where it meets another one.
+/// Edges only ever meet at their vertices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arrangement<T> {
+    pub vertices: Vec<Point>,
+    pub edges: Vec<ArrangementEdge<T>>,
+}
+
+impl<T> Arrangement<T> {
+    pub fn adjacency_list(&self) -> HashMap<usize, HashSet<usize>> {
+        let mut adjacency_list: HashMap<usize, HashSet<usize>> = (0..self.vertices.len())
+            .map(|vertex| (vertex, HashSet::new()))
+            .collect();
+
+        for ArrangementEdge {
+            vertices: [start, end],
+            ..
+        } in &self.edges
+        {
+            adjacency_list.entry(*start).or_default().insert(*end);
+            adjacency_list.entry(*end).or_default().insert(*start);
+        }
+
+        adjacency_list
+    }
+}
+
+/// Collects tagged segments from any number of layers and turns them into an `Arrangement`
+#[derive(Debug, Clone)]
+pub struct ArrangementBuilder<T> {
+    segments: Vec<TaggedSegment<T>>,
+}
+
+impl<T> Default for ArrangementBuilder<T> {
+    fn default() -> Self {
+        Self {
+            segments: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> ArrangementBuilder<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_segment(&mut self, segment: Segment, tag: T) -> &mut Self {
+        self.segments.push(TaggedSegment { segment, tag });
+        self
+    }
+
+    /// Adds a segment between every pair of consecutive points, all with the same tag
+    pub fn add_polyline(&mut self, points: &[Point], tag: T) -> &mut Self {
+        for window in points.windows(2) {
+            self.add_segment([window[0], window[1]], tag.clone());
+        }
+        self
+    }
+
+    pub fn segments(&self) -> &[TaggedSegment<T>] {
+        &self.segments
+    }
+
+    /// See `report_intersections`
+    pub fn report_intersections(&self) -> Result<Vec<SegmentIntersection<T>>, GeometryError> {
+        report_intersections(&self.segments)
+    }
+
+    pub fn build(&self) -> Result<Arrangement<T>, GeometryError> {
+        let plain_segments: Vec<Segment> =
+            self.segments.iter().map(|tagged| tagged.segment).collect();
+        let fragments = snap_round(&plain_segments);
+        let mut fragment_segments: Vec<Segment> =
+            fragments.iter().map(|fragment| fragment.segment).collect();
+
+        let mut vertices: Vec<IntersectionPoint> =
+            HashSet::<IntersectionPoint>::from_iter(find_interesctions(&fragment_segments, true)?)
+                .into_iter()
+                .collect();
+
+        let (new_segments, parents) =
+            split_segments_at_intersections_with_parents(&mut vertices, &mut fragment_segments);
+
+        let vertex_positions: Vec<Point> =
+            vertices.iter().map(IntersectionPoint::position).collect();
+        let vertex_indices: HashMap<(OrderedFloat<f32>, OrderedFloat<f32>), usize> =
+            vertex_positions
+                .iter()
+                .enumerate()
+                .map(|(i, position)| ((OrderedFloat(position.x), OrderedFloat(position.y)), i))
+                .collect();
+        let vertex_index = |position: Point| {
+            vertex_indices
+                .get(&(OrderedFloat(position.x), OrderedFloat(position.y)))
+                .copied()
+                .ok_or(GeometryError::MissingVertex { position })
+        };
+
+        let edges = fragment_segments
+            .iter()
+            .zip(&fragments)
+            .chain(
+                new_segments
+                    .iter()
+                    .zip(parents.iter().map(|&parent| &fragments[parent])),
+            )
+            .filter(|(segment, _)| segment[0] != segment[1])
+            .map(|(segment, SnappedFragment { sources, .. })| {
+                Ok(ArrangementEdge {
+                    vertices: [vertex_index(segment[0])?, vertex_index(segment[1])?],
+                    segment_indices: sources.clone(),
+                    tags: sources
+                        .iter()
+                        .map(|&source| self.segments[source].tag.clone())
+                        .collect(),
+                })
+            })
+            .collect::<Result<Vec<_>, GeometryError>>()?;
+
+        Ok(Arrangement {
+            vertices: vertex_positions,
+            edges,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tensor_field::Point;
+
+    use super::{ArrangementBuilder, TaggedSegment, report_intersections};
+
+    #[test]
+    fn crossing_segments_are_reported_with_their_tags() {
+        let segments = vec![
+            TaggedSegment {
+                segment: [Point::new(0.0, 0.0), Point::new(4.0, 4.0)],
+                tag: "street",
+            },
+            TaggedSegment {
+                segment: [Point::new(0.0, 3.0), Point::new(3.0, 0.0)],
+                tag: "river",
+            },
+            TaggedSegment {
+                segment: [Point::new(10.0, 0.0), Point::new(10.0, 5.0)],
+                tag: "wall",
+            },
+        ];
+
+        let intersections = report_intersections(&segments).unwrap();
+
+        assert_eq!(intersections.len(), 1);
+        assert_eq!(intersections[0].segment_indices, [0, 1]);
+        assert_eq!(intersections[0].tags, ["street", "river"]);
+        assert!((intersections[0].position - Point::new(1.5, 1.5)).norm() < 0.0001);
+    }
+
+    #[test]
+    fn report_only_leaves_the_segments_alone() {
+        let mut builder = ArrangementBuilder::new();
+        builder
+            .add_segment([Point::new(-2.0, 0.0), Point::new(2.0, 0.0)], 0)
+            .add_segment([Point::new(0.0, -2.0), Point::new(0.0, 2.0)], 1);
+
+        let intersections = builder.report_intersections().unwrap();
+
+        assert_eq!(intersections.len(), 1);
+        assert_eq!(builder.segments().len(), 2);
+    }
+
+    #[test]
+    fn crossing_segments_are_split_into_a_planar_graph() {
+        let mut builder = ArrangementBuilder::new();
+        builder
+            .add_segment([Point::new(-2.0, 0.0), Point::new(2.0, 0.0)], 'a')
+            .add_segment([Point::new(0.0, -2.0), Point::new(0.0, 2.0)], 'b');
+
+        let arrangement = builder.build().unwrap();
+
+        assert_eq!(arrangement.vertices.len(), 5);
+        assert_eq!(arrangement.edges.len(), 4);
+        assert_eq!(
+            arrangement
+                .edges
+                .iter()
+                .filter(|edge| edge.tags == ['a'])
+                .count(),
+            2
+        );
+
+        let center = arrangement
+            .vertices
+            .iter()
+            .position(|vertex| *vertex == Point::new(0.0, 0.0))
+            .unwrap();
+        assert_eq!(arrangement.adjacency_list()[&center].len(), 4);
+    }
+
+    #[test]
+    fn overlapping_segments_share_their_edge() {
+        let mut builder = ArrangementBuilder::new();
+        builder
+            .add_polyline(
+                &[
+                    Point::new(0.0, 0.0),
+                    Point::new(4.0, 0.0),
+                    Point::new(4.0, 4.0),
+                ],
+                "street",
+            )
+            .add_segment([Point::new(2.0, 0.0), Point::new(4.0, 0.0)], "zone");
+
+        let arrangement = builder.build().unwrap();
+
+        let shared_edge = arrangement
+            .edges
+            .iter()
+            .find(|edge| edge.tags.len() == 2)
+            .unwrap();
+        let shared_positions = shared_edge
+            .vertices
+            .map(|vertex| arrangement.vertices[vertex]);
+
+        assert!(shared_positions.contains(&Point::new(2.0, 0.0)));
+        assert!(shared_positions.contains(&Point::new(4.0, 0.0)));
+        assert_eq!(arrangement.edges.len(), 3);
+    }
+}
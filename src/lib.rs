@@ -0,0 +1,38 @@
+pub mod analytics;
+pub mod block_trace;
+pub mod buildings;
+pub mod bus_routes;
+pub mod city;
+pub mod config;
+pub mod controls;
+pub mod diagnostics;
+pub mod editor;
+mod event_queue;
+pub mod geojson;
+pub mod gtfs;
+pub mod history;
+pub mod intersections;
+pub mod mask;
+pub mod mesh_export;
+pub mod metro;
+pub mod osm;
+pub mod parcels;
+pub mod predicates;
+pub mod region;
+pub mod road_surfaces;
+pub mod schematic;
+mod snap_rounding;
+mod status;
+pub mod street_graph;
+pub mod street_network;
+pub mod street_plan;
+pub mod tensor_field;
+#[cfg(test)]
+mod test_support;
+pub mod tiles;
+pub mod triangulation;
+pub mod view;
+pub mod zoning;
+
+pub use city::{City, generate_city, generate_city_around, generate_city_with_history};
+pub use config::CityConfig;
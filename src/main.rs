@@ -1,154 +1,240 @@
-use std::io::Write;
-
 use image::{EncodableLayout, ImageBuffer};
-use nalgebra::Vector2;
+use metro_modeler::{
+    City, CityConfig,
+    analytics::{analyze_network, write_report},
+    block_trace::export_failed_faces,
+    bus_routes::export_bus_feed,
+    controls::{FrameInput, ViewerControls},
+    diagnostics::export_diagnostics,
+    editor::Editor,
+    generate_city_around, generate_city_with_history,
+    geojson::{import_features, read_geojson},
+    gtfs::export_gtfs,
+    history::GenerationHistory,
+    mesh_export::{city_meshes, export_meshes},
+    osm::{import_highways, read_osm},
+    schematic::{export_schematic, layout_schematic},
+    street_graph::GeometryError,
+    street_plan::{HermiteCurve, resample_curve},
+    tensor_field::{GRID_SIZE, Point, TensorField},
+    tiles::generate_tiled_city,
+    view::Camera2D,
+};
+use nalgebra::{Matrix4, Vector2};
 use rayon::prelude::*;
-use street_graph::path_to_graph;
-use street_plan::{HermiteCurve, SeedPoint, merge_road_endings, resample_curve, trace_street_plan};
-use tensor_field::{DesignElement, EvalEigenvectors, GRID_SIZE, Point, TensorField};
 use v4::{
-    builtin_components::mesh_component::{MeshComponent, VertexDescriptor},
+    builtin_components::{
+        camera_component::CameraComponent,
+        mesh_component::{MeshComponent, VertexDescriptor},
+    },
+    component,
+    ecs::{
+        actions::{Action, ActionQueue},
+        component::{ComponentDetails, ComponentId, ComponentSystem, UpdateParams},
+        scene::Scene,
+    },
     engine_support::texture_support::Texture,
     scene,
 };
 use wgpu::vertex_attr_array;
-
-mod event_queue;
-mod status;
-mod street_graph;
-mod street_plan;
-mod tensor_field;
+use winit::{event::MouseButton, keyboard::KeyCode};
+use winit_input_helper::WinitInputHelper;
 
 #[tokio::main]
 async fn main() {
-    let start_time = std::time::Instant::now();
-    let grid_element = DesignElement::Grid {
-        center: Vector2::new(100.0, 100.0),
-        theta: -std::f32::consts::FRAC_PI_3 * 2.0,
-        // theta: 0.0,
-        length: 500.0,
-    };
+    // Usage: metro_modeler [config.toml] [--export city.glb|city.obj]...
+    //     [--schematic metro.svg|metro.png]... [--bus-feed directory]
+    //     [--gtfs feed.zip|directory] [--report report.json]
+    //     [--diagnostics directory] [--history] [--dump-failed-faces directory]
+    //     [--osm roads.osm] [--geojson features.geojson] [--tiles]
+    let mut config_path = None;
+    let mut export_paths = Vec::new();
+    let mut schematic_paths = Vec::new();
+    let mut bus_feed_directory = None;
+    let mut gtfs_path = None;
+    let mut report_path = None;
+    let mut diagnostics_directory = None;
+    let mut record_history = false;
+    let mut failed_face_directory = None;
+    let mut osm_path = None;
+    let mut geojson_path = None;
+    let mut tiled = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--export" {
+            export_paths.extend(args.next());
+        } else if arg == "--schematic" {
+            schematic_paths.extend(args.next());
+        } else if arg == "--bus-feed" {
+            bus_feed_directory = args.next();
+        } else if arg == "--gtfs" {
+            gtfs_path = args.next();
+        } else if arg == "--report" {
+            report_path = args.next();
+        } else if arg == "--diagnostics" {
+            diagnostics_directory = args.next();
+        } else if arg == "--history" {
+            record_history = true;
+        } else if arg == "--dump-failed-faces" {
+            failed_face_directory = args.next();
+        } else if arg == "--osm" {
+            osm_path = args.next();
+        } else if arg == "--geojson" {
+            geojson_path = args.next();
+        } else if arg == "--tiles" {
+            tiled = true;
+        } else {
+            config_path = Some(arg);
+        }
+    }
 
-    let grid_element_2 = DesignElement::Grid {
-        center: Vector2::new(300.0, 400.0),
-        theta: 0.1,
-        length: 200.0,
+    let mut config = match config_path {
+        Some(path) => CityConfig::load(&path).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }),
+        None => CityConfig::default(),
     };
 
-    let radial_element = DesignElement::Radial {
-        center: Vector2::new(200.0, 200.0),
-    };
+    if let Some(path) = &geojson_path {
+        let features = read_geojson(path).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            std::process::exit(1);
+        });
+        import_features(&features, &mut config);
+        if let Err(err) = config.validate() {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
 
-    let grid_element_3 = DesignElement::Grid {
-        center: Vector2::new(0.0, 400.0),
-        theta: 0.7,
-        length: 10.0,
+    let fixed_streets = match &osm_path {
+        Some(path) => {
+            let ways = read_osm(path).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+            import_highways(&ways, &mut config)
+        }
+        None => Vec::new(),
     };
+    if tiled && !fixed_streets.is_empty() {
+        eprintln!("Tiled cities can't be generated around imported streets");
+        std::process::exit(1);
+    }
 
-    let city_center = *radial_element.center().as_ref().unwrap();
-
-    let tensor_field = TensorField::new(
-        vec![grid_element, radial_element, grid_element_2, grid_element_3],
-        0.0004,
-    );
-
-    let (major_network_major_curves_unconnected, major_network_minor_curves_unconnected) =
-        trace_street_plan(
-            &tensor_field,
-            street_plan::TraceSeeds::Random(30),
-            city_center,
-            30.0,
-            5,
-            Vec::new(),
-            Vec::new(),
-        );
+    let start_time = std::time::Instant::now();
 
-    let major_network_major_curves_len = major_network_major_curves_unconnected.len();
+    let generation = Generation {
+        config: config.clone(),
+        fixed_streets,
+        record_history,
+    };
+    let generated = if tiled {
+        generate_tiled_city(&config).map(|tiled| (tiled.city, GenerationHistory::default()))
+    } else {
+        generation.run(&config)
+    };
+    let (city, history) = generated.unwrap_or_else(|err| {
+        eprintln!("Failed to build the street graph: {err}");
+        std::process::exit(1);
+    });
 
-    let major_network_curves_unconnected: Vec<HermiteCurve> =
-        major_network_major_curves_unconnected
-            .into_iter()
-            .chain(major_network_minor_curves_unconnected)
-            .collect();
-    let major_network_merge_distance = 5.0;
-    let major_network_curves = merge_road_endings(
-        &major_network_curves_unconnected,
-        major_network_merge_distance,
-    );
-
-    let minor_network_seed_points: Vec<SeedPoint> = major_network_curves
-        .iter()
-        .flat_map(|curve| {
-            (0..curve.len() - 1)
-                .map(|i| SeedPoint {
-                    seed: (curve[i].position + curve[i + 1].position) / 2.0,
-                    priority: 0.0,
-                    follow_major_eigenvectors: i > major_network_major_curves_len,
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect();
+    println!("{}", start_time.elapsed().as_millis() as f32 / 1000.0);
 
-    let major_network: Vec<Vec<Point>> = major_network_curves
-        .par_iter()
-        .map(|curve| resample_curve(curve, 20))
-        .collect();
-
-    let (minor_network_major_curves_unconnected, minor_network_minor_curves_unconnected) =
-        trace_street_plan(
-            &tensor_field,
-            street_plan::TraceSeeds::Specific(minor_network_seed_points),
-            city_center,
-            5.0,
-            3,
-            major_network_curves[..major_network_major_curves_len].to_vec(),
-            major_network_curves[major_network_major_curves_len..].to_vec(),
+    for failure in &city.failed_faces {
+        eprintln!(
+            "Skipped a block with {} vertices: {}",
+            failure.face.len(),
+            failure.error
         );
+    }
 
-    let minor_network_curves_unconnected: Vec<HermiteCurve> =
-        minor_network_major_curves_unconnected
-            .into_iter()
-            .chain(minor_network_minor_curves_unconnected)
-            .collect();
-    let minor_network_merge_distance = 3.0;
+    for failure in &city.failed_stages {
+        eprintln!("Left out the {}: {}", failure.stage, failure.error);
+    }
 
-    let minor_network_curves = merge_road_endings(
-        &minor_network_curves_unconnected,
-        minor_network_merge_distance,
-    );
+    for skipped in &city.road_surfaces.skipped_faces {
+        eprintln!(
+            "Left a street face with {} curb points without a block: {}",
+            skipped.curb.len(),
+            skipped.reason
+        );
+    }
 
-    let minor_network: Vec<Vec<Point>> = minor_network_curves
-        .par_iter()
-        .map(|curve| resample_curve(curve, 20))
-        .collect();
+    if let Some(directory) = &failed_face_directory {
+        if let Err(err) = export_failed_faces(&city.failed_faces, &config.blocks, directory) {
+            eprintln!("Failed to dump the failed faces to {directory}: {err}");
+        }
+    }
 
-    println!("{}", start_time.elapsed().as_millis() as f32 / 1000.0);
+    if !export_paths.is_empty() {
+        let meshes = city_meshes(&city, &config);
+        for path in &export_paths {
+            if let Err(err) = export_meshes(&meshes, path) {
+                eprintln!("Failed to export {path}: {err}");
+            }
+        }
+    }
 
-    let all_curves: Vec<HermiteCurve> = minor_network_curves
-        .into_iter()
-        .chain(major_network_curves)
-        .collect();
+    if !schematic_paths.is_empty() {
+        let map = layout_schematic(&city.metro, &config.schematic);
+        for path in &schematic_paths {
+            if let Err(err) = export_schematic(&map, path) {
+                eprintln!("Failed to write the metro map to {path}: {err}");
+            }
+        }
+    }
 
-    let faces = path_to_graph(&all_curves, 20.0);
+    if let Some(directory) = &bus_feed_directory {
+        if let Err(err) = export_bus_feed(&city.buses, directory) {
+            eprintln!("Failed to write the bus feed to {directory}: {err}");
+        }
+    }
 
-    let mut output = std::fs::File::create("./out.txt").unwrap();
+    if let Some(path) = &gtfs_path {
+        if let Err(err) = export_gtfs(&city, &config, path) {
+            eprintln!("Failed to write the GTFS feed to {path}: {err}");
+        }
+    }
 
-    dbg!(faces.len());
-    for (i, face) in faces.into_iter().enumerate() {
-        if i % 5000 == 0 {
-            output.write("\n".as_bytes()).unwrap();
+    if let Some(path) = &report_path {
+        let report = analyze_network(
+            &city.street_network,
+            &city.blocks,
+            &city.block_triangulations,
+            &city.land_uses,
+            config.projection.meters_per_unit,
+        );
+        if let Err(err) = write_report(&report, path) {
+            eprintln!("Failed to write the network report to {path}: {err}");
         }
-        output.write_all(
-            format!(
-                "polygon({:?}),",
-                face.iter()
-                    .map(|vertex| (vertex.x, vertex.y))
-                    .collect::<Vec<_>>()
-            )
-            .as_bytes(),
-        ).unwrap();
     }
 
+    if let Some(directory) = &diagnostics_directory {
+        if let Err(err) = export_diagnostics(&city, &config, directory) {
+            eprintln!("Failed to write the diagnostic charts to {directory}: {err}");
+        }
+    }
+
+    // A tiled city reaches past the grid the viewer draws, so it is only exported
+    if tiled {
+        return;
+    }
+
+    let tensor_field = &city.tensor_field;
+
+    let major_network = major_network_lines(&city);
+    let minor_network = minor_network_lines(&city);
+    let (block_vertices, block_indices) = block_mesh(&city);
+    let seed_vertices = seed_vertices(&city);
+
+    let mut controls = ViewerControls::new(Editor::new(
+        config.field.design_elements.clone(),
+        config.field.decay_constant,
+    ));
+    controls.set_history(history);
+    let history_lines = history_vertices(&controls);
     let mut engine = v4::V4::builder()
         .features(wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::POLYGON_MODE_POINT)
         .window_settings(
@@ -160,8 +246,6 @@ async fn main() {
         .build()
         .await;
 
-    let sample_factor = 14;
-
     let mut norm_tex = ImageBuffer::new(GRID_SIZE, GRID_SIZE);
 
     for (x, y, pix) in norm_tex.enumerate_pixels_mut() {
@@ -177,17 +261,31 @@ async fn main() {
     let device = rendering_manager.device();
     let queue = rendering_manager.queue();
 
-    let vector_opacity = 0.2;
-
     scene! {
         scene: visualizer,
+        "camera" = {
+            components: [
+                CameraComponent(
+                    view_matrix: Camera2D::default().view_matrix(),
+                    is_active: true,
+                ),
+            ]
+        },
+        "controls" = {
+            components: [
+                ViewerInput(
+                    controls: controls,
+                    generation: generation,
+                ),
+            ]
+        },
         "eigenvectors" = {
             material: {
                 pipeline: {
                     vertex_shader_path: "./shaders/visualizer_vertex.wgsl",
                     fragment_shader_path: "./shaders/visualizer_fragment.wgsl",
                     vertex_layouts: [Vertex::vertex_layout()],
-                    uses_camera: false,
+                    uses_camera: true,
                     geometry_details: {
                         topology: wgpu::PrimitiveTopology::LineList,
                         polygon_mode: wgpu::PolygonMode::Line,
@@ -196,22 +294,7 @@ async fn main() {
             },
             components: [
                 MeshComponent(
-                    vertices: vec![
-                        (0..GRID_SIZE / sample_factor).flat_map(|x| (0..GRID_SIZE / sample_factor).flat_map(|y| {
-                            let point = Vector2::new(x as f32 * sample_factor as f32, y as f32 * sample_factor as f32);
-                            let tensor = tensor_field.evaluate_smoothed_field_at_point(point);
-                            let eigenvectors = tensor.eigenvectors();
-                            let maj = eigenvectors.major.normalize() * (sample_factor - 1) as f32;
-                            let min = eigenvectors.minor.normalize() * (sample_factor - 1) as f32;
-                            let maj_point = normalize_vector(point + maj);
-                            let min_point = normalize_vector(point + min);
-                            let norm_point = normalize_vector(point);
-                            [
-                                Vertex {pos: [norm_point.x, norm_point.y, 0.0], col: [1.0, 0.0, 0.0, vector_opacity]}, Vertex {pos: [maj_point.x, maj_point.y, 0.0], col: [1.0, 0.0, 0.0, vector_opacity]},
-                                Vertex {pos: [norm_point.x, norm_point.y, 0.0], col: [0.0, 1.0, 0.0, vector_opacity]}, Vertex {pos: [min_point.x, min_point.y, 0.0], col: [0.0, 1.0, 0.0, vector_opacity]}
-                            ]
-                        }).collect::<Vec<_>>()).collect()
-                    ],
+                    vertices: vec![glyph_vertices(tensor_field)],
                     enabled_models: vec![(0, None)]
                 ),
             ]
@@ -222,7 +305,7 @@ async fn main() {
                     vertex_shader_path: "./shaders/degenerate_point_vert.wgsl",
                     fragment_shader_path: "./shaders/degenerate_point_frag.wgsl",
                     vertex_layouts: [TexVertex::vertex_layout()],
-                    uses_camera: false,
+                    uses_camera: true,
                 },
                 attachments: [
                     Texture(
@@ -244,19 +327,19 @@ async fn main() {
                 MeshComponent(
                     vertices: vec![vec![
                         TexVertex {
-                            pos: [-1.0, 1.0, 0.1],
+                            pos: [0.0, GRID_SIZE as f32, 0.1],
                             tex_coords: [0.0, 1.0]
                         },
                         TexVertex {
-                            pos: [-1.0, -1.0, 0.1],
+                            pos: [0.0, 0.0, 0.1],
                             tex_coords: [0.0, 0.0]
                         },
                         TexVertex {
-                            pos: [1.0, -1.0, 0.1],
+                            pos: [GRID_SIZE as f32, 0.0, 0.1],
                             tex_coords: [1.0, 0.0]
                         },
                         TexVertex {
-                            pos: [1.0, 1.0, 0.1],
+                            pos: [GRID_SIZE as f32, GRID_SIZE as f32, 0.1],
                             tex_coords: [1.0, 1.0]
                         },
                     ]],
@@ -265,13 +348,34 @@ async fn main() {
                 )
             ]
         },
+        "blocks" = {
+            material: {
+                pipeline: {
+                    vertex_shader_path: "./shaders/visualizer_vertex.wgsl",
+                    fragment_shader_path: "./shaders/visualizer_fragment.wgsl",
+                    vertex_layouts: [Vertex::vertex_layout()],
+                    uses_camera: true,
+                    geometry_details: {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        polygon_mode: wgpu::PolygonMode::Fill,
+                    },
+                },
+            },
+            components: [
+                MeshComponent(
+                    vertices: vec![block_vertices],
+                    indices: vec![block_indices],
+                    enabled_models: vec![(0, None)]
+                )
+            ]
+        },
         "major_network" = {
             material: {
                 pipeline: {
                     vertex_shader_path: "./shaders/visualizer_vertex.wgsl",
                     fragment_shader_path: "./shaders/visualizer_fragment.wgsl",
                     vertex_layouts: [Vertex::vertex_layout()],
-                    uses_camera: false,
+                    uses_camera: true,
                     geometry_details: {
                         topology: wgpu::PrimitiveTopology::LineStrip,
                         polygon_mode: wgpu::PolygonMode::Line,
@@ -281,20 +385,8 @@ async fn main() {
             },
             components: [
                 MeshComponent(
-                    vertices:
-                        major_network.iter().map(|arr| {
-                            arr.iter().map(|vec| {
-                                    Vertex {
-                                        pos: [
-                                            2.0 * vec.x / GRID_SIZE as f32 - 1.0,
-                                            2.0 * vec.y / GRID_SIZE as f32 - 1.0,
-                                            0.0,
-                                        ],
-                                        col: [0.0, 0.0, 1.0, 1.0]
-                                    }
-                            }).collect::<Vec<_>>()
-                        }).collect(),
-                    enabled_models: major_network.iter().enumerate().map(|(i, _)| (i, None)).collect()
+                    enabled_models: all_models(&major_network),
+                    vertices: major_network,
                 )
             ]
         },
@@ -304,20 +396,39 @@ async fn main() {
             },
             components: [
                 MeshComponent(
-                    vertices:
-                        minor_network.iter().map(|arr| {
-                            arr.iter().map(|vec| {
-                                    Vertex {
-                                        pos: [
-                                            2.0 * vec.x / GRID_SIZE as f32 - 1.0,
-                                            2.0 * vec.y / GRID_SIZE as f32 - 1.0,
-                                            0.0,
-                                        ],
-                                        col: [1.0, 0.0, 0.0, 1.0]
-                                    }
-                            }).collect::<Vec<_>>()
-                        }).collect(),
-                    enabled_models: minor_network.iter().enumerate().map(|(i, _)| (i, None)).collect()
+                    enabled_models: all_models(&minor_network),
+                    vertices: minor_network,
+                )
+            ]
+        },
+        "seeds" = {
+            material: {
+                pipeline: {
+                    vertex_shader_path: "./shaders/visualizer_vertex.wgsl",
+                    fragment_shader_path: "./shaders/visualizer_fragment.wgsl",
+                    vertex_layouts: [Vertex::vertex_layout()],
+                    uses_camera: true,
+                    geometry_details: {
+                        topology: wgpu::PrimitiveTopology::PointList,
+                        polygon_mode: wgpu::PolygonMode::Point,
+                    },
+                },
+            },
+            components: [
+                MeshComponent(
+                    vertices: vec![seed_vertices],
+                    enabled_models: vec![(0, None)]
+                )
+            ]
+        },
+        "history" = {
+            material: {
+                pipeline: ident("network_pipeline"),
+            },
+            components: [
+                MeshComponent(
+                    enabled_models: all_models(&history_lines),
+                    vertices: history_lines,
                 )
             ]
         }
@@ -328,11 +439,308 @@ async fn main() {
     engine.main_loop().await;
 }
 
-fn normalize_vector(vec: Vector2<f32>) -> Vector2<f32> {
-    Vector2::new(
-        2.0 * vec.x / GRID_SIZE as f32 - 1.0,
-        2.0 * vec.y / GRID_SIZE as f32 - 1.0,
-    )
+/// Spacing of the eigenvector glyphs, in grid cells
+const GLYPH_SPACING: u32 = 14;
+const GLYPH_OPACITY: f32 = 0.2;
+
+/// Keys the viewer reacts to, along with the characters `ViewerControls` knows them by
+const VIEWER_KEYS: [(KeyCode, char); 24] = [
+    (KeyCode::KeyS, 's'),
+    (KeyCode::KeyG, 'g'),
+    (KeyCode::KeyR, 'r'),
+    (KeyCode::KeyP, 'p'),
+    (KeyCode::KeyX, 'x'),
+    (KeyCode::KeyE, 'e'),
+    (KeyCode::KeyQ, 'q'),
+    (KeyCode::Equal, '='),
+    (KeyCode::NumpadAdd, '+'),
+    (KeyCode::Minus, '-'),
+    (KeyCode::NumpadSubtract, '-'),
+    (KeyCode::Enter, '\r'),
+    (KeyCode::Escape, '\u{1b}'),
+    (KeyCode::Delete, '\u{7f}'),
+    (KeyCode::KeyT, 't'),
+    (KeyCode::Digit1, '1'),
+    (KeyCode::Digit2, '2'),
+    (KeyCode::Digit3, '3'),
+    (KeyCode::Digit4, '4'),
+    (KeyCode::Digit5, '5'),
+    (KeyCode::Digit6, '6'),
+    (KeyCode::Digit7, '7'),
+    (KeyCode::Comma, ','),
+    (KeyCode::Period, '.'),
+];
+
+/// What the viewer needs to trace the streets again after the design elements were edited
+struct Generation {
+    config: CityConfig,
+    fixed_streets: Vec<HermiteCurve>,
+    record_history: bool,
+}
+
+impl Generation {
+    fn run(&self, config: &CityConfig) -> Result<(City, GenerationHistory), GeometryError> {
+        if self.record_history {
+            generate_city_with_history(config, &self.fixed_streets)
+        } else {
+            generate_city_around(config, &self.fixed_streets)
+                .map(|city| (city, GenerationHistory::default()))
+        }
+    }
+}
+
+/// Feeds the window input to the viewer controls and redraws what they changed
+#[component]
+struct ViewerInput {
+    controls: ViewerControls,
+    generation: Generation,
+}
+
+impl ComponentSystem for ViewerInput {
+    fn update(&mut self, UpdateParams { input_manager, .. }: UpdateParams<'_, '_>) -> ActionQueue {
+        let changes = self.controls.handle(&frame_input(input_manager));
+        let mut actions: ActionQueue = Vec::new();
+
+        if changes.camera_moved {
+            actions.push(Box::new(SetCameraView {
+                view: self.controls.camera.view_matrix(),
+            }));
+        }
+
+        for layer in changes.toggled_layers {
+            actions.push(Box::new(SetEntityEnabled {
+                entity: layer.entity_name(),
+                enabled: self.controls.layers.is_visible(layer),
+            }));
+        }
+
+        if changes.playback_moved {
+            actions.push(ReplaceMesh::boxed(
+                "history",
+                history_vertices(&self.controls),
+                Vec::new(),
+            ));
+        }
+
+        if changes.editor.field {
+            let tensor_field = TensorField::new(
+                self.controls.editor.elements().to_vec(),
+                self.generation.config.field.decay_constant,
+            );
+            actions.push(ReplaceMesh::boxed(
+                "eigenvectors",
+                vec![glyph_vertices(&tensor_field)],
+                Vec::new(),
+            ));
+        }
+
+        if changes.editor.retrace {
+            let config = match self.controls.editor.apply_to(&self.generation.config) {
+                Ok(config) => config,
+                Err(err) => {
+                    eprintln!("Can't trace the edited field: {err}");
+                    return actions;
+                }
+            };
+            match self.generation.run(&config) {
+                Ok((city, history)) => {
+                    actions.extend(city_meshes_actions(&city));
+                    self.controls.set_history(history);
+                    actions.push(ReplaceMesh::boxed(
+                        "history",
+                        history_vertices(&self.controls),
+                        Vec::new(),
+                    ));
+                }
+                Err(err) => eprintln!("Failed to build the street graph: {err}"),
+            }
+        }
+
+        actions
+    }
+}
+
+/// Replacements of every mesh drawn from the city
+fn city_meshes_actions(city: &City) -> ActionQueue {
+    let (block_vertices, block_indices) = block_mesh(city);
+    vec![
+        ReplaceMesh::boxed("major_network", major_network_lines(city), Vec::new()),
+        ReplaceMesh::boxed("minor_network", minor_network_lines(city), Vec::new()),
+        ReplaceMesh::boxed("blocks", vec![block_vertices], vec![block_indices]),
+        ReplaceMesh::boxed("seeds", vec![seed_vertices(city)], Vec::new()),
+    ]
+}
+
+fn frame_input(input: &WinitInputHelper) -> FrameInput {
+    let (width, height) = input.resolution().unwrap_or((GRID_SIZE * 2, GRID_SIZE * 2));
+    let (moved_x, moved_y) = input.cursor_diff();
+    FrameInput {
+        window_size: Point::new(width as f32, height as f32),
+        cursor: input.cursor().map(|(x, y)| Point::new(x, y)),
+        cursor_moved: Point::new(moved_x, moved_y),
+        primary_pressed: input.mouse_pressed(MouseButton::Left),
+        primary_released: input.mouse_released(MouseButton::Left),
+        secondary_held: input.mouse_held(MouseButton::Right),
+        scroll: input.scroll_diff().1,
+        keys: VIEWER_KEYS
+            .iter()
+            .filter(|(code, _)| input.key_pressed(*code))
+            .map(|&(_, key)| key)
+            .collect(),
+    }
+}
+
+/// Swaps the models of the mesh of one of the entities of the scene
+struct ReplaceMesh {
+    entity: &'static str,
+    vertices: Vec<Vec<Vertex>>,
+    indices: Vec<Vec<u32>>,
+}
+
+impl ReplaceMesh {
+    fn boxed(
+        entity: &'static str,
+        vertices: Vec<Vec<Vertex>>,
+        indices: Vec<Vec<u32>>,
+    ) -> Box<dyn Action + Send> {
+        Box::new(Self {
+            entity,
+            vertices,
+            indices,
+        })
+    }
+}
+
+impl Action for ReplaceMesh {
+    fn execute(self: Box<Self>, scene: &mut Scene, device: &wgpu::Device, _queue: &wgpu::Queue) {
+        let Some(mesh) = scene.get_entity_component_mut::<MeshComponent<Vertex>>(self.entity)
+        else {
+            return;
+        };
+        let enabled_models = all_models(&self.vertices);
+        mesh.set_models(self.vertices, self.indices, enabled_models, device);
+    }
+}
+
+/// Points the camera of the scene at what `Camera2D` shows
+struct SetCameraView {
+    view: Matrix4<f32>,
+}
+
+impl Action for SetCameraView {
+    fn execute(self: Box<Self>, scene: &mut Scene, _device: &wgpu::Device, queue: &wgpu::Queue) {
+        if let Some(camera) = scene.get_entity_component_mut::<CameraComponent>("camera") {
+            camera.set_view_matrix(self.view, queue);
+        }
+    }
+}
+
+/// Shows or hides one of the entities of the scene
+struct SetEntityEnabled {
+    entity: &'static str,
+    enabled: bool,
+}
+
+impl Action for SetEntityEnabled {
+    fn execute(self: Box<Self>, scene: &mut Scene, _device: &wgpu::Device, _queue: &wgpu::Queue) {
+        scene.set_entity_enabled(self.entity, self.enabled);
+    }
+}
+
+fn all_models<T>(models: &[T]) -> Vec<(usize, Option<usize>)> {
+    (0..models.len()).map(|model| (model, None)).collect()
+}
+
+fn history_vertices(controls: &ViewerControls) -> Vec<Vec<Vertex>> {
+    controls
+        .history_lines()
+        .into_iter()
+        .map(|(line, col)| {
+            line.into_iter()
+                .map(|point| grid_vertex(point, col))
+                .collect()
+        })
+        .collect()
+}
+
+fn glyph_vertices(tensor_field: &TensorField) -> Vec<Vertex> {
+    tensor_field
+        .eigenvector_glyphs(GLYPH_SPACING)
+        .into_iter()
+        .flat_map(|glyph| {
+            [
+                (glyph.major, [1.0, 0.0, 0.0, GLYPH_OPACITY]),
+                (glyph.minor, [0.0, 1.0, 0.0, GLYPH_OPACITY]),
+            ]
+            .into_iter()
+            .flat_map(move |(direction, col)| {
+                [
+                    grid_vertex(glyph.position, col),
+                    grid_vertex(glyph.position + direction, col),
+                ]
+            })
+        })
+        .collect()
+}
+
+fn major_network_lines(city: &City) -> Vec<Vec<Vertex>> {
+    city.levels[0]
+        .par_iter()
+        .map(|curve| street_line(curve, [0.0, 0.0, 1.0, 1.0]))
+        .collect()
+}
+
+fn minor_network_lines(city: &City) -> Vec<Vec<Vertex>> {
+    city.levels[1..]
+        .par_iter()
+        .flatten()
+        .map(|curve| street_line(curve, [1.0, 0.0, 0.0, 1.0]))
+        .collect()
+}
+
+fn street_line(curve: &HermiteCurve, col: [f32; 4]) -> Vec<Vertex> {
+    resample_curve(curve, 20)
+        .into_iter()
+        .map(|point| grid_vertex(point, col))
+        .collect()
+}
+
+fn block_mesh(city: &City) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    for triangulation in &city.block_triangulations {
+        let start = vertices.len() as u32;
+        vertices.extend(
+            triangulation
+                .vertices
+                .iter()
+                .map(|&vertex| grid_vertex(vertex, [0.6, 0.6, 0.6, 0.5])),
+        );
+        indices.extend(
+            triangulation
+                .triangles
+                .iter()
+                .flatten()
+                .map(|&index| start + index as u32),
+        );
+    }
+    (vertices, indices)
+}
+
+fn seed_vertices(city: &City) -> Vec<Vertex> {
+    city.seeds
+        .iter()
+        .flatten()
+        .map(|&seed| grid_vertex(seed, [1.0, 0.8, 0.0, 1.0]))
+        .collect()
+}
+
+/// A vertex at a grid position, which the camera of the viewer maps onto the window
+fn grid_vertex(point: Point, col: [f32; 4]) -> Vertex {
+    Vertex {
+        pos: [point.x, point.y, 0.0],
+        col,
+    }
 }
 
 #[repr(C)]
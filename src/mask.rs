@@ -0,0 +1,120 @@
+use crate::config::MaskConfig;
+use crate::region::{clip_curve_by, ring_edges, rings_cross};
+use crate::street_graph::Segment;
+use crate::street_plan::HermiteCurve;
+use crate::tensor_field::Point;
+use crate::triangulation::contains_point;
+
+impl MaskConfig {
+    pub fn is_empty(&self) -> bool {
+        self.water.is_empty() && self.boundary.is_empty()
+    }
+
+    /// Whether the point is on land inside the city limits
+    pub fn allows(&self, point: Point) -> bool {
+        (self.boundary.is_empty() || inside_rings(&self.boundary, point))
+            && !inside_rings(&self.water, point)
+    }
+
+    /// The pieces of the curves the mask allows, cut where they cross the shore or the city limits
+    pub fn clip_curves(&self, curves: Vec<HermiteCurve>) -> Vec<HermiteCurve> {
+        if self.is_empty() {
+            return curves;
+        }
+        let edges: Vec<Segment> = self.rings().flat_map(|ring| ring_edges(ring)).collect();
+        curves
+            .iter()
+            .flat_map(|curve| clip_curve_by(curve, &edges, |point| self.allows(point)).0)
+            .collect()
+    }
+
+    /// Whether the block lies entirely on allowed land, without crossing any of the rings or
+    /// enclosing one of them
+    pub fn allows_block(&self, block: &[Point]) -> bool {
+        block.first().is_some_and(|&point| self.allows(point))
+            && self
+                .rings()
+                .all(|ring| !rings_cross(block, ring) && !contains_point(block, ring[0]))
+    }
+
+    /// The same mask moved by `offset`
+    pub fn translated(&self, offset: Point) -> MaskConfig {
+        let translate = |rings: &[Vec<Point>]| {
+            rings
+                .iter()
+                .map(|ring| ring.iter().map(|point| point + offset).collect())
+                .collect()
+        };
+        MaskConfig {
+            water: translate(&self.water),
+            boundary: translate(&self.boundary),
+        }
+    }
+
+    fn rings(&self) -> impl Iterator<Item = &Vec<Point>> {
+        self.water.iter().chain(&self.boundary)
+    }
+}
+
+/// Even-odd test of a point against a set of rings
+fn inside_rings(rings: &[Vec<Point>], point: Point) -> bool {
+    rings
+        .iter()
+        .filter(|ring| contains_point(ring, point))
+        .count()
+        % 2
+        == 1
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::MaskConfig;
+    use crate::tensor_field::Point;
+    use crate::test_support::{curve, square};
+
+    fn mask() -> MaskConfig {
+        MaskConfig {
+            // A lake with an island
+            water: vec![square(100.0, 200.0), square(140.0, 160.0)],
+            boundary: vec![square(0.0, 300.0)],
+        }
+    }
+
+    #[test]
+    fn water_is_masked_except_for_islands() {
+        let mask = mask();
+
+        assert!(mask.allows(Point::new(50.0, 50.0)));
+        assert!(!mask.allows(Point::new(120.0, 120.0)));
+        assert!(mask.allows(Point::new(150.0, 150.0)));
+        assert!(!mask.allows(Point::new(350.0, 50.0)));
+        assert!(MaskConfig::default().allows(Point::new(350.0, 50.0)));
+    }
+
+    #[test]
+    fn streets_are_cut_at_the_shore_and_the_city_limits() {
+        let streets = mask().clip_curves(vec![curve(&[(50.0, 150.0), (350.0, 150.0)])]);
+
+        let ends: Vec<[Point; 2]> = streets
+            .iter()
+            .map(|street| [street[0].position, street.last().unwrap().position])
+            .collect();
+        let expected = [(50.0, 100.0), (140.0, 160.0), (200.0, 300.0)];
+        assert_eq!(ends.len(), expected.len());
+        for ([start, end], (start_x, end_x)) in ends.iter().zip(expected) {
+            assert!((start - Point::new(start_x, 150.0)).norm() < 1e-3);
+            assert!((end - Point::new(end_x, 150.0)).norm() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn blocks_touching_water_or_enclosing_it_are_not_allowed() {
+        let mask = mask();
+
+        assert!(mask.allows_block(&square(10.0, 90.0)));
+        assert!(mask.allows_block(&square(145.0, 155.0)));
+        assert!(!mask.allows_block(&square(90.0, 110.0)));
+        assert!(!mask.allows_block(&square(50.0, 250.0)));
+        assert!(!mask.allows_block(&square(280.0, 320.0)));
+    }
+}
@@ -0,0 +1,540 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::city::{City, PipelineStage};
+use crate::config::CityConfig;
+use crate::street_plan::resample_curve;
+use crate::tensor_field::Point;
+use crate::triangulation::{Triangulation, triangulate};
+use crate::zoning::LandUse;
+
+const ROAD_COLOR: [f32; 4] = [0.25, 0.25, 0.27, 1.0];
+const SIDEWALK_COLOR: [f32; 4] = [0.62, 0.62, 0.6, 1.0];
+
+fn block_color(land_use: LandUse) -> [f32; 4] {
+    match land_use {
+        LandUse::Residential => [0.72, 0.72, 0.68, 1.0],
+        LandUse::Commercial => [0.78, 0.66, 0.62, 1.0],
+        LandUse::Industrial => [0.66, 0.64, 0.72, 1.0],
+        LandUse::Park => [0.45, 0.66, 0.4, 1.0],
+        LandUse::Civic => [0.8, 0.74, 0.55, 1.0],
+        LandUse::WaterAdjacent => [0.62, 0.74, 0.78, 1.0],
+    }
+}
+
+fn building_color(land_use: LandUse) -> [f32; 4] {
+    match land_use {
+        LandUse::Residential | LandUse::Park => [0.86, 0.8, 0.7, 1.0],
+        LandUse::Commercial => [0.7, 0.76, 0.84, 1.0],
+        LandUse::Industrial => [0.66, 0.6, 0.56, 1.0],
+        LandUse::Civic => [0.9, 0.86, 0.76, 1.0],
+        LandUse::WaterAdjacent => [0.88, 0.86, 0.82, 1.0],
+    }
+}
+
+// glTF enums and GLB chunk markers
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const JSON_CHUNK: &[u8; 4] = b"JSON";
+const BIN_CHUNK: &[u8; 4] = b"BIN\0";
+
+/// A triangle mesh with flat normals and a single material named after the mesh. Positions are
+/// Y-up: the city's `x` stays `x`, heights go along `y` and the city's `y` runs along `-z`, so
+/// polygons that are counter-clockwise on the map are counter-clockwise seen from above.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mesh {
+    pub name: String,
+    pub color: [f32; 4],
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    fn new(name: impl Into<String>, color: [f32; 4]) -> Self {
+        Self {
+            name: name.into(),
+            color,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn push_vertex(&mut self, point: Point, height: f32, normal: [f32; 3]) -> u32 {
+        self.positions.push(to_3d(point, height));
+        self.normals.push(normal);
+        self.positions.len() as u32 - 1
+    }
+
+    /// Adds a flat, upward facing polygon at the given height
+    fn push_polygon(&mut self, outline: &[Point], holes: &[Vec<Point>], height: f32) {
+        let Triangulation {
+            vertices,
+            triangles,
+            ..
+        } = triangulate(outline, holes);
+        let start = self.positions.len() as u32;
+        for vertex in vertices {
+            self.push_vertex(vertex, height, [0.0, 1.0, 0.0]);
+        }
+        self.indices.extend(
+            triangles
+                .into_iter()
+                .flatten()
+                .map(|index| start + index as u32),
+        );
+    }
+
+    /// Adds vertical walls between the two heights along every edge of the ring, facing to the
+    /// right of the edges. That is outward for counter-clockwise outlines and for clockwise holes.
+    fn push_walls(&mut self, ring: &[Point], bottom: f32, top: f32) {
+        for i in 0..ring.len() {
+            let (start, end) = (ring[i], ring[(i + 1) % ring.len()]);
+            let Some(direction) = (end - start).try_normalize(f32::EPSILON) else {
+                continue;
+            };
+            let normal = to_3d(Point::new(direction.y, -direction.x), 0.0);
+
+            let corners = [
+                self.push_vertex(start, bottom, normal),
+                self.push_vertex(end, bottom, normal),
+                self.push_vertex(end, top, normal),
+                self.push_vertex(start, top, normal),
+            ];
+            self.push_quad(corners);
+        }
+    }
+
+    fn push_quad(&mut self, [a, b, c, d]: [u32; 4]) {
+        self.indices.extend([a, b, c, a, c, d]);
+    }
+
+    fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        self.positions.iter().fold(
+            ([f32::MAX; 3], [f32::MIN; 3]),
+            |(mut min, mut max), position| {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(position[axis]);
+                    max[axis] = max[axis].max(position[axis]);
+                }
+                (min, max)
+            },
+        )
+    }
+}
+
+fn to_3d(point: Point, height: f32) -> [f32; 3] {
+    [point.x, height, -point.y]
+}
+
+/// Builds one road mesh per street level, named after the level, meshes of the junctions and of
+/// the sidewalks and blocks as slabs at sidewalk height, all from `City::road_surfaces`, and a
+/// mesh of the buildings extruded from their footprints. When the road surfaces could not be
+/// built, the roads are laid as flat strips along the streets instead, which simply overlap where
+/// streets meet.
+pub fn city_meshes(city: &City, config: &CityConfig) -> Vec<Mesh> {
+    let export = &config.export;
+    let surfaces = &city.road_surfaces;
+    let roads_as_strips = city
+        .failed_stages
+        .iter()
+        .any(|failure| failure.stage == PipelineStage::RoadSurfaces);
+
+    let mut meshes: Vec<Mesh> = config
+        .levels
+        .iter()
+        .enumerate()
+        .map(|(level_index, level)| {
+            let mut mesh = Mesh::new(format!("roads_{}", level.name), ROAD_COLOR);
+            if roads_as_strips {
+                let half_width = config.roads.width_of_level(level_index) / 2.0;
+                for curve in city.levels[level_index]
+                    .iter()
+                    .filter(|curve| curve.len() > 1)
+                {
+                    let mut centerline = resample_curve(curve, config.roads.curve_samples as i32);
+                    centerline.dedup_by(|a, b| (*a - *b).norm_squared() < f32::EPSILON);
+                    push_road_strip(&mut mesh, &centerline, half_width);
+                }
+            }
+            for road in surfaces
+                .roads
+                .iter()
+                .filter(|road| road.street.level == level_index)
+            {
+                mesh.push_polygon(&road.polygon, &[], 0.0);
+            }
+            mesh
+        })
+        .collect();
+
+    let mut junctions = Mesh::new("junctions", ROAD_COLOR);
+    for junction in &surfaces.junctions {
+        junctions.push_polygon(&junction.polygon, &[], 0.0);
+    }
+    meshes.push(junctions);
+
+    let mut sidewalks = Mesh::new("sidewalks", SIDEWALK_COLOR);
+    for sidewalk in &surfaces.sidewalks {
+        sidewalks.push_polygon(
+            &sidewalk.outer,
+            std::slice::from_ref(&sidewalk.inner),
+            export.sidewalk_height,
+        );
+        sidewalks.push_walls(&sidewalk.outer, 0.0, export.sidewalk_height);
+    }
+    meshes.push(sidewalks);
+
+    // Blocks and buildings get one mesh per land use so they can be told apart in the exports
+    let mut blocks: Vec<Mesh> = LandUse::ALL
+        .iter()
+        .map(|&land_use| Mesh::new(format!("blocks_{land_use}"), block_color(land_use)))
+        .collect();
+    for (block, block_index) in surfaces.blocks.iter().zip(&city.surface_block_indices) {
+        let land_use = block_index.map_or(LandUse::Residential, |index| city.land_uses[index]);
+        blocks[land_use as usize].push_polygon(block, &[], export.sidewalk_height);
+    }
+
+    let mut buildings: Vec<Mesh> = LandUse::ALL
+        .iter()
+        .map(|&land_use| Mesh::new(format!("buildings_{land_use}"), building_color(land_use)))
+        .collect();
+    for building in &city.buildings {
+        let (bottom, top) = (
+            export.sidewalk_height,
+            export.sidewalk_height + building.height,
+        );
+        let mesh = &mut buildings[building.land_use as usize];
+        mesh.push_polygon(&building.outline, &building.holes, top);
+        for ring in std::iter::once(&building.outline).chain(&building.holes) {
+            mesh.push_walls(ring, bottom, top);
+        }
+    }
+
+    meshes.extend(
+        blocks
+            .into_iter()
+            .chain(buildings)
+            .filter(|mesh| !mesh.indices.is_empty()),
+    );
+
+    meshes
+}
+
+fn push_road_strip(mesh: &mut Mesh, centerline: &[Point], half_width: f32) {
+    if centerline.len() < 2 {
+        return;
+    }
+
+    let up = [0.0, 1.0, 0.0];
+    let mut previous_pair: Option<[u32; 2]> = None;
+
+    for i in 0..centerline.len() {
+        let tangent =
+            centerline[(i + 1).min(centerline.len() - 1)] - centerline[i.saturating_sub(1)];
+        let Some(tangent) = tangent.try_normalize(f32::EPSILON) else {
+            continue;
+        };
+        let left = Point::new(-tangent.y, tangent.x) * half_width;
+
+        let pair = [
+            mesh.push_vertex(centerline[i] - left, 0.0, up),
+            mesh.push_vertex(centerline[i] + left, 0.0, up),
+        ];
+        if let Some([previous_right, previous_left]) = previous_pair {
+            mesh.push_quad([previous_right, pair[0], pair[1], previous_left]);
+        }
+        previous_pair = Some(pair);
+    }
+}
+
+/// Writes the meshes as binary glTF 2.0, one node per mesh, each with its own material
+pub fn write_glb<W: Write>(meshes: &[Mesh], writer: &mut W) -> io::Result<()> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut gltf_meshes = Vec::new();
+    let mut materials = Vec::new();
+    let mut nodes = Vec::new();
+
+    let mut push_view = |buffer: &mut Vec<u8>, bytes: &[u8], target: u32| {
+        let offset = buffer.len();
+        buffer.extend_from_slice(bytes);
+        buffer.resize(buffer.len().next_multiple_of(4), 0);
+        buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        buffer_views.len() - 1
+    };
+
+    for mesh in meshes {
+        if mesh.indices.is_empty() {
+            continue;
+        }
+
+        let positions: Vec<u8> = mesh
+            .positions
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let normals: Vec<u8> = mesh
+            .normals
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let indices: Vec<u8> = mesh
+            .indices
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+
+        let (min, max) = mesh.bounds();
+        let position_view = push_view(&mut buffer, &positions, ARRAY_BUFFER);
+        accessors.push(json!({
+            "bufferView": position_view,
+            "componentType": FLOAT,
+            "count": mesh.positions.len(),
+            "type": "VEC3",
+            "min": min,
+            "max": max,
+        }));
+        let normal_view = push_view(&mut buffer, &normals, ARRAY_BUFFER);
+        accessors.push(json!({
+            "bufferView": normal_view,
+            "componentType": FLOAT,
+            "count": mesh.normals.len(),
+            "type": "VEC3",
+        }));
+        let index_view = push_view(&mut buffer, &indices, ELEMENT_ARRAY_BUFFER);
+        accessors.push(json!({
+            "bufferView": index_view,
+            "componentType": UNSIGNED_INT,
+            "count": mesh.indices.len(),
+            "type": "SCALAR",
+        }));
+
+        materials.push(json!({
+            "name": mesh.name,
+            "pbrMetallicRoughness": {
+                "baseColorFactor": mesh.color,
+                "metallicFactor": 0.0,
+                "roughnessFactor": 0.9,
+            },
+        }));
+        gltf_meshes.push(json!({
+            "name": mesh.name,
+            "primitives": [{
+                "attributes": {
+                    "POSITION": accessors.len() - 3,
+                    "NORMAL": accessors.len() - 2,
+                },
+                "indices": accessors.len() - 1,
+                "material": materials.len() - 1,
+            }],
+        }));
+        nodes.push(json!({ "name": mesh.name, "mesh": gltf_meshes.len() - 1 }));
+    }
+
+    let document = json!({
+        "asset": { "version": "2.0", "generator": "metro_modeler" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": gltf_meshes,
+        "materials": materials,
+        "accessors": accessors,
+        "bufferViews": buffer_views,
+        "buffers": [{ "byteLength": buffer.len() }],
+    });
+
+    let mut json_chunk = serde_json::to_vec(&document).map_err(io::Error::other)?;
+    json_chunk.resize(json_chunk.len().next_multiple_of(4), b' ');
+
+    let total_length = 12 + 8 + json_chunk.len() + 8 + buffer.len();
+
+    writer.write_all(GLB_MAGIC)?;
+    writer.write_all(&2u32.to_le_bytes())?;
+    writer.write_all(&(total_length as u32).to_le_bytes())?;
+    writer.write_all(&(json_chunk.len() as u32).to_le_bytes())?;
+    writer.write_all(JSON_CHUNK)?;
+    writer.write_all(&json_chunk)?;
+    writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+    writer.write_all(BIN_CHUNK)?;
+    writer.write_all(&buffer)?;
+
+    Ok(())
+}
+
+/// Writes the meshes as a Wavefront OBJ, one object per mesh, using materials from the library
+/// written by `write_mtl`
+pub fn write_obj<W: Write>(
+    meshes: &[Mesh],
+    material_library: &str,
+    writer: &mut W,
+) -> io::Result<()> {
+    writeln!(writer, "mtllib {material_library}")?;
+
+    let mut vertex_offset = 1;
+    for mesh in meshes {
+        writeln!(writer, "o {}", mesh.name)?;
+        for [x, y, z] in &mesh.positions {
+            writeln!(writer, "v {x} {y} {z}")?;
+        }
+        for [x, y, z] in &mesh.normals {
+            writeln!(writer, "vn {x} {y} {z}")?;
+        }
+        writeln!(writer, "usemtl {}", mesh.name)?;
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize + vertex_offset);
+            writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?;
+        }
+        vertex_offset += mesh.positions.len();
+    }
+
+    Ok(())
+}
+
+pub fn write_mtl<W: Write>(meshes: &[Mesh], writer: &mut W) -> io::Result<()> {
+    for mesh in meshes {
+        let [r, g, b, a] = mesh.color;
+        writeln!(writer, "newmtl {}", mesh.name)?;
+        writeln!(writer, "Kd {r} {g} {b}")?;
+        writeln!(writer, "d {a}")?;
+    }
+
+    Ok(())
+}
+
+/// Writes `.glb` files as binary glTF and anything else as OBJ, with the material library next
+/// to it
+pub fn export_meshes(meshes: &[Mesh], path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    if path.extension().is_some_and(|extension| extension == "glb") {
+        write_glb(meshes, &mut writer)?;
+    } else {
+        let mtl_path = path.with_extension("mtl");
+        let mtl_name = mtl_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        write_obj(meshes, &mtl_name, &mut writer)?;
+        write_mtl(meshes, &mut BufWriter::new(File::create(&mtl_path)?))?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tensor_field::Point;
+
+    use super::{Mesh, push_road_strip, write_glb, write_obj};
+
+    fn extruded_square() -> Mesh {
+        let mut mesh = Mesh::new("building", [1.0; 4]);
+        let square = vec![
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 0.0),
+            Point::new(1.0, 1.0),
+            Point::new(0.0, 1.0),
+        ];
+        mesh.push_polygon(&square, &[], 2.0);
+        mesh.push_walls(&square, 0.0, 2.0);
+        mesh
+    }
+
+    #[test]
+    fn walls_face_outward() {
+        let mesh = extruded_square();
+
+        assert_eq!(mesh.indices.len(), 3 * (2 + 4 * 2));
+        // The wall along the bottom edge of the square faces away from the map's y axis
+        assert_eq!(mesh.normals[4], [0.0, 0.0, 1.0]);
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[triangle[i] as usize]);
+            let cross = nalgebra::Vector3::from(b) - nalgebra::Vector3::from(a);
+            let cross = cross.cross(&(nalgebra::Vector3::from(c) - nalgebra::Vector3::from(a)));
+            let normal = nalgebra::Vector3::from(mesh.normals[triangle[0] as usize]);
+            assert!(cross.dot(&normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn road_strip_has_the_requested_width() {
+        let mut mesh = Mesh::new("road", [1.0; 4]);
+        push_road_strip(
+            &mut mesh,
+            &[
+                Point::new(0.0, 0.0),
+                Point::new(5.0, 0.0),
+                Point::new(10.0, 0.0),
+            ],
+            1.5,
+        );
+
+        assert_eq!(mesh.positions.len(), 6);
+        assert_eq!(mesh.indices.len(), 12);
+        assert_eq!(mesh.positions[0], [0.0, 0.0, 1.5]);
+        assert_eq!(mesh.positions[1], [0.0, 0.0, -1.5]);
+    }
+
+    #[test]
+    fn glb_has_a_valid_header_and_chunks() {
+        let mut bytes = Vec::new();
+        write_glb(&[extruded_square()], &mut bytes).unwrap();
+
+        assert_eq!(&bytes[0..4], b"glTF");
+        assert_eq!(
+            u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize,
+            bytes.len()
+        );
+
+        let json_length = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+        assert_eq!(&bytes[16..20], b"JSON");
+        assert_eq!(json_length % 4, 0);
+        let document: serde_json::Value =
+            serde_json::from_slice(&bytes[20..20 + json_length]).unwrap();
+        assert_eq!(document["meshes"][0]["name"], "building");
+        assert_eq!(&bytes[24 + json_length..28 + json_length], b"BIN\0");
+    }
+
+    #[test]
+    fn obj_faces_are_one_based_across_objects() {
+        let mut text = Vec::new();
+        write_obj(
+            &[extruded_square(), extruded_square()],
+            "city.mtl",
+            &mut text,
+        )
+        .unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        assert!(text.starts_with("mtllib city.mtl\n"));
+        assert_eq!(text.matches("\no building\n").count(), 2);
+        let face_indices: Vec<usize> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("f "))
+            .flat_map(|face| face.split(' '))
+            .map(|corner| corner.split("//").next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(face_indices.iter().min(), Some(&1));
+        assert_eq!(
+            face_indices.iter().max(),
+            Some(&(2 * extruded_square().positions.len()))
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use crate::config::MetroConfig;
+use crate::tensor_field::Point;
+use crate::triangulation::Triangulation;
+use crate::zoning::LandUse;
+
+/// Stations already served by another line count for this share of their demand when a line
+/// picks its next stop, so lines spread out but may still meet for transfers
+const SERVED_DEMAND_FACTOR: f32 = 0.5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Station {
+    pub position: Point,
+    /// Index of the block in `City::blocks` the station sits in
+    pub block: usize,
+    /// Demand of the blocks within the catchment radius the station was placed for
+    pub demand: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetroLine {
+    /// Indices into `Metro::stations`, in the order the line visits them
+    pub stations: Vec<usize>,
+    /// The track, running through every station in order
+    pub polyline: Vec<Point>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metro {
+    pub stations: Vec<Station>,
+    pub lines: Vec<MetroLine>,
+}
+
+/// Places the stations of a metro network and routes its lines between them.
+///
+/// Every block has a demand of its area, scaled by the weight of its land use and by up to twice
+/// as much close to the city center. Stations are placed one at a time at the block centroid with
+/// the most demand within `catchment_radius` that isn't yet covered by an earlier station, and
+/// never closer than `min_station_spacing` to one.
+///
+/// All lines start at the station with the most demand and leave it in evenly spread directions.
+/// Both ends then grow one station at a time, picking the reachable station with the most demand
+/// per distance that is at most `max_station_spacing` away and doesn't turn the line by more than
+/// `max_turn_angle`, until no station is left to go to.
+pub fn plan_metro(
+    blocks: &[Triangulation],
+    land_uses: &[LandUse],
+    city_center: Point,
+    config: &MetroConfig,
+) -> Metro {
+    let block_demands: Vec<BlockDemand> = blocks
+        .iter()
+        .zip(land_uses)
+        .enumerate()
+        .filter_map(|(index, (block, land_use))| {
+            let centroid = block.centroid()?;
+            let weight = config.land_use_demand.get(land_use).copied().unwrap_or(1.0);
+            let centrality =
+                1.0 - ((centroid - city_center).norm() / config.falloff_distance).clamp(0.0, 1.0);
+            Some(BlockDemand {
+                block: index,
+                centroid,
+                demand: block.area() * weight * (1.0 + centrality),
+            })
+        })
+        .collect();
+
+    let stations = place_stations(&block_demands, config);
+    let lines = route_lines(&stations, config);
+
+    Metro { stations, lines }
+}
+
+/// A block that covers some area, with the index it has in `City::blocks`
+struct BlockDemand {
+    block: usize,
+    centroid: Point,
+    demand: f32,
+}
+
+fn place_stations(block_demands: &[BlockDemand], config: &MetroConfig) -> Vec<Station> {
+    let catchment_squared = config.catchment_radius * config.catchment_radius;
+    let spacing_squared = config.min_station_spacing * config.min_station_spacing;
+
+    let catchments: Vec<Vec<usize>> = block_demands
+        .iter()
+        .map(|block_demand| {
+            (0..block_demands.len())
+                .filter(|&other| {
+                    (block_demands[other].centroid - block_demand.centroid).norm_squared()
+                        <= catchment_squared
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut covered = vec![false; block_demands.len()];
+    let mut stations: Vec<Station> = Vec::new();
+
+    while stations.len() < config.station_count {
+        let best = (0..block_demands.len())
+            .filter(|&candidate| {
+                stations.iter().all(|station| {
+                    (station.position - block_demands[candidate].centroid).norm_squared()
+                        >= spacing_squared
+                })
+            })
+            .map(|candidate| {
+                let demand: f32 = catchments[candidate]
+                    .iter()
+                    .filter(|&&block| !covered[block])
+                    .map(|&block| block_demands[block].demand)
+                    .sum();
+                (candidate, demand)
+            })
+            .filter(|&(_, demand)| demand > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)));
+
+        let Some((candidate, demand)) = best else {
+            break;
+        };
+        for &covered_block in &catchments[candidate] {
+            covered[covered_block] = true;
+        }
+        stations.push(Station {
+            position: block_demands[candidate].centroid,
+            block: block_demands[candidate].block,
+            demand,
+        });
+    }
+
+    stations
+}
+
+fn route_lines(stations: &[Station], config: &MetroConfig) -> Vec<MetroLine> {
+    let Some(hub) = (0..stations.len()).max_by(|&a, &b| {
+        stations[a]
+            .demand
+            .total_cmp(&stations[b].demand)
+            .then(b.cmp(&a))
+    }) else {
+        return Vec::new();
+    };
+
+    let mut served = vec![false; stations.len()];
+    let mut lines = Vec::new();
+    for line_index in 0..config.line_count {
+        // Lines through a shared hub only need half a turn to point every which way
+        let angle = std::f32::consts::PI * line_index as f32 / config.line_count as f32;
+        let heading = Point::new(angle.cos(), angle.sin());
+
+        let mut visited = vec![hub];
+        let forward = extend_line(stations, &served, &mut visited, hub, heading, config);
+        let backward = extend_line(stations, &served, &mut visited, hub, -heading, config);
+
+        let line: Vec<usize> = backward
+            .into_iter()
+            .rev()
+            .chain(std::iter::once(hub))
+            .chain(forward)
+            .collect();
+        // A line that only runs along stations other lines already serve isn't worth building
+        if line.len() < 2 || line.iter().all(|&station| served[station]) {
+            continue;
+        }
+
+        for &station in &line {
+            served[station] = true;
+        }
+        lines.push(MetroLine {
+            polyline: line
+                .iter()
+                .map(|&station| stations[station].position)
+                .collect(),
+            stations: line,
+        });
+    }
+
+    lines
+}
+
+/// Grows one end of a line from `start` in the direction of `heading`, returning the stations it
+/// passes in order, `start` left out
+fn extend_line(
+    stations: &[Station],
+    served: &[bool],
+    visited: &mut Vec<usize>,
+    start: usize,
+    heading: Point,
+    config: &MetroConfig,
+) -> Vec<usize> {
+    let min_alignment = config.max_turn_angle.cos();
+    let mut current = start;
+    let mut heading = heading;
+    let mut path = Vec::new();
+
+    loop {
+        let position = stations[current].position;
+        let next = (0..stations.len())
+            .filter(|station| !visited.contains(station))
+            .filter_map(|station| {
+                let offset = stations[station].position - position;
+                let distance = offset.norm();
+                if distance == 0.0 || distance > config.max_station_spacing {
+                    return None;
+                }
+                let direction = offset / distance;
+                if direction.dot(&heading) < min_alignment {
+                    return None;
+                }
+                let demand = if served[station] {
+                    stations[station].demand * SERVED_DEMAND_FACTOR
+                } else {
+                    stations[station].demand
+                };
+                Some((station, direction, demand / distance))
+            })
+            .max_by(|a, b| a.2.total_cmp(&b.2).then(b.0.cmp(&a.0)));
+
+        let Some((station, direction, _)) = next else {
+            break;
+        };
+        visited.push(station);
+        path.push(station);
+        current = station;
+        heading = direction;
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::MetroConfig;
+    use crate::tensor_field::Point;
+    use crate::triangulation::{Triangulation, triangulate};
+    use crate::zoning::LandUse;
+
+    use super::plan_metro;
+
+    fn grid_of_blocks(count: usize, spacing: f32) -> Vec<Triangulation> {
+        (0..count)
+            .flat_map(|x| (0..count).map(move |y| (x, y)))
+            .map(|(x, y)| {
+                let corner = Point::new(x as f32 * spacing, y as f32 * spacing);
+                triangulate(
+                    &[
+                        corner,
+                        corner + Point::new(10.0, 0.0),
+                        corner + Point::new(10.0, 10.0),
+                        corner + Point::new(0.0, 10.0),
+                    ],
+                    &[],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stations_keep_their_spacing_and_lines_their_turns() {
+        let blocks = grid_of_blocks(20, 12.0);
+        let land_uses = vec![LandUse::Residential; blocks.len()];
+        let config = MetroConfig::default();
+
+        let metro = plan_metro(&blocks, &land_uses, Point::new(120.0, 120.0), &config);
+
+        assert!(metro.stations.len() > 4);
+        for (i, a) in metro.stations.iter().enumerate() {
+            for b in &metro.stations[i + 1..] {
+                assert!((a.position - b.position).norm() >= config.min_station_spacing);
+            }
+        }
+
+        assert!(!metro.lines.is_empty());
+        assert!(metro.lines.len() <= config.line_count);
+        for line in &metro.lines {
+            assert!(line.stations.len() >= 2);
+            assert_eq!(line.polyline.len(), line.stations.len());
+            for window in line.polyline.windows(2) {
+                assert!((window[1] - window[0]).norm() <= config.max_station_spacing);
+            }
+            for window in line.polyline.windows(3) {
+                let incoming = (window[1] - window[0]).normalize();
+                let outgoing = (window[2] - window[1]).normalize();
+                assert!(incoming.dot(&outgoing) >= config.max_turn_angle.cos() - 0.0001);
+            }
+        }
+    }
+
+    #[test]
+    fn busiest_stations_go_where_the_demand_is() {
+        let blocks = grid_of_blocks(20, 12.0);
+        let land_uses: Vec<LandUse> = (0..blocks.len())
+            .map(|i| {
+                if i / 20 >= 15 && i % 20 >= 15 {
+                    LandUse::Commercial
+                } else {
+                    LandUse::Park
+                }
+            })
+            .collect();
+        let config = MetroConfig {
+            station_count: 1,
+            ..MetroConfig::default()
+        };
+
+        let metro = plan_metro(&blocks, &land_uses, Point::new(0.0, 0.0), &config);
+
+        assert_eq!(metro.stations.len(), 1);
+        let position = metro.stations[0].position;
+        assert!(position.x > 150.0 && position.y > 150.0);
+        assert!(metro.lines.is_empty());
+    }
+
+    #[test]
+    fn stations_point_at_their_block_past_empty_ones() {
+        let mut blocks = vec![Triangulation::default(); 3];
+        blocks.extend(grid_of_blocks(1, 12.0));
+        let land_uses = vec![LandUse::Residential; blocks.len()];
+        let config = MetroConfig {
+            station_count: 1,
+            ..MetroConfig::default()
+        };
+
+        let metro = plan_metro(&blocks, &land_uses, Point::new(0.0, 0.0), &config);
+
+        assert_eq!(metro.stations.len(), 1);
+        assert_eq!(metro.stations[0].block, 3);
+    }
+}
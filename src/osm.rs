@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::config::{CityConfig, OsmConfig, ProjectionConfig};
+use crate::gtfs::from_lat_lon;
+use crate::region::clip_curve;
+use crate::street_plan::{ControlPoint, HermiteCurve};
+use crate::tensor_field::{DesignElement, GRID_SIZE, Point};
+
+/// A road of an OpenStreetMap file
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsmWay {
+    pub id: i64,
+    /// Value of the way's `highway` tag
+    pub highway: String,
+    /// Latitude and longitude of every node of the way, in degrees
+    pub nodes: Vec<(f64, f64)>,
+}
+
+#[derive(Debug)]
+pub enum OsmError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Xml(roxmltree::Error),
+    /// An element is missing an attribute it needs, or the attribute is not a number
+    InvalidAttribute {
+        element: String,
+        attribute: &'static str,
+    },
+}
+
+impl std::fmt::Display for OsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OsmError::Io { path, source } => {
+                write!(f, "could not read OSM file {}: {source}", path.display())
+            }
+            OsmError::Xml(err) => write!(f, "could not parse OSM file: {err}"),
+            OsmError::InvalidAttribute { element, attribute } => {
+                write!(
+                    f,
+                    "`{element}` element has a missing or invalid `{attribute}`"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for OsmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OsmError::Io { source, .. } => Some(source),
+            OsmError::Xml(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+pub fn read_osm(path: impl AsRef<Path>) -> Result<Vec<OsmWay>, OsmError> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path).map_err(|source| OsmError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_osm(&contents)
+}
+
+/// The ways of an OSM XML document that have a `highway` tag. Extracts cut out of a larger map
+/// refer to nodes past their bounds that are not in the file, so ways are split where nodes are
+/// missing.
+pub fn parse_osm(contents: &str) -> Result<Vec<OsmWay>, OsmError> {
+    let document = roxmltree::Document::parse(contents).map_err(OsmError::Xml)?;
+    let elements = |name: &'static str| {
+        document
+            .root_element()
+            .children()
+            .filter(move |element| element.has_tag_name(name))
+    };
+
+    let mut nodes: HashMap<i64, (f64, f64)> = HashMap::new();
+    for node in elements("node") {
+        nodes.insert(
+            attribute(node, "id")?,
+            (attribute(node, "lat")?, attribute(node, "lon")?),
+        );
+    }
+
+    let mut ways = Vec::new();
+    for way in elements("way") {
+        let Some(highway) = way
+            .children()
+            .filter(|child| child.has_tag_name("tag"))
+            .find(|tag| tag.attribute("k") == Some("highway"))
+            .and_then(|tag| tag.attribute("v"))
+        else {
+            continue;
+        };
+        let id = attribute(way, "id")?;
+
+        let mut runs: Vec<Vec<(f64, f64)>> = vec![Vec::new()];
+        for node_ref in way.children().filter(|child| child.has_tag_name("nd")) {
+            match nodes.get(&attribute(node_ref, "ref")?) {
+                Some(&node) => runs.last_mut().unwrap().push(node),
+                None => runs.push(Vec::new()),
+            }
+        }
+        ways.extend(
+            runs.into_iter()
+                .filter(|run| run.len() >= 2)
+                .map(|nodes| OsmWay {
+                    id,
+                    highway: highway.to_string(),
+                    nodes,
+                }),
+        );
+    }
+    Ok(ways)
+}
+
+fn attribute<T: FromStr>(element: roxmltree::Node, name: &'static str) -> Result<T, OsmError> {
+    element
+        .attribute(name)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| OsmError::InvalidAttribute {
+            element: element.tag_name().name().to_string(),
+            attribute: name,
+        })
+}
+
+/// The streets to generate a city around with `generate_city_around`, made from the ways with one
+/// of the highway tags in `osm.highways`. When `osm.design_elements` is set, the major highways
+/// are also added to the config's tensor field as polyline design elements.
+pub fn import_highways(ways: &[OsmWay], config: &mut CityConfig) -> Vec<HermiteCurve> {
+    if config.osm.design_elements {
+        let elements = highway_design_elements(ways, &config.osm, &config.projection);
+        config.field.design_elements.extend(elements);
+    }
+    highway_curves(ways, &config.osm, &config.projection)
+}
+
+/// The imported highways in city coordinates, cut at the edges of the tensor field grid. Every
+/// node becomes a control point heading from the node before it towards the node after it.
+pub fn highway_curves(
+    ways: &[OsmWay],
+    osm: &OsmConfig,
+    projection: &ProjectionConfig,
+) -> Vec<HermiteCurve> {
+    imported_ways(ways, osm, projection)
+        .map(|(_, curve)| curve)
+        .collect()
+}
+
+/// A polyline design element along every major highway, inside the tensor field grid
+pub fn highway_design_elements(
+    ways: &[OsmWay],
+    osm: &OsmConfig,
+    projection: &ProjectionConfig,
+) -> Vec<DesignElement> {
+    imported_ways(ways, osm, projection)
+        .filter(|(way, _)| osm.major_highways.contains(&way.highway))
+        .map(|(_, curve)| DesignElement::PolyLine {
+            points: curve
+                .iter()
+                .map(|control_point| control_point.position)
+                .collect(),
+            decay_constant: osm.design_element_decay,
+        })
+        .collect()
+}
+
+fn imported_ways<'a>(
+    ways: &'a [OsmWay],
+    osm: &'a OsmConfig,
+    projection: &'a ProjectionConfig,
+) -> impl Iterator<Item = (&'a OsmWay, HermiteCurve)> + 'a {
+    let size = GRID_SIZE as f32;
+    let grid = [
+        Point::new(0.0, 0.0),
+        Point::new(size, 0.0),
+        Point::new(size, size),
+        Point::new(0.0, size),
+    ];
+    ways.iter()
+        .filter(|way| osm.highways.contains(&way.highway))
+        .flat_map(move |way| {
+            let mut points: Vec<Point> = way
+                .nodes
+                .iter()
+                .map(|&(latitude, longitude)| from_lat_lon(latitude, longitude, projection))
+                .collect();
+            points.dedup();
+            clip_curve(&polyline_curve(&points), &grid, true)
+                .0
+                .into_iter()
+                .map(move |curve| (way, curve))
+        })
+}
+
+fn polyline_curve(points: &[Point]) -> HermiteCurve {
+    let last = points.len().saturating_sub(1);
+    (0..points.len())
+        .map(|index| {
+            let [previous, next] = [index.saturating_sub(1), (index + 1).min(last)];
+            ControlPoint {
+                position: points[index],
+                velocity: (points[next] - points[previous]) / (next - previous).max(1) as f32,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{CityConfig, OsmConfig, ProjectionConfig};
+    use crate::gtfs::to_lat_lon;
+    use crate::tensor_field::{DesignElement, Point};
+
+    use super::{OsmError, OsmWay, highway_curves, import_highways, parse_osm};
+
+    const EXTRACT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6">
+  <bounds minlat="45.0" minlon="0.0" maxlat="45.01" maxlon="0.01"/>
+  <node id="1" lat="45.001" lon="0.001"/>
+  <node id="2" lat="45.002" lon="0.001"/>
+  <node id="3" lat="45.003" lon="0.002"/>
+  <node id="5" lat="45.004" lon="0.003"/>
+  <node id="6" lat="45.005" lon="0.003"/>
+  <way id="10">
+    <nd ref="1"/><nd ref="2"/><nd ref="3"/><nd ref="4"/><nd ref="5"/><nd ref="6"/>
+    <tag k="highway" v="primary"/>
+    <tag k="name" v="Main Street"/>
+  </way>
+  <way id="11">
+    <nd ref="1"/><nd ref="5"/>
+    <tag k="building" v="yes"/>
+  </way>
+</osm>"#;
+
+    fn way(highway: &str, points: &[Point], projection: &ProjectionConfig) -> OsmWay {
+        OsmWay {
+            id: 1,
+            highway: highway.to_string(),
+            nodes: points
+                .iter()
+                .map(|&point| to_lat_lon(point, projection))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn highways_are_read_and_split_at_missing_nodes() {
+        let ways = parse_osm(EXTRACT).unwrap();
+
+        assert_eq!(ways.len(), 2);
+        assert!(
+            ways.iter()
+                .all(|way| way.id == 10 && way.highway == "primary")
+        );
+        assert_eq!(
+            ways[0].nodes,
+            [(45.001, 0.001), (45.002, 0.001), (45.003, 0.002)]
+        );
+        assert_eq!(ways[1].nodes.len(), 2);
+
+        assert!(matches!(
+            parse_osm(&EXTRACT.replace(r#"lat="45.002""#, r#"lat="north""#)),
+            Err(OsmError::InvalidAttribute {
+                attribute: "lat",
+                ..
+            })
+        ));
+        assert!(matches!(parse_osm("<osm>"), Err(OsmError::Xml(_))));
+    }
+
+    #[test]
+    fn highways_are_placed_in_city_coordinates_and_cut_at_the_grid() {
+        let projection = ProjectionConfig::default();
+        let osm = OsmConfig::default();
+        let ways = [
+            way(
+                "residential",
+                &[
+                    Point::new(100.0, 100.0),
+                    Point::new(200.0, 100.0),
+                    Point::new(600.0, 100.0),
+                ],
+                &projection,
+            ),
+            way(
+                "footway",
+                &[Point::new(10.0, 10.0), Point::new(20.0, 10.0)],
+                &projection,
+            ),
+        ];
+
+        let curves = highway_curves(&ways, &osm, &projection);
+
+        assert_eq!(curves.len(), 1);
+        assert_eq!(curves[0].len(), 3);
+        assert!((curves[0][0].position - Point::new(100.0, 100.0)).norm() < 1e-3);
+        assert!((curves[0][1].velocity - Point::new(250.0, 0.0)).norm() < 1e-2);
+        assert!((curves[0][2].position - Point::new(512.0, 100.0)).norm() < 1e-3);
+    }
+
+    #[test]
+    fn major_highways_become_design_elements_when_asked_for() {
+        let mut config = CityConfig::default();
+        let design_element_count = config.field.design_elements.len();
+        let ways = [
+            way(
+                "primary",
+                &[Point::new(100.0, 100.0), Point::new(300.0, 300.0)],
+                &config.projection,
+            ),
+            way(
+                "residential",
+                &[Point::new(100.0, 300.0), Point::new(300.0, 300.0)],
+                &config.projection,
+            ),
+        ];
+
+        assert_eq!(import_highways(&ways, &mut config).len(), 2);
+        assert_eq!(config.field.design_elements.len(), design_element_count);
+
+        config.osm.design_elements = true;
+        assert_eq!(import_highways(&ways, &mut config).len(), 2);
+        assert_eq!(config.field.design_elements.len(), design_element_count + 1);
+        assert!(matches!(
+            config.field.design_elements.last(),
+            Some(DesignElement::PolyLine { points, .. }) if points.len() == 2
+        ));
+    }
+}
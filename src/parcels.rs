@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+
+use crate::config::ParcelConfig;
+use crate::street_graph::{Segment, face_area};
+use crate::street_plan::HermiteCurve;
+use crate::tensor_field::Point;
+
+/// How much further than the inset distance a block edge may be from a street and still count
+/// as facing it. Flattening and point merging move block edges around a little.
+pub(crate) const FRONTAGE_TOLERANCE: f32 = 0.5;
+
+/// Pieces smaller than this left over from cutting a lot are thrown away
+const MIN_LOT_AREA: f32 = 0.01;
+
+const STREET_BUCKET_SIZE: f32 = 8.0;
+
+/// A street of the city, as the index of its level and its index inside that level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StreetId {
+    pub level: usize,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parcel {
+    pub polygon: Vec<Point>,
+    /// Index of the block in `City::blocks` the parcel was cut from
+    pub block: usize,
+    /// The longest of the parcel's edges that face a street
+    pub front: Segment,
+    /// The street that `front` faces
+    pub street: StreetId,
+    /// Total length of the parcel's edges that face a street
+    pub frontage: f32,
+}
+
+/// A polygon along with the street each of its edges faces, if any. `fronts[i]` belongs to the
+/// edge from `points[i]` to `points[i + 1]`.
+#[derive(Debug, Clone)]
+struct Lot {
+    points: Vec<Point>,
+    fronts: Vec<Option<StreetId>>,
+}
+
+impl Lot {
+    fn edges(&self) -> impl Iterator<Item = (Point, Point, Option<StreetId>)> + '_ {
+        (0..self.points.len()).map(|i| {
+            (
+                self.points[i],
+                self.points[(i + 1) % self.points.len()],
+                self.fronts[i],
+            )
+        })
+    }
+
+    fn frontage(&self) -> f32 {
+        self.edges()
+            .filter(|(_, _, street)| street.is_some())
+            .map(|(start, end, _)| (end - start).norm())
+            .sum()
+    }
+
+    /// The longest edge that faces a street
+    fn main_frontage(&self) -> Option<(Point, Point, StreetId)> {
+        self.edges()
+            .filter_map(|(start, end, street)| street.map(|street| (start, end, street)))
+            .max_by(|a, b| {
+                (a.1 - a.0)
+                    .norm_squared()
+                    .total_cmp(&(b.1 - b.0).norm_squared())
+            })
+    }
+
+    fn area(&self) -> f32 {
+        face_area(&self.points).abs()
+    }
+
+    /// Keeps the part of the lot where `(point - origin) . normal <= 0`. The new edge along the
+    /// cut doesn't face any street.
+    fn clip(&self, origin: Point, normal: Point) -> Lot {
+        let side = |point: Point| (point - origin).dot(&normal);
+        let crossing = |start: Point, end: Point| {
+            let (start_side, end_side) = (side(start), side(end));
+            start + (end - start) * (start_side / (start_side - end_side))
+        };
+
+        let mut clipped = Lot {
+            points: Vec::new(),
+            fronts: Vec::new(),
+        };
+
+        for (start, end, street) in self.edges() {
+            match (side(start) <= 0.0, side(end) <= 0.0) {
+                (true, true) => {
+                    clipped.points.push(start);
+                    clipped.fronts.push(street);
+                }
+                (true, false) => {
+                    clipped.points.push(start);
+                    clipped.fronts.push(street);
+                    clipped.points.push(crossing(start, end));
+                    clipped.fronts.push(None);
+                }
+                (false, true) => {
+                    clipped.points.push(crossing(start, end));
+                    clipped.fronts.push(street);
+                }
+                (false, false) => {}
+            }
+        }
+
+        clipped
+    }
+
+    fn is_degenerate(&self) -> bool {
+        self.points.len() < 3 || self.area() < MIN_LOT_AREA
+    }
+}
+
+/// The smallest rectangle around the points that is aligned with one of the polygon's edges, as
+/// its center and its two axes scaled to half of the rectangle's extent along them. The first
+/// axis is the longer one.
+fn oriented_bounding_box(points: &[Point]) -> (Point, Point, Point) {
+    (0..points.len())
+        .filter_map(|i| {
+            let direction = points[(i + 1) % points.len()] - points[i];
+            if direction.norm_squared() == 0.0 {
+                return None;
+            }
+            let u = direction.normalize();
+            let v = Point::new(-u.y, u.x);
+
+            let (mut u_min, mut u_max, mut v_min, mut v_max) =
+                (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+            for point in points {
+                let (u_coord, v_coord) = (point.dot(&u), point.dot(&v));
+                u_min = u_min.min(u_coord);
+                u_max = u_max.max(u_coord);
+                v_min = v_min.min(v_coord);
+                v_max = v_max.max(v_coord);
+            }
+
+            let center = u * (u_min + u_max) / 2.0 + v * (v_min + v_max) / 2.0;
+            let (u_half, v_half) = (u * (u_max - u_min) / 2.0, v * (v_max - v_min) / 2.0);
+            let area = (u_max - u_min) * (v_max - v_min);
+
+            Some((area, center, u_half, v_half))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, center, u_half, v_half)| {
+            if u_half.norm_squared() >= v_half.norm_squared() {
+                (center, u_half, v_half)
+            } else {
+                (center, v_half, u_half)
+            }
+        })
+        .unwrap_or((
+            points.first().copied().unwrap_or_default(),
+            Point::zeros(),
+            Point::zeros(),
+        ))
+}
+
+type StreetBuckets = HashMap<(i64, i64), Vec<(StreetId, [Point; 2])>>;
+
+pub(crate) struct StreetIndex<'a> {
+    buckets: StreetBuckets,
+    levels: &'a [Vec<HermiteCurve>],
+}
+
+impl<'a> StreetIndex<'a> {
+    pub(crate) fn new(levels: &'a [Vec<HermiteCurve>]) -> Self {
+        let mut buckets = StreetBuckets::new();
+
+        for (level, curves) in levels.iter().enumerate() {
+            for (index, curve) in curves.iter().enumerate() {
+                for window in curve.windows(2) {
+                    let segment = [window[0].position, window[1].position];
+                    let min = bucket_key(segment[0].inf(&segment[1]));
+                    let max = bucket_key(segment[0].sup(&segment[1]));
+                    for x in min.0..=max.0 {
+                        for y in min.1..=max.1 {
+                            buckets
+                                .entry((x, y))
+                                .or_default()
+                                .push((StreetId { level, index }, segment));
+                        }
+                    }
+                }
+            }
+        }
+
+        Self { buckets, levels }
+    }
+
+    /// The street that both ends and the middle of the edge are within `max_distance` of
+    pub(crate) fn street_facing_edge(
+        &self,
+        start: Point,
+        end: Point,
+        max_distance: f32,
+    ) -> Option<StreetId> {
+        let midpoint = (start + end) / 2.0;
+        let reach = Point::new(max_distance, max_distance);
+        let min = bucket_key(midpoint - reach);
+        let max = bucket_key(midpoint + reach);
+
+        let mut closest: Option<(f32, StreetId)> = None;
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for (street, segment) in self.buckets.get(&(x, y)).into_iter().flatten() {
+                    let distance = distance_to_segment(midpoint, *segment);
+                    if distance <= max_distance
+                        && closest.is_none_or(|(closest_distance, _)| distance < closest_distance)
+                    {
+                        closest = Some((distance, *street));
+                    }
+                }
+            }
+        }
+
+        let (_, street) = closest?;
+        let curve = &self.levels[street.level][street.index];
+        let distance_to_street = |point: Point| {
+            curve
+                .windows(2)
+                .map(|window| distance_to_segment(point, [window[0].position, window[1].position]))
+                .fold(f32::MAX, f32::min)
+        };
+
+        (distance_to_street(start) <= max_distance && distance_to_street(end) <= max_distance)
+            .then_some(street)
+    }
+}
+
+fn bucket_key(point: Point) -> (i64, i64) {
+    (
+        (point.x / STREET_BUCKET_SIZE).floor() as i64,
+        (point.y / STREET_BUCKET_SIZE).floor() as i64,
+    )
+}
+
+pub(crate) fn distance_to_segment(point: Point, segment: [Point; 2]) -> f32 {
+    let direction = segment[1] - segment[0];
+    let length_squared = direction.norm_squared();
+    if length_squared == 0.0 {
+        return (point - segment[0]).norm();
+    }
+    let t = ((point - segment[0]).dot(&direction) / length_squared).clamp(0.0, 1.0);
+    (point - (segment[0] + direction * t)).norm()
+}
+
+/// Cuts every block into lots. `street_distance` is how far the block edges are from the street
+/// centerlines, which is the inset distance the blocks were built with.
+///
+/// Lots are split in half across the longer axis of their oriented bounding box, or across the
+/// shorter one when that would leave a half without enough frontage, for as long as both halves
+/// keep at least `min_frontage` of street frontage, so blocks with less frontage than that stay
+/// a single lot. The finished lots are then cut back to `max_depth` from their street and to
+/// `max_aspect_ratio` times their width. Whatever is cut off there is left over as the inside of
+/// the block, so every parcel touches a street.
+pub fn subdivide_blocks(
+    blocks: &[Vec<Point>],
+    levels: &[Vec<HermiteCurve>],
+    config: &ParcelConfig,
+    street_distance: f32,
+) -> Vec<Parcel> {
+    let street_index = StreetIndex::new(levels);
+    let max_distance = street_distance + FRONTAGE_TOLERANCE;
+
+    let mut parcels = Vec::new();
+
+    for (block_index, block) in blocks.iter().enumerate() {
+        let fronts = (0..block.len())
+            .map(|i| {
+                street_index.street_facing_edge(
+                    block[i],
+                    block[(i + 1) % block.len()],
+                    max_distance,
+                )
+            })
+            .collect();
+
+        let mut lots = Vec::new();
+        split_lot(
+            Lot {
+                points: block.clone(),
+                fronts,
+            },
+            config,
+            &mut lots,
+        );
+
+        parcels.extend(lots.into_iter().filter_map(|lot| {
+            let (start, end, street) = lot.main_frontage()?;
+            Some(Parcel {
+                frontage: lot.frontage(),
+                polygon: lot.points,
+                block: block_index,
+                front: [start, end],
+                street,
+            })
+        }));
+    }
+
+    parcels
+}
+
+fn split_lot(lot: Lot, config: &ParcelConfig, lots: &mut Vec<Lot>) {
+    if lot.is_degenerate() || lot.main_frontage().is_none() {
+        return;
+    }
+
+    let (center, long_axis, short_axis) = oriented_bounding_box(&lot.points);
+
+    for axis in [long_axis, short_axis] {
+        if axis.norm_squared() == 0.0 {
+            continue;
+        }
+
+        let first = lot.clip(center, axis);
+        let second = lot.clip(center, -axis);
+
+        if !first.is_degenerate()
+            && !second.is_degenerate()
+            && first.frontage() >= config.min_frontage
+            && second.frontage() >= config.min_frontage
+        {
+            split_lot(first, config, lots);
+            split_lot(second, config, lots);
+            return;
+        }
+    }
+
+    let trimmed = trim_depth(lot, config);
+    if !trimmed.is_degenerate() {
+        lots.push(trimmed);
+    }
+}
+
+/// Cuts the lot back to the depth allowed by `max_depth` and `max_aspect_ratio`, measured from
+/// its main frontage
+fn trim_depth(lot: Lot, config: &ParcelConfig) -> Lot {
+    let Some((start, end, _)) = lot.main_frontage() else {
+        return lot;
+    };
+
+    let along = (end - start).normalize();
+    let across = Point::new(-along.y, along.x);
+
+    // Depth is measured on whichever side of the frontage the lot is on
+    let centroid = lot.points.iter().sum::<Point>() / lot.points.len() as f32;
+    let inward = if (centroid - start).dot(&across) >= 0.0 {
+        across
+    } else {
+        -across
+    };
+
+    let (width_min, width_max) = lot
+        .points
+        .iter()
+        .map(|point| (point - start).dot(&along))
+        .fold((f32::MAX, f32::MIN), |(min, max), coord| {
+            (min.min(coord), max.max(coord))
+        });
+    let depth = lot
+        .points
+        .iter()
+        .map(|point| (point - start).dot(&inward))
+        .fold(0.0, f32::max);
+
+    let allowed_depth = config
+        .max_depth
+        .min((width_max - width_min) * config.max_aspect_ratio);
+
+    if depth > allowed_depth {
+        lot.clip(start + inward * allowed_depth, inward)
+    } else {
+        lot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::ParcelConfig;
+    use crate::street_plan::HermiteCurve;
+    use crate::tensor_field::Point;
+    use crate::test_support::curve;
+
+    use super::{StreetId, subdivide_blocks};
+
+    fn square_block_between_four_streets() -> (Vec<Point>, Vec<Vec<HermiteCurve>>) {
+        let block = vec![
+            Point::new(1.0, 1.0),
+            Point::new(21.0, 1.0),
+            Point::new(21.0, 21.0),
+            Point::new(1.0, 21.0),
+        ];
+        let levels = vec![vec![
+            curve(&[(-5.0, 0.0), (27.0, 0.0)]),
+            curve(&[(22.0, -5.0), (22.0, 27.0)]),
+            curve(&[(27.0, 22.0), (-5.0, 22.0)]),
+            curve(&[(0.0, 27.0), (0.0, -5.0)]),
+        ]];
+
+        (block, levels)
+    }
+
+    #[test]
+    fn every_parcel_touches_a_street() {
+        let (block, levels) = square_block_between_four_streets();
+        let config = ParcelConfig {
+            min_frontage: 4.0,
+            max_depth: 8.0,
+            max_aspect_ratio: 3.0,
+        };
+
+        let parcels = subdivide_blocks(&[block], &levels, &config, 1.0);
+
+        assert!(parcels.len() > 4);
+        for parcel in &parcels {
+            assert!(parcel.frontage >= config.min_frontage);
+            assert_eq!(parcel.block, 0);
+        }
+    }
+
+    #[test]
+    fn parcels_respect_the_depth_limit() {
+        let (block, levels) = square_block_between_four_streets();
+        let config = ParcelConfig {
+            min_frontage: 4.0,
+            max_depth: 5.0,
+            max_aspect_ratio: 3.0,
+        };
+
+        let parcels = subdivide_blocks(&[block], &levels, &config, 1.0);
+
+        for parcel in &parcels {
+            // Every point of a lot is within the depth limit of one of the four streets
+            for point in &parcel.polygon {
+                let distance_to_block_edge = (point.x - 1.0)
+                    .min(21.0 - point.x)
+                    .min(point.y - 1.0)
+                    .min(21.0 - point.y);
+                assert!(distance_to_block_edge <= config.max_depth + 0.001);
+            }
+        }
+    }
+
+    #[test]
+    fn parcels_record_the_street_they_face() {
+        let block = vec![
+            Point::new(0.0, 1.0),
+            Point::new(12.0, 1.0),
+            Point::new(12.0, 4.0),
+            Point::new(0.0, 4.0),
+        ];
+        let levels = vec![
+            vec![curve(&[(-5.0, 0.0), (20.0, 0.0)])],
+            vec![curve(&[(-5.0, 30.0), (20.0, 30.0)])],
+        ];
+        let config = ParcelConfig {
+            min_frontage: 3.0,
+            max_depth: 10.0,
+            max_aspect_ratio: 4.0,
+        };
+
+        let parcels = subdivide_blocks(&[block], &levels, &config, 1.0);
+
+        assert_eq!(parcels.len(), 4);
+        for parcel in &parcels {
+            assert_eq!(parcel.street, StreetId { level: 0, index: 0 });
+            assert!((parcel.frontage - 3.0).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn blocks_without_street_access_get_no_parcels() {
+        let block = vec![
+            Point::new(50.0, 50.0),
+            Point::new(60.0, 50.0),
+            Point::new(60.0, 60.0),
+            Point::new(50.0, 60.0),
+        ];
+        let levels = vec![vec![curve(&[(0.0, 0.0), (10.0, 0.0)])]];
+
+        let parcels = subdivide_blocks(&[block], &levels, &ParcelConfig::default(), 1.0);
+
+        assert!(parcels.is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+use crate::street_graph::Segment;
+use crate::tensor_field::Point;
+
+// Shewchuk's adaptive orientation test. The determinant is first computed in f64 and only
+// recomputed exactly when it is too close to zero for the rounding error bound to vouch for its
+// sign. See "Adaptive Precision Floating-Point Arithmetic and Fast Robust Geometric Predicates".
+
+const EPSILON: f64 = f64::EPSILON / 2.0;
+const CCW_ERROR_BOUND: f64 = (3.0 + 16.0 * EPSILON) * EPSILON;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    CounterClockwise,
+    Clockwise,
+    Collinear,
+}
+
+/// Returns a value whose sign is exactly the sign of the determinant
+/// `(a - c) x (b - c)`: positive when `a`, `b`, `c` turn counter-clockwise, negative when they
+/// turn clockwise and zero when they are collinear. Only the sign is reliable, the magnitude is
+/// an approximation.
+pub fn orient2d(a: Point, b: Point, c: Point) -> f64 {
+    let (ax, ay) = (a.x as f64, a.y as f64);
+    let (bx, by) = (b.x as f64, b.y as f64);
+    let (cx, cy) = (c.x as f64, c.y as f64);
+
+    let det_left = (ax - cx) * (by - cy);
+    let det_right = (ay - cy) * (bx - cx);
+    let det = det_left - det_right;
+
+    let det_sum = if det_left > 0.0 {
+        if det_right <= 0.0 {
+            return det;
+        }
+        det_left + det_right
+    } else if det_left < 0.0 {
+        if det_right >= 0.0 {
+            return det;
+        }
+        -det_left - det_right
+    } else {
+        return det;
+    };
+
+    if det.abs() >= CCW_ERROR_BOUND * det_sum {
+        det
+    } else {
+        orient2d_exact([ax, ay], [bx, by], [cx, cy])
+    }
+}
+
+pub fn orientation(a: Point, b: Point, c: Point) -> Orientation {
+    let det = orient2d(a, b, c);
+    if det > 0.0 {
+        Orientation::CounterClockwise
+    } else if det < 0.0 {
+        Orientation::Clockwise
+    } else {
+        Orientation::Collinear
+    }
+}
+
+/// Exact test for whether two closed segments share at least one point. Collinear overlapping
+/// segments are not counted, they have no single intersection point.
+pub fn segments_intersect(segment_0: Segment, segment_1: Segment) -> bool {
+    let [p_0, p_1] = segment_0;
+    let [q_0, q_1] = segment_1;
+
+    let o_0 = orient2d(p_0, p_1, q_0).signum_or_zero();
+    let o_1 = orient2d(p_0, p_1, q_1).signum_or_zero();
+    let o_2 = orient2d(q_0, q_1, p_0).signum_or_zero();
+    let o_3 = orient2d(q_0, q_1, p_1).signum_or_zero();
+
+    if o_0 == 0 && o_1 == 0 {
+        return false;
+    }
+
+    o_0 * o_1 <= 0 && o_2 * o_3 <= 0
+}
+
+/// Intersection point of the lines through both segments, computed in double precision.
+/// Returns `None` for parallel lines.
+pub fn line_intersection(segment_0: Segment, segment_1: Segment) -> Option<Point> {
+    let [p_0, p_1] = segment_0.map(|p| [p.x as f64, p.y as f64]);
+    let [q_0, q_1] = segment_1.map(|p| [p.x as f64, p.y as f64]);
+
+    let r = [p_1[0] - p_0[0], p_1[1] - p_0[1]];
+    let s = [q_1[0] - q_0[0], q_1[1] - q_0[1]];
+
+    let denominator = r[0] * s[1] - r[1] * s[0];
+    if denominator == 0.0 {
+        return None;
+    }
+
+    let q_minus_p = [q_0[0] - p_0[0], q_0[1] - p_0[1]];
+    let u = (q_minus_p[0] * s[1] - q_minus_p[1] * s[0]) / denominator;
+
+    Some(Point::new(
+        (p_0[0] + u * r[0]) as f32,
+        (p_0[1] + u * r[1]) as f32,
+    ))
+}
+
+trait SignumOrZero {
+    fn signum_or_zero(self) -> i8;
+}
+
+impl SignumOrZero for f64 {
+    fn signum_or_zero(self) -> i8 {
+        if self > 0.0 {
+            1
+        } else if self < 0.0 {
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_virtual = sum - a;
+    let a_virtual = sum - b_virtual;
+    (sum, (a - a_virtual) + (b - b_virtual))
+}
+
+fn two_diff(a: f64, b: f64) -> (f64, f64) {
+    two_sum(a, -b)
+}
+
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let product = a * b;
+    (product, a.mul_add(b, -product))
+}
+
+/// Adds `value` to a nonoverlapping expansion while keeping it nonoverlapping, with the
+/// components sorted from smallest to largest magnitude
+fn grow_expansion(expansion: &mut Vec<f64>, value: f64) {
+    let mut carry = value;
+    for component in expansion.iter_mut() {
+        let (sum, error) = two_sum(carry, *component);
+        *component = error;
+        carry = sum;
+    }
+    expansion.push(carry);
+}
+
+fn orient2d_exact(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    let ac_x = two_diff(a[0], c[0]);
+    let ac_y = two_diff(a[1], c[1]);
+    let bc_x = two_diff(b[0], c[0]);
+    let bc_y = two_diff(b[1], c[1]);
+
+    let mut expansion = Vec::with_capacity(16);
+    for (left, right, sign) in [(ac_x, bc_y, 1.0), (ac_y, bc_x, -1.0)] {
+        for l in [left.0, left.1] {
+            for r in [right.0, right.1] {
+                let (product, error) = two_product(l, r);
+                grow_expansion(&mut expansion, sign * error);
+                grow_expansion(&mut expansion, sign * product);
+            }
+        }
+    }
+
+    expansion
+        .into_iter()
+        .rev()
+        .find(|component| *component != 0.0)
+        .unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::tensor_field::Point;
+
+    use super::{Orientation, orient2d, orientation, segments_intersect};
+
+    fn exact_orientation_sign(a: Point, b: Point, c: Point) -> i128 {
+        // Every coordinate in these tests is a multiple of 2^-20, so scaling by 2^20 makes them
+        // integers and the determinant can be computed exactly
+        let scale = |v: f32| (v as f64 * (1u64 << 20) as f64) as i128;
+        let (ax, ay, bx, by, cx, cy) = (
+            scale(a.x),
+            scale(a.y),
+            scale(b.x),
+            scale(b.y),
+            scale(c.x),
+            scale(c.y),
+        );
+        ((ax - cx) * (by - cy) - (ay - cy) * (bx - cx)).signum()
+    }
+
+    #[test]
+    fn orientation_of_simple_triangles() {
+        let a = Point::new(0.0, 0.0);
+        let b = Point::new(1.0, 0.0);
+        let c = Point::new(0.0, 1.0);
+
+        assert_eq!(orientation(a, b, c), Orientation::CounterClockwise);
+        assert_eq!(orientation(a, c, b), Orientation::Clockwise);
+        assert_eq!(
+            orientation(a, b, Point::new(2.0, 0.0)),
+            Orientation::Collinear
+        );
+    }
+
+    #[test]
+    fn orientation_matches_exact_arithmetic_on_nearly_collinear_points() {
+        let mut rng = ChaCha8Rng::seed_from_u64(29);
+        let granularity = 1.0 / (1u64 << 20) as f32;
+
+        for _ in 0..10000 {
+            let a = Point::new(
+                rng.random_range(0..512) as f32,
+                rng.random_range(0..512) as f32,
+            );
+            let b = Point::new(
+                rng.random_range(0..512) as f32,
+                rng.random_range(0..512) as f32,
+            );
+            let t = rng.random_range(0..256) as f32 / 256.0;
+            let nudge = Point::new(
+                rng.random_range(-2..=2) as f32 * granularity,
+                rng.random_range(-2..=2) as f32 * granularity,
+            );
+            let c = a + (b - a) * t + nudge;
+
+            let det = orient2d(a, b, c);
+            let sign = if det > 0.0 {
+                1
+            } else if det < 0.0 {
+                -1
+            } else {
+                0
+            };
+            assert_eq!(sign, exact_orientation_sign(a, b, c));
+        }
+    }
+
+    #[test]
+    fn touching_segments_intersect() {
+        assert!(segments_intersect(
+            [Point::new(0.0, 0.0), Point::new(2.0, 2.0)],
+            [Point::new(2.0, 2.0), Point::new(4.0, 0.0)],
+        ));
+        assert!(segments_intersect(
+            [Point::new(0.0, 0.0), Point::new(2.0, 0.0)],
+            [Point::new(1.0, 0.0), Point::new(1.0, 5.0)],
+        ));
+        assert!(!segments_intersect(
+            [Point::new(0.0, 0.0), Point::new(2.0, 0.0)],
+            [Point::new(1.0, 0.1), Point::new(1.0, 5.0)],
+        ));
+    }
+}
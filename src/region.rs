@@ -0,0 +1,650 @@
+use std::collections::HashMap;
+
+use crate::buildings::Building;
+use crate::city::{BlockContents, City, assemble_city, build_on_blocks, previous_level_seeds};
+use crate::config::{CityConfig, SeedConfig};
+use crate::parcels::{Parcel, StreetId, distance_to_segment};
+use crate::predicates::{line_intersection, segments_intersect};
+use crate::street_graph::{Blocks, GeometryError, Segment, path_to_selected_blocks};
+use crate::street_plan::{
+    ControlPoint, HermiteCurve, SeedPoint, TraceSeeds, debug_seed_points,
+    follows_major_eigenvectors, merge_road_endings_onto, prioritize_points, trace_street_plan,
+};
+use crate::tensor_field::{Point, TensorField};
+use crate::triangulation::{Triangulation, contains_point, triangulate};
+use crate::zoning::LandUse;
+
+/// Redoes the streets and blocks inside `region`, a simple polygon, and keeps the rest of the city
+/// as it is. Streets are cut at the region's border and the parts inside are dropped. Every level
+/// is then traced again from its seeds inside the region and the points where its streets were
+/// cut, with the streets outside as fixed previous curves, and the new streets are cut down to
+/// the region. Only the street faces overlapping the region go through block extraction again.
+///
+/// Streets, blocks and block triangulations outside the region come out unchanged, and so do the
+/// land uses, parcels and buildings of the blocks there, moved to the blocks' new indices. The
+/// tensor field is built from the design elements in the config and the new streets are kept to
+/// its mask, both of which may have been edited since the city was generated; the rest of the
+/// config is expected to be the one the city was generated with. The metro, the street network,
+/// the bus routes and the road surfaces are planned again for the whole city.
+pub fn regenerate_region(
+    city: &City,
+    config: &CityConfig,
+    region: &[Point],
+) -> Result<City, GeometryError> {
+    let tensor_field = TensorField::new(
+        config.field.design_elements.clone(),
+        config.field.decay_constant,
+    );
+
+    let mut previous_major_curves: Vec<HermiteCurve> = Vec::new();
+    let mut previous_minor_curves: Vec<HermiteCurve> = Vec::new();
+    let mut levels: Vec<Vec<HermiteCurve>> = Vec::new();
+    let mut level_seeds: Vec<Vec<Point>> = Vec::new();
+    // The street of the city every kept piece of a street was cut from, by level
+    let mut street_origins: Vec<Vec<usize>> = Vec::new();
+
+    for ((level, curves), old_seeds) in config.levels.iter().zip(&city.levels).zip(&city.seeds) {
+        let mut kept = Vec::new();
+        let mut origins = Vec::new();
+        let mut cut_points = Vec::new();
+        for (index, curve) in curves.iter().enumerate() {
+            let (pieces, cuts) = clip_curve(curve, region, false);
+            origins.extend(std::iter::repeat_n(index, pieces.len()));
+            kept.extend(pieces);
+            // A street is traced on from where it was cut along the same eigenvectors
+            let follow_major_eigenvectors = follows_major_eigenvectors(curve, &tensor_field);
+            cut_points.extend(cuts.into_iter().map(|seed| SeedPoint {
+                seed,
+                priority: 0.0,
+                follow_major_eigenvectors,
+            }));
+        }
+
+        let mut seeds: Vec<SeedPoint> = match level.seeds {
+            SeedConfig::Random => {
+                prioritize_points(&debug_seed_points(), city.city_center, &tensor_field).into_vec()
+            }
+            SeedConfig::PreviousLevel => {
+                previous_level_seeds(&previous_major_curves, &previous_minor_curves)
+            }
+        };
+        seeds.retain(|seed| contains_point(region, seed.seed) && config.mask.allows(seed.seed));
+        seeds.extend(cut_points);
+
+        for curve in &kept {
+            if follows_major_eigenvectors(curve, &tensor_field) {
+                previous_major_curves.push(curve.clone());
+            } else {
+                previous_minor_curves.push(curve.clone());
+            }
+        }
+
+        let seed_positions: Vec<Point> = seeds.iter().map(|seed| seed.seed).collect();
+        let (major_curves, minor_curves) = trace_street_plan(
+            &tensor_field,
+            TraceSeeds::Specific(seeds),
+            city.city_center,
+            level.d_sep,
+            level.iterations,
+            previous_major_curves.clone(),
+            previous_minor_curves.clone(),
+            &config.tracing,
+            &config.smoothing,
+            None,
+        );
+
+        let inside = |curves: Vec<HermiteCurve>| -> Vec<HermiteCurve> {
+            config.mask.clip_curves(
+                curves
+                    .iter()
+                    .flat_map(|curve| clip_curve(curve, region, true).0)
+                    .collect(),
+            )
+        };
+        let major_curves = inside(major_curves);
+        let major_curves_len = major_curves.len();
+        let new_curves: Vec<HermiteCurve> = major_curves
+            .into_iter()
+            .chain(inside(minor_curves))
+            .collect();
+        let fixed_curves: Vec<&HermiteCurve> = previous_major_curves
+            .iter()
+            .chain(&previous_minor_curves)
+            .collect();
+        let new_curves = merge_road_endings_onto(&new_curves, &fixed_curves, level.merge_distance);
+
+        previous_major_curves.extend_from_slice(&new_curves[..major_curves_len]);
+        previous_minor_curves.extend_from_slice(&new_curves[major_curves_len..]);
+        level_seeds.push(
+            old_seeds
+                .iter()
+                .copied()
+                .filter(|&seed| !contains_point(region, seed))
+                .chain(seed_positions)
+                .collect(),
+        );
+        street_origins.push(origins);
+        levels.push(kept.into_iter().chain(new_curves).collect());
+    }
+
+    let all_curves: Vec<HermiteCurve> = levels.iter().rev().flatten().cloned().collect();
+    let (new_blocks, affected_faces) =
+        path_to_selected_blocks(&all_curves, &config.blocks, |face| {
+            polygons_overlap(face, region)
+        })?;
+
+    // Street faces clear of the region are bounded by the same streets as before, so the blocks
+    // that came out of them can stay
+    let kept = kept_blocks(&city.blocks, &city.block_triangulations, &affected_faces);
+    let mut blocks = Blocks {
+        faces: kept
+            .iter()
+            .map(|&index| city.blocks[index].clone())
+            .collect(),
+        failures: city
+            .failed_faces
+            .iter()
+            .filter(|failure| !polygons_overlap(&failure.face, region))
+            .cloned()
+            .collect(),
+        traces: city
+            .block_traces
+            .iter()
+            .filter(|trace| !polygons_overlap(&trace.face, region))
+            .cloned()
+            .collect(),
+    };
+    let mut block_triangulations: Vec<Triangulation> = kept
+        .iter()
+        .map(|&index| city.block_triangulations[index].clone())
+        .collect();
+    let removed: Vec<usize> = (0..city.blocks.len())
+        .filter(|index| kept.binary_search(index).is_err())
+        .collect();
+    for face in new_blocks
+        .faces
+        .into_iter()
+        .filter(|face| config.mask.allows_block(face))
+    {
+        // Blocks of an affected face can come out the same as before, only starting at another
+        // vertex, in which case the old block is kept
+        match removed
+            .iter()
+            .find(|&&index| same_ring(&city.blocks[index], &face))
+        {
+            Some(&index) => {
+                blocks.faces.push(city.blocks[index].clone());
+                block_triangulations.push(city.block_triangulations[index].clone());
+            }
+            None => {
+                block_triangulations.push(triangulate(&face, &[]));
+                blocks.faces.push(face);
+            }
+        }
+    }
+    blocks.failures.extend(new_blocks.failures);
+    blocks.traces.extend(new_blocks.traces);
+
+    let contents = rebuild_block_contents(
+        city,
+        &kept,
+        &blocks.faces,
+        &block_triangulations,
+        &levels,
+        &street_origins,
+        config,
+    );
+
+    Ok(assemble_city(
+        config,
+        tensor_field,
+        levels,
+        level_seeds,
+        blocks,
+        block_triangulations,
+        contents,
+    ))
+}
+
+/// The land uses, parcels and buildings of the city's `kept` blocks, which are the first ones of
+/// `blocks` now, followed by those of the blocks after them, which are built on from scratch.
+/// The parcels of the kept blocks are pointed at the piece of their street they face among the
+/// new `levels`, since the streets cut at the region's border moved to other indices.
+fn rebuild_block_contents(
+    city: &City,
+    kept: &[usize],
+    blocks: &[Vec<Point>],
+    block_triangulations: &[Triangulation],
+    levels: &[Vec<HermiteCurve>],
+    street_origins: &[Vec<usize>],
+    config: &CityConfig,
+) -> BlockContents {
+    let mut land_uses: Vec<LandUse> = kept.iter().map(|&index| city.land_uses[index]).collect();
+    let mut parcels: Vec<Parcel> = Vec::new();
+    let mut parcel_indices: HashMap<usize, usize> = HashMap::new();
+    for (index, parcel) in city.parcels.iter().enumerate() {
+        let Ok(block) = kept.binary_search(&parcel.block) else {
+            continue;
+        };
+        // The street runs along the kept block, so a piece of it is always left outside the region
+        let Some(street) = moved_street(
+            parcel.street,
+            (parcel.front[0] + parcel.front[1]) / 2.0,
+            levels,
+            street_origins,
+        ) else {
+            continue;
+        };
+        parcel_indices.insert(index, parcels.len());
+        parcels.push(Parcel {
+            block,
+            street,
+            ..parcel.clone()
+        });
+    }
+    let mut buildings: Vec<Building> = city
+        .buildings
+        .iter()
+        .filter_map(|building| {
+            let parcel = *parcel_indices.get(&building.parcel)?;
+            Some(Building {
+                parcel,
+                ..building.clone()
+            })
+        })
+        .collect();
+
+    let BlockContents {
+        land_uses: new_land_uses,
+        parcels: new_parcels,
+        buildings: new_buildings,
+    } = build_on_blocks(
+        config,
+        levels,
+        &blocks[kept.len()..],
+        &block_triangulations[kept.len()..],
+    );
+    let parcel_offset = parcels.len();
+    land_uses.extend(new_land_uses);
+    parcels.extend(new_parcels.into_iter().map(|parcel| Parcel {
+        block: parcel.block + kept.len(),
+        ..parcel
+    }));
+    buildings.extend(new_buildings.into_iter().map(|building| Building {
+        parcel: building.parcel + parcel_offset,
+        ..building
+    }));
+
+    BlockContents {
+        land_uses,
+        parcels,
+        buildings,
+    }
+}
+
+/// The piece of the city's `street` closest to `point` among the new `levels`, given the street
+/// every kept piece came from
+fn moved_street(
+    street: StreetId,
+    point: Point,
+    levels: &[Vec<HermiteCurve>],
+    street_origins: &[Vec<usize>],
+) -> Option<StreetId> {
+    let distance = |index: usize| {
+        levels[street.level][index]
+            .windows(2)
+            .map(|pair| distance_to_segment(point, [pair[0].position, pair[1].position]))
+            .fold(f32::MAX, f32::min)
+    };
+    street_origins
+        .get(street.level)?
+        .iter()
+        .enumerate()
+        .filter(|&(_, &origin)| origin == street.index)
+        .map(|(index, _)| index)
+        .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+        .map(|index| StreetId {
+            level: street.level,
+            index,
+        })
+}
+
+/// The pieces of the curve inside the region, or outside it when `inside` is false, along with
+/// the points where the curve was cut. Control points on the dropped side are left out and a new
+/// one is put where the curve's control polygon crosses the border. A curve that stays on the
+/// kept side is returned as it is.
+pub(crate) fn clip_curve(
+    curve: &HermiteCurve,
+    region: &[Point],
+    inside: bool,
+) -> (Vec<HermiteCurve>, Vec<Point>) {
+    clip_curve_by(curve, &ring_edges(region), |point| {
+        contains_point(region, point) == inside
+    })
+}
+
+/// Same as `clip_curve` for an area bounded by `edges`, keeping the parts where `keep` holds
+pub(crate) fn clip_curve_by(
+    curve: &HermiteCurve,
+    edges: &[Segment],
+    keep: impl Fn(Point) -> bool,
+) -> (Vec<HermiteCurve>, Vec<Point>) {
+    let mut pieces: Vec<HermiteCurve> = Vec::new();
+    let mut cuts: Vec<Point> = Vec::new();
+    let mut piece: HermiteCurve = Vec::new();
+    for window in curve.windows(2) {
+        let [start, end] = [window[0], window[1]];
+        let ranges = kept_ranges(start.position, end.position, edges, &keep);
+        if ranges.is_empty() && !piece.is_empty() {
+            pieces.push(std::mem::take(&mut piece));
+        }
+        for (enter, exit) in ranges {
+            let mut at = |t: f32| {
+                let position = start.position.lerp(&end.position, t);
+                cuts.push(position);
+                ControlPoint {
+                    position,
+                    velocity: start.velocity.lerp(&end.velocity, t) * (exit - enter),
+                }
+            };
+            if enter > 0.0 && !piece.is_empty() {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            if piece.is_empty() {
+                piece.push(if enter > 0.0 { at(enter) } else { start });
+            }
+            if exit < 1.0 {
+                piece.push(at(exit));
+                pieces.push(std::mem::take(&mut piece));
+            } else {
+                piece.push(end);
+            }
+        }
+    }
+    pieces.push(piece);
+    pieces.retain(|piece| piece.len() >= 2);
+    (pieces, cuts)
+}
+
+/// The ranges of `t` for which `start + t * (end - start)` lies on the kept side of the edges,
+/// with neighbouring ranges on the same side joined
+fn kept_ranges(
+    start: Point,
+    end: Point,
+    edges: &[Segment],
+    keep: &impl Fn(Point) -> bool,
+) -> Vec<(f32, f32)> {
+    let direction = end - start;
+    let length_squared = direction.norm_squared();
+    if length_squared == 0.0 {
+        return Vec::new();
+    }
+    let mut crossings: Vec<f32> = edges
+        .iter()
+        .copied()
+        .filter(|&edge| segments_intersect([start, end], edge))
+        .filter_map(|edge| line_intersection([start, end], edge))
+        .map(|point| (point - start).dot(&direction) / length_squared)
+        .filter(|&t| t > 0.0 && t < 1.0)
+        .collect();
+    crossings.sort_by(f32::total_cmp);
+    crossings.dedup();
+
+    let mut ranges: Vec<(f32, f32)> = Vec::new();
+    let bounds: Vec<f32> = std::iter::once(0.0)
+        .chain(crossings)
+        .chain(std::iter::once(1.0))
+        .collect();
+    for pair in bounds.windows(2) {
+        let middle = start + direction * ((pair[0] + pair[1]) / 2.0);
+        if !keep(middle) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(last) if last.1 == pair[0] => last.1 = pair[1],
+            _ => ranges.push((pair[0], pair[1])),
+        }
+    }
+    ranges
+}
+
+pub(crate) fn ring_edges(ring: &[Point]) -> Vec<Segment> {
+    (0..ring.len())
+        .map(|i| [ring[i], ring[(i + 1) % ring.len()]])
+        .collect()
+}
+
+/// Whether an edge of one ring crosses or touches an edge of the other
+pub(crate) fn rings_cross(ring: &[Point], other: &[Point]) -> bool {
+    let other_edges = ring_edges(other);
+    ring_edges(ring).into_iter().any(|edge| {
+        other_edges
+            .iter()
+            .any(|&other_edge| segments_intersect(edge, other_edge))
+    })
+}
+
+/// Whether two simple polygons share any area or touch
+fn polygons_overlap(polygon: &[Point], other: &[Point]) -> bool {
+    rings_cross(polygon, other)
+        || polygon
+            .first()
+            .is_some_and(|&point| contains_point(other, point))
+        || other
+            .first()
+            .is_some_and(|&point| contains_point(polygon, point))
+}
+
+/// Whether both rings have the same vertices in the same order, starting anywhere
+fn same_ring(ring: &[Point], other: &[Point]) -> bool {
+    ring.len() == other.len()
+        && (0..ring.len()).any(|offset| {
+            ring.iter()
+                .cycle()
+                .skip(offset)
+                .zip(other)
+                .all(|(vertex, other_vertex)| vertex == other_vertex)
+        })
+}
+
+/// Indices of the blocks that did not come out of one of the affected street faces. Blocks lie
+/// inside the face they were extracted from, so one point inside the block tells which face it
+/// belongs to.
+fn kept_blocks(
+    blocks: &[Vec<Point>],
+    block_triangulations: &[Triangulation],
+    affected_faces: &[Vec<Point>],
+) -> Vec<usize> {
+    blocks
+        .iter()
+        .zip(block_triangulations)
+        .enumerate()
+        .filter(|(_, (block, triangulation))| {
+            let point = triangulation
+                .triangle_points()
+                .next()
+                .map(|[a, b, c]| (a + b + c) / 3.0)
+                .unwrap_or(block[0]);
+            !affected_faces
+                .iter()
+                .any(|face| contains_point(face, point))
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::city::{City, generate_city};
+    use crate::config::{BlockConfig, CityConfig};
+    use crate::street_graph::{path_to_graph, path_to_selected_blocks};
+    use crate::street_plan::HermiteCurve;
+    use crate::tensor_field::Point;
+    use crate::test_support::{curve, square};
+    use crate::triangulation::triangulate;
+
+    use super::{clip_curve, kept_blocks, polygons_overlap, regenerate_region, same_ring};
+
+    #[test]
+    fn streets_are_cut_at_the_region_border() {
+        let region = square(100.0, 200.0);
+        let street = curve(&[
+            (50.0, 150.0),
+            (150.0, 150.0),
+            (250.0, 150.0),
+            (300.0, 160.0),
+        ]);
+
+        let (outside, cuts) = clip_curve(&street, &region, false);
+        assert_eq!(outside.len(), 2);
+        assert_eq!(
+            outside[0]
+                .iter()
+                .map(|point| point.position)
+                .collect::<Vec<_>>(),
+            [Point::new(50.0, 150.0), Point::new(100.0, 150.0)]
+        );
+        assert_eq!(outside[1][0].position, Point::new(200.0, 150.0));
+        assert_eq!(outside[1].len(), 3);
+        assert_eq!(cuts, [Point::new(100.0, 150.0), Point::new(200.0, 150.0)]);
+
+        let (inside, _) = clip_curve(&street, &region, true);
+        assert_eq!(inside.len(), 1);
+        assert_eq!(inside[0].len(), 3);
+
+        // Streets clear of the region come back as they were
+        let clear = curve(&[(10.0, 10.0), (20.0, 30.0), (40.0, 20.0)]);
+        let (pieces, cuts) = clip_curve(&clear, &region, false);
+        assert_eq!(pieces, std::slice::from_ref(&clear));
+        assert!(cuts.is_empty());
+        assert!(clip_curve(&clear, &region, true).0.is_empty());
+    }
+
+    #[test]
+    fn polygons_overlap_when_they_cross_or_contain_each_other() {
+        let region = square(100.0, 200.0);
+        assert!(polygons_overlap(&square(150.0, 250.0), &region));
+        assert!(polygons_overlap(&square(120.0, 180.0), &region));
+        assert!(polygons_overlap(&square(0.0, 300.0), &region));
+        assert!(!polygons_overlap(&square(210.0, 300.0), &region));
+    }
+
+    #[test]
+    fn only_blocks_of_faces_touching_the_region_are_extracted_again() {
+        let streets: Vec<HermiteCurve> = [0.0, 100.0, 200.0, 300.0]
+            .into_iter()
+            .flat_map(|line| {
+                [
+                    curve(&[(line, -10.0), (line, 310.0)]),
+                    curve(&[(-10.0, line), (310.0, line)]),
+                ]
+            })
+            .collect();
+        let block_config = BlockConfig::default();
+        let all = path_to_graph(&streets, &block_config).unwrap();
+        let triangulations: Vec<_> = all
+            .faces
+            .iter()
+            .map(|face| triangulate(face, &[]))
+            .collect();
+
+        let region = square(140.0, 160.0);
+        let (selected, affected_faces) = path_to_selected_blocks(&streets, &block_config, |face| {
+            polygons_overlap(face, &region)
+        })
+        .unwrap();
+        assert_eq!(affected_faces.len(), 1);
+
+        let kept = kept_blocks(&all.faces, &triangulations, &affected_faces);
+        assert_eq!(kept.len() + selected.faces.len(), all.faces.len());
+        // Faces may start at a different vertex every time the graph is built
+        let removed: Vec<_> = (0..all.faces.len())
+            .filter(|index| !kept.contains(index))
+            .collect();
+        assert_eq!(removed.len(), selected.faces.len());
+        assert!(
+            removed
+                .iter()
+                .zip(&selected.faces)
+                .all(|(&index, face)| same_ring(&all.faces[index], face))
+        );
+    }
+
+    /// The land use, parcels and buildings of a block, without the indices they are stored at
+    fn block_contents(city: &City, block: usize) -> impl PartialEq + std::fmt::Debug {
+        let parcels: Vec<usize> = (0..city.parcels.len())
+            .filter(|&parcel| city.parcels[parcel].block == block)
+            .collect();
+        (
+            city.land_uses[block],
+            parcels
+                .iter()
+                .map(|&parcel| {
+                    let parcel = &city.parcels[parcel];
+                    (parcel.polygon.clone(), parcel.front, parcel.frontage)
+                })
+                .collect::<Vec<_>>(),
+            city.buildings
+                .iter()
+                .filter_map(|building| {
+                    let position = parcels
+                        .iter()
+                        .position(|&parcel| parcel == building.parcel)?;
+                    Some((
+                        position,
+                        building.land_use,
+                        building.outline.clone(),
+                        building.holes.clone(),
+                        building.height,
+                    ))
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn the_city_outside_the_region_is_left_as_it_was() {
+        let mut config = CityConfig::default();
+        // Only the major streets, to keep the city small
+        config.levels.truncate(1);
+        let city = generate_city(&config).unwrap();
+        let region = square(200.0, 320.0);
+
+        let regenerated = regenerate_region(&city, &config, &region).unwrap();
+
+        for (old, new) in city.levels.iter().zip(&regenerated.levels) {
+            for street in old.iter().filter(|street| {
+                let points: Vec<Point> = street.iter().map(|point| point.position).collect();
+                !polygons_overlap(&points, &region)
+            }) {
+                assert!(new.contains(street));
+            }
+        }
+
+        // Blocks next to the region may lie in a street face that reaches into it
+        let surroundings = square(195.0, 325.0);
+        let mut compared = 0;
+        for (block, points) in city.blocks.iter().enumerate() {
+            if polygons_overlap(points, &surroundings) {
+                continue;
+            }
+            let new_block = regenerated
+                .blocks
+                .iter()
+                .position(|new_points| new_points == points)
+                .unwrap();
+            assert_eq!(
+                regenerated.block_triangulations[new_block],
+                city.block_triangulations[block]
+            );
+            assert_eq!(
+                block_contents(&regenerated, new_block),
+                block_contents(&city, block)
+            );
+            compared += 1;
+        }
+        assert!(compared > 0);
+        assert!(compared < city.blocks.len());
+        assert!(!city.parcels.is_empty());
+    }
+}
@@ -0,0 +1,670 @@
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+
+use crate::config::RoadConfig;
+use crate::intersections::{Arrangement, ArrangementBuilder};
+use crate::parcels::StreetId;
+use crate::predicates::segments_intersect;
+use crate::street_graph::{GeometryError, face_area, scale_face};
+use crate::street_plan::{HermiteCurve, resample_curve};
+use crate::tensor_field::Point;
+use crate::triangulation::contains_point;
+
+/// Fillet arcs are approximated with this many segments
+const ARC_SEGMENTS: usize = 6;
+
+/// Corners whose mitered point would be further than this many road widths from the street
+/// crossing are beveled instead
+const MAX_MITER_RATIO: f32 = 3.0;
+
+/// Fillets never take up more than this share of the streets they connect, so that the roads
+/// between two crossings don't get cut away from both ends
+const MAX_FILLET_SHARE: f32 = 0.45;
+
+/// Consecutive boundary points that turn less than this are merged into a straight edge
+const COLLINEAR_TOLERANCE: f32 = 1e-4;
+
+/// Boundary points closer together than this are merged. Offsetting a ring with edges much
+/// shorter than the offset distance folds it over itself.
+const MIN_EDGE_LENGTH: f32 = 0.01;
+
+/// Edges shorter than this that double back on the previous or next edge are dropped
+const MAX_SPIKE_LENGTH: f32 = 0.2;
+
+/// The stretch of a street between two vertices of the street graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoadPiece {
+    pub polygon: Vec<Point>,
+    /// When several streets run along the same stretch, the widest one
+    pub street: StreetId,
+}
+
+/// The area where three or more roads meet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Junction {
+    pub position: Point,
+    pub polygon: Vec<Point>,
+    pub streets: Vec<StreetId>,
+}
+
+/// The ring between the curb and the block behind it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sidewalk {
+    pub outer: Vec<Point>,
+    pub inner: Vec<Point>,
+}
+
+/// Why a face of the street graph got no sidewalk or block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The curb line around the face crosses itself, because the roads around it overlap
+    CurbNotSimple,
+    /// The curb line is fine but the sidewalk inside it leaves no room for a block
+    TooNarrow,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::CurbNotSimple => write!(f, "the curb line crosses itself"),
+            SkipReason::TooNarrow => write!(f, "the sidewalk leaves no room for a block"),
+        }
+    }
+}
+
+/// A face of the street graph left out of the tiling. Only the roads around it cover it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedFace {
+    pub curb: Vec<Point>,
+    pub reason: SkipReason,
+}
+
+/// Road, junction, sidewalk and block polygons that tile the city without overlapping, except
+/// for the faces in `skipped_faces`. All polygons are counter-clockwise, sidewalks have their
+/// block as a hole.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoadSurfaces {
+    pub roads: Vec<RoadPiece>,
+    pub junctions: Vec<Junction>,
+    pub sidewalks: Vec<Sidewalk>,
+    /// The land behind every sidewalk. These follow the street graph directly, unlike
+    /// `City::blocks` which are flattened and split on top of that.
+    pub blocks: Vec<Vec<Point>>,
+    /// Faces of the street graph that got no sidewalk or block
+    pub skipped_faces: Vec<SkippedFace>,
+}
+
+/// One of the edges leaving a vertex of the street graph
+#[derive(Debug, Clone, Copy)]
+struct Spoke {
+    edge: usize,
+    neighbor: usize,
+    direction: Point,
+    half_width: f32,
+}
+
+impl Spoke {
+    fn left(&self) -> Point {
+        Point::new(-self.direction.y, self.direction.x)
+    }
+}
+
+/// Offsets every street by half its width and cuts the road area up at every point where three
+/// or more roads meet. The corners between the roads of a junction are filleted, the same arcs
+/// then bound the sidewalks of the blocks on the other side, so the pieces fit together exactly.
+///
+/// Faces of the street graph that are too narrow to fit their roads get no sidewalk or block,
+/// the roads around them overlap there instead. They are listed in `RoadSurfaces::skipped_faces`.
+pub fn build_road_surfaces(
+    levels: &[Vec<HermiteCurve>],
+    config: &RoadConfig,
+) -> Result<RoadSurfaces, GeometryError> {
+    let mut builder = ArrangementBuilder::new();
+    for (level, curves) in levels.iter().enumerate() {
+        for (index, curve) in curves.iter().enumerate() {
+            if curve.len() < 2 {
+                continue;
+            }
+            let mut centerline = resample_curve(curve, config.curve_samples as i32);
+            centerline.dedup();
+            builder.add_polyline(&centerline, StreetId { level, index });
+        }
+    }
+    let arrangement = builder.build()?;
+
+    let surfaces = SurfaceBuilder::new(&arrangement, config);
+
+    Ok(RoadSurfaces {
+        roads: surfaces.roads(),
+        junctions: surfaces.junctions(),
+        ..surfaces.sidewalks_and_blocks()
+    })
+}
+
+struct SurfaceBuilder<'a> {
+    arrangement: &'a Arrangement<StreetId>,
+    edge_streets: Vec<StreetId>,
+    /// The edges around every vertex in counter-clockwise order
+    spokes: Vec<Vec<Spoke>>,
+    spoke_indices: HashMap<(usize, usize), usize>,
+    is_junction: Vec<bool>,
+    /// `corners[vertex][k]` runs from the left side of spoke `k` to the right side of spoke
+    /// `k + 1`
+    corners: Vec<Vec<Vec<Point>>>,
+    /// How far from the vertex the roads of a junction are cut off, per spoke
+    cuts: Vec<Vec<f32>>,
+    corner_radius: f32,
+    sidewalk_width: f32,
+}
+
+impl<'a> SurfaceBuilder<'a> {
+    fn new(arrangement: &'a Arrangement<StreetId>, config: &RoadConfig) -> Self {
+        let width_of = |street: &StreetId| config.width_of_level(street.level);
+
+        let edge_streets: Vec<StreetId> = arrangement
+            .edges
+            .iter()
+            .map(|edge| {
+                *edge
+                    .tags
+                    .iter()
+                    .max_by(|a, b| width_of(a).total_cmp(&width_of(b)))
+                    .expect("Every edge comes from at least one street")
+            })
+            .collect();
+
+        let mut spokes: Vec<Vec<Spoke>> = vec![Vec::new(); arrangement.vertices.len()];
+        for (edge_index, edge) in arrangement.edges.iter().enumerate() {
+            let [start, end] = edge.vertices;
+            let half_width = width_of(&edge_streets[edge_index]) / 2.0;
+            for (from, to) in [(start, end), (end, start)] {
+                let Some(direction) = (arrangement.vertices[to] - arrangement.vertices[from])
+                    .try_normalize(f32::EPSILON)
+                else {
+                    continue;
+                };
+                spokes[from].push(Spoke {
+                    edge: edge_index,
+                    neighbor: to,
+                    direction,
+                    half_width,
+                });
+            }
+        }
+        for vertex_spokes in &mut spokes {
+            vertex_spokes.sort_by(|a, b| {
+                a.direction
+                    .y
+                    .atan2(a.direction.x)
+                    .total_cmp(&b.direction.y.atan2(b.direction.x))
+            });
+        }
+
+        let spoke_indices = spokes
+            .iter()
+            .enumerate()
+            .flat_map(|(vertex, vertex_spokes)| {
+                vertex_spokes
+                    .iter()
+                    .enumerate()
+                    .map(move |(k, spoke)| ((vertex, spoke.edge), k))
+            })
+            .collect();
+
+        let mut builder = Self {
+            arrangement,
+            edge_streets,
+            spokes,
+            spoke_indices,
+            is_junction: Vec::new(),
+            corners: Vec::new(),
+            cuts: Vec::new(),
+            corner_radius: config.corner_radius,
+            sidewalk_width: config.sidewalk_width,
+        };
+
+        builder.is_junction = (0..arrangement.vertices.len())
+            .map(|vertex| {
+                let degree = builder.spokes[vertex].len();
+                degree >= 3 || (degree == 2 && (0..2).any(|k| builder.needs_bevel(vertex, k)))
+            })
+            .collect();
+        builder.corners = (0..arrangement.vertices.len())
+            .map(|vertex| {
+                (0..builder.spokes[vertex].len())
+                    .map(|k| builder.corner(vertex, k))
+                    .collect()
+            })
+            .collect();
+        builder.cuts = (0..arrangement.vertices.len())
+            .map(|vertex| builder.junction_cuts(vertex))
+            .collect();
+
+        builder
+    }
+
+    fn position(&self, vertex: usize) -> Point {
+        self.arrangement.vertices[vertex]
+    }
+
+    fn edge_length(&self, spoke: &Spoke) -> f32 {
+        let [start, end] = self.arrangement.edges[spoke.edge].vertices;
+        (self.position(end) - self.position(start)).norm()
+    }
+
+    /// Where the left edge of spoke `k` meets the right edge of the next spoke, or `None` when
+    /// they are parallel or meet too far away
+    fn miter_point(&self, vertex: usize, k: usize) -> Option<Point> {
+        let spokes = &self.spokes[vertex];
+        let (spoke, next) = (spokes[k], spokes[(k + 1) % spokes.len()]);
+        if spokes.len() < 2 {
+            return None;
+        }
+
+        let origin = self.position(vertex);
+        let left_line = origin + spoke.left() * spoke.half_width;
+        let right_line = origin - next.left() * next.half_width;
+
+        let denominator = spoke.direction.perp(&next.direction);
+        if denominator.abs() < COLLINEAR_TOLERANCE {
+            return None;
+        }
+        let t = (right_line - left_line).perp(&next.direction) / denominator;
+        let miter = left_line + spoke.direction * t;
+
+        ((miter - origin).norm() <= MAX_MITER_RATIO * 2.0 * spoke.half_width.max(next.half_width))
+            .then_some(miter)
+    }
+
+    /// The two points of the bevel between spoke `k` and the next one, for dead ends, sharp
+    /// corners and straight continuations. The points are the same for continuations between
+    /// roads of the same width.
+    fn bevel(&self, vertex: usize, k: usize) -> Vec<Point> {
+        let spokes = &self.spokes[vertex];
+        let origin = self.position(vertex);
+        let (spoke, next) = (spokes[k], spokes[(k + 1) % spokes.len()]);
+
+        let mut bevel = vec![
+            origin + spoke.left() * spoke.half_width,
+            origin - next.left() * next.half_width,
+        ];
+        bevel.dedup_by(|a, b| (*a - *b).norm_squared() < f32::EPSILON);
+        bevel
+    }
+
+    fn needs_bevel(&self, vertex: usize, k: usize) -> bool {
+        self.miter_point(vertex, k).is_none() && self.bevel(vertex, k).len() > 1
+    }
+
+    fn corner(&self, vertex: usize, k: usize) -> Vec<Point> {
+        let spokes = &self.spokes[vertex];
+        let origin = self.position(vertex);
+        let (spoke, next) = (spokes[k], spokes[(k + 1) % spokes.len()]);
+
+        let Some(miter) = self.miter_point(vertex, k) else {
+            // Dead ends get a square cap
+            return self.bevel(vertex, k);
+        };
+
+        let angle = spoke.direction.angle(&next.direction);
+        let convex = spoke.direction.perp(&next.direction) > 0.0;
+        if !self.is_junction[vertex] || !convex || self.corner_radius <= 0.0 {
+            return vec![miter];
+        }
+
+        // The arc is tangent to both road edges, its tangent points are kept clear of the far
+        // ends of both streets
+        let half_angle = angle / 2.0;
+        let miter_distance = (miter - origin)
+            .dot(&spoke.direction)
+            .max((miter - origin).dot(&next.direction))
+            .max(0.0);
+        let max_tangent_distance = (MAX_FILLET_SHARE
+            * self.edge_length(&spoke).min(self.edge_length(&next))
+            - miter_distance)
+            .max(0.0);
+        let tangent_distance = (self.corner_radius / half_angle.tan()).min(max_tangent_distance);
+        let radius = tangent_distance * half_angle.tan();
+        if radius <= self.sidewalk_width {
+            // The sidewalk follows the fillet with an arc that much smaller, there'd be nothing
+            // left of it
+            return vec![miter];
+        }
+
+        let bisector = (spoke.direction + next.direction).normalize();
+        let center = miter + bisector * (radius / half_angle.sin());
+        let start = miter + spoke.direction * tangent_distance - center;
+        let end = miter + next.direction * tangent_distance - center;
+
+        let start_angle = start.y.atan2(start.x);
+        let mut sweep = end.y.atan2(end.x) - start_angle;
+        if sweep > PI {
+            sweep -= 2.0 * PI;
+        } else if sweep < -PI {
+            sweep += 2.0 * PI;
+        }
+
+        (0..=ARC_SEGMENTS)
+            .map(|i| {
+                let arc_angle = start_angle + sweep * i as f32 / ARC_SEGMENTS as f32;
+                center + Point::new(arc_angle.cos(), arc_angle.sin()) * radius
+            })
+            .collect()
+    }
+
+    /// Every road of a junction is cut off past the furthest corner point along it
+    fn junction_cuts(&self, vertex: usize) -> Vec<f32> {
+        let spokes = &self.spokes[vertex];
+        if !self.is_junction[vertex] {
+            return vec![0.0; spokes.len()];
+        }
+
+        let origin = self.position(vertex);
+        (0..spokes.len())
+            .map(|k| {
+                let previous_corner = &self.corners[vertex][(k + spokes.len() - 1) % spokes.len()];
+                let next_corner = &self.corners[vertex][k];
+                [previous_corner.last(), next_corner.first()]
+                    .into_iter()
+                    .flatten()
+                    .map(|point| (point - origin).dot(&spokes[k].direction))
+                    .fold(0.0, f32::max)
+            })
+            .collect()
+    }
+
+    /// The points where the road along spoke `k` starts at the vertex, on its right and left
+    fn road_end(&self, vertex: usize, k: usize) -> [Point; 2] {
+        let spokes = &self.spokes[vertex];
+        let spoke = spokes[k];
+
+        if self.is_junction[vertex] {
+            let cut = self.position(vertex) + spoke.direction * self.cuts[vertex][k];
+            let offset = spoke.left() * spoke.half_width;
+            [cut - offset, cut + offset]
+        } else {
+            let previous_corner = &self.corners[vertex][(k + spokes.len() - 1) % spokes.len()];
+            let next_corner = &self.corners[vertex][k];
+            [
+                *previous_corner.last().expect("Corners are never empty"),
+                *next_corner.first().expect("Corners are never empty"),
+            ]
+        }
+    }
+
+    fn roads(&self) -> Vec<RoadPiece> {
+        self.arrangement
+            .edges
+            .iter()
+            .enumerate()
+            .filter_map(|(edge_index, edge)| {
+                let [start, end] = edge.vertices;
+                let start_k = *self.spoke_indices.get(&(start, edge_index))?;
+                let end_k = *self.spoke_indices.get(&(end, edge_index))?;
+
+                let [start_right, start_left] = self.road_end(start, start_k);
+                let [end_left, end_right] = self.road_end(end, end_k);
+
+                let polygon = clean_ring(vec![start_right, end_right, end_left, start_left]);
+                (polygon.len() >= 3 && face_area(&polygon) > 0.0).then(|| RoadPiece {
+                    polygon,
+                    street: self.edge_streets[edge_index],
+                })
+            })
+            .collect()
+    }
+
+    fn junctions(&self) -> Vec<Junction> {
+        (0..self.arrangement.vertices.len())
+            .filter(|&vertex| self.is_junction[vertex])
+            .filter_map(|vertex| {
+                let polygon: Vec<Point> = (0..self.spokes[vertex].len())
+                    .flat_map(|k| {
+                        self.road_end(vertex, k)
+                            .into_iter()
+                            .chain(self.corners[vertex][k].iter().copied())
+                    })
+                    .collect();
+                let polygon = clean_ring(polygon);
+
+                let mut streets: Vec<StreetId> = Vec::new();
+                for spoke in &self.spokes[vertex] {
+                    for tag in &self.arrangement.edges[spoke.edge].tags {
+                        if !streets.contains(tag) {
+                            streets.push(*tag);
+                        }
+                    }
+                }
+
+                (polygon.len() >= 3 && face_area(&polygon) > 0.0).then(|| Junction {
+                    position: self.position(vertex),
+                    polygon,
+                    streets,
+                })
+            })
+            .collect()
+    }
+
+    /// Walks the faces of the street graph, keeping each face on the left, and builds the curb
+    /// line of every face from the corners around it
+    fn sidewalks_and_blocks(&self) -> RoadSurfaces {
+        let mut surfaces = RoadSurfaces::default();
+        let mut visited: HashSet<(usize, usize)> = HashSet::new();
+
+        for start_vertex in 0..self.spokes.len() {
+            for start_k in 0..self.spokes[start_vertex].len() {
+                if visited.contains(&(start_vertex, start_k)) {
+                    continue;
+                }
+
+                let mut curb = Vec::new();
+                let (mut vertex, mut k) = (start_vertex, start_k);
+                while visited.insert((vertex, k)) {
+                    let spoke = self.spokes[vertex][k];
+                    let next_vertex = spoke.neighbor;
+                    let back_k = self.spoke_indices[&(next_vertex, spoke.edge)];
+                    let next_degree = self.spokes[next_vertex].len();
+
+                    if self.is_junction[vertex] {
+                        curb.push(self.road_end(vertex, k)[1]);
+                    }
+                    if self.is_junction[next_vertex] {
+                        curb.push(self.road_end(next_vertex, back_k)[0]);
+                    }
+
+                    let corner_k = (back_k + next_degree - 1) % next_degree;
+                    curb.extend(self.corners[next_vertex][corner_k].iter().rev());
+
+                    (vertex, k) = (next_vertex, corner_k);
+                }
+
+                // Clockwise curbs run around the outside of the street graph
+                let curb = clean_ring(curb);
+                if curb.len() < 3 || face_area(&curb) <= 0.0 {
+                    continue;
+                }
+                if !is_simple(&curb) {
+                    surfaces.skipped_faces.push(SkippedFace {
+                        curb,
+                        reason: SkipReason::CurbNotSimple,
+                    });
+                    continue;
+                }
+
+                let inner = clean_ring(scale_face(curb.clone(), self.sidewalk_width));
+                if inner.len() >= 3
+                    && face_area(&inner) > 0.0
+                    && is_simple(&inner)
+                    && inner.iter().all(|&point| contains_point(&curb, point))
+                {
+                    surfaces.blocks.push(inner.clone());
+                    surfaces.sidewalks.push(Sidewalk { outer: curb, inner });
+                } else {
+                    surfaces.skipped_faces.push(SkippedFace {
+                        curb,
+                        reason: SkipReason::TooNarrow,
+                    });
+                }
+            }
+        }
+
+        surfaces
+    }
+}
+
+/// Drops repeated points and points that don't turn the ring
+fn clean_ring(mut ring: Vec<Point>) -> Vec<Point> {
+    let min_length_squared = MIN_EDGE_LENGTH * MIN_EDGE_LENGTH;
+    ring.dedup_by(|a, b| (*a - *b).norm_squared() < min_length_squared);
+    while ring.len() > 1 && (ring[0] - ring[ring.len() - 1]).norm_squared() < min_length_squared {
+        ring.pop();
+    }
+
+    let mut changed = true;
+    while changed && ring.len() >= 3 {
+        changed = false;
+        for i in 0..ring.len() {
+            let previous = ring[(i + ring.len() - 1) % ring.len()];
+            let next = ring[(i + 1) % ring.len()];
+            let (incoming, outgoing) = (ring[i] - previous, next - ring[i]);
+            let (incoming_length, outgoing_length) = (incoming.norm(), outgoing.norm());
+            let scale = incoming_length * outgoing_length;
+            let collinear = (incoming.perp(&outgoing) / scale).abs() < COLLINEAR_TOLERANCE
+                && incoming.dot(&outgoing) > 0.0;
+            // Cut points landing a hair past a corner leave short spikes doubling back
+            let spike = incoming.dot(&outgoing) < 0.0
+                && incoming_length.min(outgoing_length) < MAX_SPIKE_LENGTH;
+            if scale == 0.0 || collinear || spike {
+                ring.remove(i);
+                changed = true;
+                break;
+            }
+        }
+    }
+
+    ring
+}
+
+fn is_simple(ring: &[Point]) -> bool {
+    let edges: Vec<[Point; 2]> = (0..ring.len())
+        .map(|i| [ring[i], ring[(i + 1) % ring.len()]])
+        .collect();
+    (0..edges.len()).all(|i| {
+        (i + 2..edges.len())
+            .filter(|&j| !(i == 0 && j == edges.len() - 1))
+            .all(|j| !segments_intersect(edges[i], edges[j]))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::RoadConfig;
+    use crate::street_graph::face_area;
+    use crate::street_plan::HermiteCurve;
+    use crate::tensor_field::Point;
+    use crate::test_support::curve;
+
+    use super::build_road_surfaces;
+
+    /// Two horizontal and two vertical streets crossing in a # shape, 20 apart
+    fn grid_streets() -> Vec<Vec<HermiteCurve>> {
+        vec![vec![
+            curve(&[(-20.0, 0.0), (0.0, 0.0), (20.0, 0.0), (40.0, 0.0)]),
+            curve(&[(-20.0, 20.0), (0.0, 20.0), (20.0, 20.0), (40.0, 20.0)]),
+            curve(&[(0.0, -20.0), (0.0, 0.0), (0.0, 20.0), (0.0, 40.0)]),
+            curve(&[(20.0, -20.0), (20.0, 0.0), (20.0, 20.0), (20.0, 40.0)]),
+        ]]
+    }
+
+    fn config() -> RoadConfig {
+        RoadConfig {
+            level_widths: vec![2.0],
+            sidewalk_width: 0.5,
+            corner_radius: 1.0,
+            curve_samples: 1,
+        }
+    }
+
+    #[test]
+    fn four_way_crossings_get_junctions() {
+        let surfaces = build_road_surfaces(&grid_streets(), &config()).unwrap();
+
+        assert_eq!(surfaces.junctions.len(), 4);
+        for junction in &surfaces.junctions {
+            assert_eq!(junction.streets.len(), 2);
+            // A cross of two 2 wide roads reaching 2 out from the center, plus the four corners
+            // between the road edges and the unit fillets
+            let area = face_area(&junction.polygon);
+            let fillet_area = 4.0 * (1.0 - std::f32::consts::FRAC_PI_4);
+            assert!((area - (12.0 + fillet_area)).abs() < 0.05, "area {area}");
+        }
+    }
+
+    #[test]
+    fn the_enclosed_square_is_tiled_exactly() {
+        let surfaces = build_road_surfaces(&grid_streets(), &config()).unwrap();
+
+        assert_eq!(surfaces.roads.len(), 12);
+        assert_eq!(surfaces.blocks.len(), 1);
+        assert_eq!(surfaces.sidewalks.len(), 1);
+        assert!(surfaces.skipped_faces.is_empty());
+
+        let centroid = |polygon: &[Point]| polygon.iter().sum::<Point>() / polygon.len() as f32;
+        let road_between_junctions = surfaces
+            .roads
+            .iter()
+            .find(|road| (centroid(&road.polygon) - Point::new(10.0, 0.0)).norm() < 0.01)
+            .unwrap();
+
+        // The square between the four centerlines holds the block, its sidewalk, half of each
+        // of the four roads around it and a quarter of each of the four junctions
+        let sidewalk = &surfaces.sidewalks[0];
+        let covered = face_area(&surfaces.blocks[0])
+            + (face_area(&sidewalk.outer) - face_area(&sidewalk.inner))
+            + 2.0 * face_area(&road_between_junctions.polygon)
+            + face_area(&surfaces.junctions[0].polygon);
+
+        assert!((covered - 400.0).abs() < 0.05, "covered {covered}");
+    }
+
+    #[test]
+    fn dead_ends_and_bends_get_no_junction() {
+        let levels = vec![vec![curve(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)])]];
+
+        let surfaces = build_road_surfaces(&levels, &config()).unwrap();
+
+        assert!(surfaces.junctions.is_empty());
+        assert!(surfaces.blocks.is_empty());
+        assert_eq!(surfaces.roads.len(), 2);
+        let area: f32 = surfaces
+            .roads
+            .iter()
+            .map(|road| face_area(&road.polygon))
+            .sum();
+        // Two 10 long, 2 wide strips sharing a mitered corner
+        assert!((area - 40.0).abs() < 0.001, "area {area}");
+    }
+
+    #[test]
+    fn faces_too_narrow_for_a_block_are_reported() {
+        // The two vertical streets are closer together than the roads along them are wide
+        let levels = vec![vec![
+            curve(&[(-20.0, 0.0), (0.0, 0.0), (2.5, 0.0), (20.0, 0.0)]),
+            curve(&[(-20.0, 20.0), (0.0, 20.0), (2.5, 20.0), (20.0, 20.0)]),
+            curve(&[(0.0, -20.0), (0.0, 0.0), (0.0, 20.0), (0.0, 40.0)]),
+            curve(&[(2.5, -20.0), (2.5, 0.0), (2.5, 20.0), (2.5, 40.0)]),
+        ]];
+
+        let surfaces = build_road_surfaces(&levels, &config()).unwrap();
+
+        assert!(surfaces.blocks.is_empty());
+        assert_eq!(surfaces.skipped_faces.len(), 1);
+        let skipped = &surfaces.skipped_faces[0];
+        assert!(
+            skipped
+                .curb
+                .iter()
+                .all(|point| point.y > 0.0 && point.y < 20.0)
+        );
+    }
+}
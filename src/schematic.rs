@@ -0,0 +1,962 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use image::{Rgba, RgbaImage};
+
+use crate::config::SchematicConfig;
+use crate::metro::Metro;
+use crate::predicates::segments_intersect;
+use crate::tensor_field::Point;
+
+/// How far a leg may be stretched past one grid step to get around stations already placed
+const MAX_LEG_STEPS: i32 = 4;
+
+pub(crate) const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+pub(crate) const INK: Rgba<u8> = Rgba([25, 25, 25, 255]);
+
+/// Gap between a station marker and its label
+const LABEL_GAP: f32 = 4.0;
+/// How many label heights further out a label may be pushed when every side next to its
+/// station is taken
+const LABEL_RINGS: usize = 4;
+const LEADER_WIDTH: f32 = 1.0;
+const MARKER_STROKE: f32 = 2.0;
+
+/// 5x7 glyphs of the PNG labels, one row per byte with the leftmost pixel in the highest of the
+/// five low bits
+const DIGIT_GLYPHS: [[u8; 7]; 10] = [
+    [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+    [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+    [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+    [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+    [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+    [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+    [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+    [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+];
+
+/// An axis aligned box in map pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Rect {
+    fn around(center: Point, radius: f32) -> Self {
+        let reach = Point::new(radius, radius);
+        Self {
+            min: center - reach,
+            max: center + reach,
+        }
+    }
+
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.min.x < other.max.x
+            && other.min.x < self.max.x
+            && self.min.y < other.max.y
+            && other.min.y < self.max.y
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x) && (self.min.y..=self.max.y).contains(&point.y)
+    }
+
+    fn crosses_segment(&self, segment: [Point; 2]) -> bool {
+        let corners = [
+            self.min,
+            Point::new(self.max.x, self.min.y),
+            self.max,
+            Point::new(self.min.x, self.max.y),
+        ];
+        segment.iter().any(|&point| self.contains(point))
+            || (0..4).any(|i| segments_intersect(segment, [corners[i], corners[(i + 1) % 4]]))
+    }
+
+    fn translate(&mut self, offset: Point) {
+        self.min += offset;
+        self.max += offset;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchematicLine {
+    pub color: [u8; 3],
+    /// The track between every pair of consecutive stations, in the order the line runs. Legs
+    /// shared with other lines are pushed to the side so the lines run next to each other.
+    pub legs: Vec<Vec<Point>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchematicStation {
+    /// Index into `Metro::stations`
+    pub station: usize,
+    pub label: String,
+    /// Where the station sits on each of the lines serving it, one circle each. Interchanges
+    /// have their circles linked.
+    pub markers: Vec<Point>,
+    pub interchange: bool,
+    pub label_box: Rect,
+    /// Line from the station to its label, for labels that had to be pushed away from it
+    pub leader: Option<[Point; 2]>,
+}
+
+/// A metro network laid out as a schematic map, in pixels with `y` pointing down
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchematicMap {
+    pub width: f32,
+    pub height: f32,
+    pub lines: Vec<SchematicLine>,
+    pub stations: Vec<SchematicStation>,
+    pub line_width: f32,
+    pub station_radius: f32,
+    pub label_size: f32,
+}
+
+/// Lays the metro out as an octilinear map. Stations are put on a grid one step apart, starting
+/// from the busiest interchange and following the lines outward, with every leg pointing in
+/// the multiple of 45° closest to its direction in the city. Legs are stretched by whole steps
+/// or turned by 45° when that spot is taken. Legs between stations that were both placed from
+/// other neighbours get a single octilinear bend instead.
+///
+/// Labels are tried on all eight sides of their station, busiest stations first, and go on the
+/// first side where they don't cover another label, station or line.
+pub fn layout_schematic(metro: &Metro, config: &SchematicConfig) -> SchematicMap {
+    let colors: Vec<[u8; 3]> = config
+        .line_colors
+        .iter()
+        .filter_map(|color| parse_hex_color(color))
+        .collect();
+
+    let grid = place_on_grid(metro);
+
+    let to_pixels = |(x, y): (i32, i32)| {
+        Point::new(
+            x as f32 * config.station_spacing,
+            -y as f32 * config.station_spacing,
+        )
+    };
+
+    // Lines sharing a leg are spread out across it, in the order of the lines
+    let mut leg_lines: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (line_index, line) in metro.lines.iter().enumerate() {
+        for window in line.stations.windows(2) {
+            let lines = leg_lines.entry(leg_key(window[0], window[1])).or_default();
+            if !lines.contains(&line_index) {
+                lines.push(line_index);
+            }
+        }
+    }
+
+    let mut markers: Vec<Vec<Point>> = vec![Vec::new(); metro.stations.len()];
+    let mut serving_lines: Vec<HashSet<usize>> = vec![HashSet::new(); metro.stations.len()];
+    let lines: Vec<SchematicLine> = metro
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(line_index, line)| {
+            let legs = line
+                .stations
+                .windows(2)
+                .map(|window| {
+                    let key = leg_key(window[0], window[1]);
+                    let sharing = &leg_lines[&key];
+                    let slot = sharing
+                        .iter()
+                        .position(|&other| other == line_index)
+                        .unwrap();
+                    let offset =
+                        (slot as f32 - (sharing.len() - 1) as f32 / 2.0) * config.line_width;
+
+                    let route: Vec<Point> = octilinear_route(grid[&key.0], grid[&key.1])
+                        .into_iter()
+                        .map(to_pixels)
+                        .collect();
+                    let mut leg = offset_polyline(&route, offset);
+                    if key.0 != window[0] {
+                        leg.reverse();
+                    }
+
+                    for (station, point) in [(window[0], leg[0]), (window[1], leg[leg.len() - 1])] {
+                        if serving_lines[station].insert(line_index) {
+                            markers[station].push(point);
+                        }
+                    }
+                    leg
+                })
+                .collect();
+
+            SchematicLine {
+                color: colors
+                    .get(line_index % colors.len().max(1))
+                    .copied()
+                    .unwrap_or([0, 0, 0]),
+                legs,
+            }
+        })
+        .collect();
+
+    let mut stations: Vec<SchematicStation> = markers
+        .into_iter()
+        .enumerate()
+        .filter(|(_, markers)| !markers.is_empty())
+        .map(|(station, mut markers)| {
+            let mut distinct: Vec<Point> = Vec::new();
+            markers.retain(|&marker| {
+                let is_new = distinct.iter().all(|&other| (other - marker).norm() > 0.5);
+                if is_new {
+                    distinct.push(marker);
+                }
+                is_new
+            });
+            SchematicStation {
+                station,
+                label: (station + 1).to_string(),
+                markers,
+                interchange: serving_lines[station].len() > 1,
+                label_box: Rect {
+                    min: Point::zeros(),
+                    max: Point::zeros(),
+                },
+                leader: None,
+            }
+        })
+        .collect();
+
+    place_labels(metro, &lines, &mut stations, config);
+
+    let mut map = SchematicMap {
+        width: 0.0,
+        height: 0.0,
+        lines,
+        stations,
+        line_width: config.line_width,
+        station_radius: config.station_radius,
+        label_size: config.label_size,
+    };
+    fit_to_margin(&mut map, config.margin);
+    map
+}
+
+fn leg_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// Grid position of every station on a line, breadth first from the station on the most lines
+fn place_on_grid(metro: &Metro) -> HashMap<usize, (i32, i32)> {
+    let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); metro.stations.len()];
+    let mut line_counts = vec![0; metro.stations.len()];
+    for line in &metro.lines {
+        for window in line.stations.windows(2) {
+            if !neighbours[window[0]].contains(&window[1]) {
+                neighbours[window[0]].push(window[1]);
+                neighbours[window[1]].push(window[0]);
+            }
+        }
+        for &station in line.stations.iter().collect::<HashSet<_>>() {
+            line_counts[station] += 1;
+        }
+    }
+
+    let mut roots: Vec<usize> = (0..metro.stations.len())
+        .filter(|&station| line_counts[station] > 0)
+        .collect();
+    roots.sort_by_key(|&station| std::cmp::Reverse(line_counts[station]));
+
+    let mut grid: HashMap<usize, (i32, i32)> = HashMap::new();
+    let mut occupied: HashSet<(i32, i32)> = HashSet::new();
+    for root in roots {
+        if grid.contains_key(&root) {
+            continue;
+        }
+        // Separate parts of the network go to the right of everything placed so far
+        let start = (occupied.iter().map(|&(x, _)| x + 2).max().unwrap_or(0), 0);
+        grid.insert(root, start);
+        occupied.insert(start);
+
+        let mut queue = VecDeque::from([root]);
+        while let Some(station) = queue.pop_front() {
+            for &neighbour in &neighbours[station] {
+                if grid.contains_key(&neighbour) {
+                    continue;
+                }
+                let offset = metro.stations[neighbour].position - metro.stations[station].position;
+                let position = free_grid_position(grid[&station], offset, &occupied);
+                grid.insert(neighbour, position);
+                occupied.insert(position);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    grid
+}
+
+/// The spot for a station in the direction of `offset` from `from`, trying the closest
+/// octilinear direction first, then the ones 45° and 90° off, each at one step and further
+fn free_grid_position(
+    from: (i32, i32),
+    offset: Point,
+    occupied: &HashSet<(i32, i32)>,
+) -> (i32, i32) {
+    let octant = (offset.y.atan2(offset.x) / std::f32::consts::FRAC_PI_4).round() as i32;
+    let direction = |octant: i32| {
+        let angle = octant as f32 * std::f32::consts::FRAC_PI_4;
+        (angle.cos().round() as i32, angle.sin().round() as i32)
+    };
+
+    let candidates = [0, 1, -1, 2, -2].into_iter().flat_map(|turn| {
+        let (dx, dy) = direction(octant + turn);
+        (1..=MAX_LEG_STEPS).map(move |steps| (dx, dy, steps))
+    });
+    for (dx, dy, steps) in candidates {
+        let is_free =
+            (1..=steps).all(|step| !occupied.contains(&(from.0 + dx * step, from.1 + dy * step)));
+        if is_free {
+            return (from.0 + dx * steps, from.1 + dy * steps);
+        }
+    }
+
+    // Crowded all around, keep going straight until there's room
+    let (dx, dy) = direction(octant);
+    (MAX_LEG_STEPS + 1..)
+        .map(|steps| (from.0 + dx * steps, from.1 + dy * steps))
+        .find(|position| !occupied.contains(position))
+        .unwrap()
+}
+
+/// Straight when the two points already line up on a multiple of 45°, otherwise diagonally
+/// first and straight for the rest
+fn octilinear_route(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    if dx == 0 || dy == 0 || dx.abs() == dy.abs() {
+        return vec![from, to];
+    }
+    let diagonal = dx.abs().min(dy.abs());
+    let bend = (
+        from.0 + diagonal * dx.signum(),
+        from.1 + diagonal * dy.signum(),
+    );
+    vec![from, bend, to]
+}
+
+/// Moves a polyline sideways by `distance`, to the left of its direction for positive distances
+fn offset_polyline(points: &[Point], distance: f32) -> Vec<Point> {
+    if distance == 0.0 {
+        return points.to_vec();
+    }
+    let normals: Vec<Point> = points
+        .windows(2)
+        .map(|window| {
+            let direction = (window[1] - window[0])
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Point::zeros);
+            Point::new(-direction.y, direction.x)
+        })
+        .collect();
+
+    (0..points.len())
+        .map(|i| {
+            let before = normals[i.saturating_sub(1).min(normals.len() - 1)];
+            let after = normals[i.min(normals.len() - 1)];
+            let miter = (before + after)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or(after);
+            points[i] + miter * (distance / miter.dot(&after).max(0.5))
+        })
+        .collect()
+}
+
+pub(crate) fn label_width(label: &str, label_size: f32) -> f32 {
+    let cell = label_size / 7.0;
+    (label.chars().count() as f32 * 6.0 - 1.0).max(0.0) * cell
+}
+
+fn place_labels(
+    metro: &Metro,
+    lines: &[SchematicLine],
+    stations: &mut [SchematicStation],
+    config: &SchematicConfig,
+) {
+    let marker_radius = config.station_radius + MARKER_STROKE;
+    let track_reach = config.line_width / 2.0;
+
+    let segments: Vec<[Point; 2]> = lines
+        .iter()
+        .flat_map(|line| &line.legs)
+        .flat_map(|leg| leg.windows(2).map(|window| [window[0], window[1]]))
+        .collect();
+    let markers: Vec<Rect> = stations
+        .iter()
+        .flat_map(|station| &station.markers)
+        .map(|&marker| Rect::around(marker, marker_radius))
+        .collect();
+
+    let mut order: Vec<usize> = (0..stations.len()).collect();
+    order.sort_by(|&a, &b| {
+        metro.stations[stations[b].station]
+            .demand
+            .total_cmp(&metro.stations[stations[a].station].demand)
+    });
+
+    let mut placed: Vec<Rect> = Vec::new();
+    for index in order {
+        let station = &stations[index];
+        let bounds = station.markers.iter().fold(
+            Rect {
+                min: Point::new(f32::MAX, f32::MAX),
+                max: Point::new(f32::MIN, f32::MIN),
+            },
+            |bounds, &marker| Rect {
+                min: bounds
+                    .min
+                    .inf(&(marker - Point::new(marker_radius, marker_radius))),
+                max: bounds
+                    .max
+                    .sup(&(marker + Point::new(marker_radius, marker_radius))),
+            },
+        );
+        let size = Point::new(
+            label_width(&station.label, config.label_size),
+            config.label_size,
+        );
+        let center = (bounds.min + bounds.max) / 2.0;
+
+        // East and west read best, then above and below, then the corners. When all of them are
+        // taken the label moves further out on the same sides and gets a leader line.
+        let sides = [
+            (1, 0),
+            (-1, 0),
+            (0, -1),
+            (0, 1),
+            (1, -1),
+            (1, 1),
+            (-1, -1),
+            (-1, 1),
+        ];
+        let candidates = (0..LABEL_RINGS).flat_map(|ring| {
+            let gap = LABEL_GAP + ring as f32 * (config.label_size + LABEL_GAP);
+            sides.map(|(x, y): (i32, i32)| {
+                let along = |side: i32, low: f32, high: f32, center: f32, size: f32| match side {
+                    1 => high + gap,
+                    -1 => low - gap - size,
+                    _ => center - size / 2.0,
+                };
+                let min = Point::new(
+                    along(x, bounds.min.x, bounds.max.x, center.x, size.x),
+                    along(y, bounds.min.y, bounds.max.y, center.y, size.y),
+                );
+                (
+                    ring,
+                    Rect {
+                        min,
+                        max: min + size,
+                    },
+                )
+            })
+        });
+
+        let conflicts = |candidate: &Rect| {
+            let track_box = Rect {
+                min: candidate.min - Point::new(track_reach, track_reach),
+                max: candidate.max + Point::new(track_reach, track_reach),
+            };
+            placed
+                .iter()
+                .filter(|other| other.overlaps(candidate))
+                .count()
+                + markers
+                    .iter()
+                    .filter(|other| other.overlaps(candidate))
+                    .count()
+                + segments
+                    .iter()
+                    .filter(|&&segment| track_box.crosses_segment(segment))
+                    .count()
+        };
+        let (ring, label_box) = candidates
+            .min_by_key(|(ring, candidate)| (conflicts(candidate), *ring))
+            .unwrap();
+
+        placed.push(label_box);
+        stations[index].label_box = label_box;
+        stations[index].leader =
+            (ring > 0).then(|| [center, center.sup(&label_box.min).inf(&label_box.max)]);
+    }
+}
+
+/// Moves everything so the map starts `margin` from the top left corner and sizes it to leave
+/// the same margin on the other sides
+fn fit_to_margin(map: &mut SchematicMap, margin: f32) {
+    let reach = map.station_radius + MARKER_STROKE;
+    let points = map
+        .lines
+        .iter()
+        .flat_map(|line| line.legs.iter().flatten().copied())
+        .chain(map.stations.iter().flat_map(|station| {
+            station
+                .markers
+                .iter()
+                .flat_map(|&marker| {
+                    [
+                        marker - Point::new(reach, reach),
+                        marker + Point::new(reach, reach),
+                    ]
+                })
+                .chain([station.label_box.min, station.label_box.max])
+        }));
+    let (min, max) = points.fold(
+        (
+            Point::new(f32::MAX, f32::MAX),
+            Point::new(f32::MIN, f32::MIN),
+        ),
+        |(min, max), point| (min.inf(&point), max.sup(&point)),
+    );
+    if min.x > max.x {
+        map.width = 2.0 * margin;
+        map.height = 2.0 * margin;
+        return;
+    }
+
+    let offset = Point::new(margin, margin) - min;
+    for point in map
+        .lines
+        .iter_mut()
+        .flat_map(|line| line.legs.iter_mut().flatten())
+    {
+        *point += offset;
+    }
+    for station in &mut map.stations {
+        for marker in &mut station.markers {
+            *marker += offset;
+        }
+        station.label_box.translate(offset);
+        if let Some(leader) = &mut station.leader {
+            for point in leader {
+                *point += offset;
+            }
+        }
+    }
+    map.width = max.x - min.x + 2.0 * margin;
+    map.height = max.y - min.y + 2.0 * margin;
+}
+
+/// Parses colors written as `#rrggbb`
+pub(crate) fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn hex_color([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Writes the map as an SVG document. Labels use a monospace font stretched to the width they
+/// were placed with.
+pub fn write_svg<W: Write>(map: &SchematicMap, writer: &mut W) -> io::Result<()> {
+    let point_list = |points: &[Point]| {
+        points
+            .iter()
+            .map(|point| format!("{:.1},{:.1}", point.x, point.y))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.0} {h:.0}">"#,
+        w = map.width.ceil(),
+        h = map.height.ceil(),
+    )?;
+    writeln!(writer, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+
+    writeln!(
+        writer,
+        r#"<g fill="none" stroke-width="{:.1}" stroke-linecap="round" stroke-linejoin="round">"#,
+        map.line_width
+    )?;
+    for line in &map.lines {
+        for leg in &line.legs {
+            writeln!(
+                writer,
+                r#"<polyline points="{}" stroke="{}"/>"#,
+                point_list(leg),
+                hex_color(line.color)
+            )?;
+        }
+    }
+    writeln!(writer, "</g>")?;
+
+    for [start, end] in map.stations.iter().filter_map(|station| station.leader) {
+        writeln!(
+            writer,
+            r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="black" stroke-width="{LEADER_WIDTH:.1}"/>"#,
+            start.x, start.y, end.x, end.y,
+        )?;
+    }
+
+    let radius = map.station_radius;
+    for station in &map.stations {
+        if station.interchange {
+            for pair in station.markers.windows(2) {
+                for (color, width) in [
+                    ("black", 2.0 * (radius + MARKER_STROKE)),
+                    ("white", 2.0 * radius),
+                ] {
+                    writeln!(
+                        writer,
+                        r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{color}" stroke-width="{width:.1}"/>"#,
+                        pair[0].x, pair[0].y, pair[1].x, pair[1].y,
+                    )?;
+                }
+            }
+        }
+        let stroke = if station.interchange {
+            "black"
+        } else {
+            "#555555"
+        };
+        for marker in &station.markers {
+            writeln!(
+                writer,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="{radius:.1}" fill="white" stroke="{stroke}" stroke-width="{MARKER_STROKE:.1}"/>"#,
+                marker.x, marker.y,
+            )?;
+        }
+    }
+
+    for station in &map.stations {
+        let label_box = station.label_box;
+        writeln!(
+            writer,
+            r#"<text x="{:.1}" y="{:.1}" font-family="monospace" font-size="{:.1}" textLength="{:.1}" lengthAdjust="spacingAndGlyphs">{}</text>"#,
+            label_box.min.x,
+            label_box.max.y,
+            map.label_size,
+            label_box.max.x - label_box.min.x,
+            station.label,
+        )?;
+    }
+
+    writeln!(writer, "</svg>")
+}
+
+/// Draws the map into an image, antialiased, one pixel per map unit
+pub fn render_png(map: &SchematicMap) -> RgbaImage {
+    let mut image = RgbaImage::from_pixel(
+        map.width.ceil().max(1.0) as u32,
+        map.height.ceil().max(1.0) as u32,
+        BACKGROUND,
+    );
+
+    for line in &map.lines {
+        let [r, g, b] = line.color;
+        for leg in &line.legs {
+            for window in leg.windows(2) {
+                fill_capsule(
+                    &mut image,
+                    [window[0], window[1]],
+                    map.line_width / 2.0,
+                    Rgba([r, g, b, 255]),
+                );
+            }
+        }
+    }
+
+    for leader in map.stations.iter().filter_map(|station| station.leader) {
+        fill_capsule(&mut image, leader, LEADER_WIDTH / 2.0, INK);
+    }
+
+    let radius = map.station_radius;
+    for station in &map.stations {
+        let stroke = if station.interchange {
+            INK
+        } else {
+            Rgba([85, 85, 85, 255])
+        };
+        if station.interchange {
+            for pair in station.markers.windows(2) {
+                fill_capsule(&mut image, [pair[0], pair[1]], radius + MARKER_STROKE, INK);
+            }
+        }
+        for &marker in &station.markers {
+            fill_capsule(
+                &mut image,
+                [marker, marker],
+                radius + MARKER_STROKE / 2.0,
+                stroke,
+            );
+        }
+        if station.interchange {
+            for pair in station.markers.windows(2) {
+                fill_capsule(
+                    &mut image,
+                    [pair[0], pair[1]],
+                    radius - MARKER_STROKE / 2.0,
+                    BACKGROUND,
+                );
+            }
+        }
+        for &marker in &station.markers {
+            fill_capsule(
+                &mut image,
+                [marker, marker],
+                radius - MARKER_STROKE / 2.0,
+                BACKGROUND,
+            );
+        }
+    }
+
+    for station in &map.stations {
+        draw_label(
+            &mut image,
+            &station.label,
+            station.label_box.min,
+            map.label_size,
+        );
+    }
+
+    image
+}
+
+/// Fills everything within `radius` of the segment, blending the edge by how much of each pixel
+/// it covers
+pub(crate) fn fill_capsule(
+    image: &mut RgbaImage,
+    segment: [Point; 2],
+    radius: f32,
+    color: Rgba<u8>,
+) {
+    let min = segment[0].inf(&segment[1]) - Point::new(radius + 1.0, radius + 1.0);
+    let max = segment[0].sup(&segment[1]) + Point::new(radius + 1.0, radius + 1.0);
+    let direction = segment[1] - segment[0];
+    let length_squared = direction.norm_squared();
+
+    for y in min.y.floor().max(0.0) as u32..(max.y.ceil().max(0.0) as u32).min(image.height()) {
+        for x in min.x.floor().max(0.0) as u32..(max.x.ceil().max(0.0) as u32).min(image.width()) {
+            let center = Point::new(x as f32 + 0.5, y as f32 + 0.5);
+            let t = if length_squared > 0.0 {
+                ((center - segment[0]).dot(&direction) / length_squared).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let distance = (center - (segment[0] + direction * t)).norm();
+            let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                blend(image.get_pixel_mut(x, y), color, coverage);
+            }
+        }
+    }
+}
+
+pub(crate) fn blend(pixel: &mut Rgba<u8>, color: Rgba<u8>, coverage: f32) {
+    for channel in 0..3 {
+        let mixed =
+            pixel[channel] as f32 + (color[channel] as f32 - pixel[channel] as f32) * coverage;
+        pixel[channel] = mixed.round() as u8;
+    }
+    pixel[3] = 255;
+}
+
+pub(crate) fn draw_label(image: &mut RgbaImage, label: &str, top_left: Point, label_size: f32) {
+    let cell = label_size / 7.0;
+    for (i, character) in label.chars().enumerate() {
+        let Some(glyph) = character
+            .to_digit(10)
+            .map(|digit| DIGIT_GLYPHS[digit as usize])
+        else {
+            continue;
+        };
+        let left = top_left.x + i as f32 * 6.0 * cell;
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..5 {
+                if bits & (0x10 >> column) == 0 {
+                    continue;
+                }
+                let cell_min =
+                    Point::new(left + column as f32 * cell, top_left.y + row as f32 * cell);
+                fill_rect(image, cell_min, cell_min + Point::new(cell, cell), INK);
+            }
+        }
+    }
+}
+
+pub(crate) fn fill_rect(image: &mut RgbaImage, min: Point, max: Point, color: Rgba<u8>) {
+    for y in min.y.floor().max(0.0) as u32..(max.y.ceil().max(0.0) as u32).min(image.height()) {
+        for x in min.x.floor().max(0.0) as u32..(max.x.ceil().max(0.0) as u32).min(image.width()) {
+            let covered_x = (max.x.min(x as f32 + 1.0) - min.x.max(x as f32)).clamp(0.0, 1.0);
+            let covered_y = (max.y.min(y as f32 + 1.0) - min.y.max(y as f32)).clamp(0.0, 1.0);
+            blend(image.get_pixel_mut(x, y), color, covered_x * covered_y);
+        }
+    }
+}
+
+/// Writes the map to `path`, as PNG when it ends in `.png` and as SVG when it ends in `.svg`
+pub fn export_schematic(map: &SchematicMap, path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) if extension.eq_ignore_ascii_case("png") => {
+            render_png(map).save(path).map_err(io::Error::other)
+        }
+        Some(extension) if extension.eq_ignore_ascii_case("svg") => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            write_svg(map, &mut writer)?;
+            writer.flush()
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is neither a .svg nor a .png file", path.display()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::SchematicConfig;
+    use crate::metro::{Metro, MetroLine, Station};
+    use crate::tensor_field::Point;
+
+    use super::{
+        Rect, SchematicLine, SchematicStation, layout_schematic, place_labels, render_png,
+        write_svg,
+    };
+
+    fn metro() -> Metro {
+        let positions = [
+            Point::new(0.0, 0.0),
+            Point::new(40.0, 5.0),
+            Point::new(80.0, 30.0),
+            Point::new(-35.0, -10.0),
+            Point::new(5.0, 45.0),
+            Point::new(-10.0, -50.0),
+            Point::new(60.0, -40.0),
+        ];
+        let stations = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| Station {
+                position,
+                block: i,
+                demand: 10.0 - i as f32,
+            })
+            .collect();
+        let line = |stations: Vec<usize>| MetroLine {
+            polyline: stations.iter().map(|&i| positions[i]).collect(),
+            stations,
+        };
+        Metro {
+            stations,
+            lines: vec![
+                line(vec![3, 0, 1, 2]),
+                line(vec![5, 0, 4]),
+                line(vec![6, 1, 0, 4]),
+            ],
+        }
+    }
+
+    #[test]
+    fn legs_are_octilinear_and_labels_stay_apart() {
+        let map = layout_schematic(&metro(), &SchematicConfig::default());
+
+        assert_eq!(map.lines.len(), 3);
+        assert_eq!(map.stations.len(), 7);
+        for leg in map.lines.iter().flat_map(|line| &line.legs) {
+            for window in leg.windows(2) {
+                let direction = window[1] - window[0];
+                let angle = direction.y.atan2(direction.x) / std::f32::consts::FRAC_PI_4;
+                assert!((angle - angle.round()).abs() < 0.001);
+            }
+        }
+        for (i, a) in map.stations.iter().enumerate() {
+            for b in &map.stations[i + 1..] {
+                assert!(!a.label_box.overlaps(&b.label_box));
+            }
+        }
+
+        let interchanges: Vec<usize> = map
+            .stations
+            .iter()
+            .filter(|station| station.interchange)
+            .map(|station| station.station)
+            .collect();
+        assert_eq!(interchanges.len(), 3);
+        assert!(
+            interchanges
+                .iter()
+                .all(|station| [0, 1, 4].contains(station))
+        );
+    }
+
+    #[test]
+    fn svg_and_png_cover_the_map() {
+        let map = layout_schematic(&metro(), &SchematicConfig::default());
+
+        let mut svg = Vec::new();
+        write_svg(&map, &mut svg).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<polyline").count(), 3 + 2 + 3);
+        assert_eq!(svg.matches("<text").count(), 7);
+
+        let image = render_png(&map);
+        assert_eq!(image.width(), map.width.ceil() as u32);
+        assert_eq!(image.height(), map.height.ceil() as u32);
+        assert!(
+            image
+                .pixels()
+                .any(|pixel| pixel.0 == [0xe3, 0x20, 0x17, 0xff])
+        );
+    }
+
+    #[test]
+    fn crowded_labels_move_out_behind_a_leader() {
+        let metro = Metro {
+            stations: vec![Station {
+                position: Point::zeros(),
+                block: 0,
+                demand: 1.0,
+            }],
+            lines: Vec::new(),
+        };
+        // Tracks close enough together to cross every side right next to the station
+        let lines = vec![SchematicLine {
+            color: [0, 0, 0],
+            legs: (-3..=3)
+                .map(|row| {
+                    let y = row as f32 * 8.0;
+                    vec![Point::new(-24.0, y), Point::new(24.0, y)]
+                })
+                .collect(),
+        }];
+        let mut stations = vec![SchematicStation {
+            station: 0,
+            label: "1".to_string(),
+            markers: vec![Point::zeros()],
+            interchange: false,
+            label_box: Rect {
+                min: Point::zeros(),
+                max: Point::zeros(),
+            },
+            leader: None,
+        }];
+        let config = SchematicConfig::default();
+
+        place_labels(&metro, &lines, &mut stations, &config);
+
+        let station = &stations[0];
+        assert!(station.label_box.min.x > 24.0 + config.line_width / 2.0);
+        let [start, end] = station.leader.unwrap();
+        assert_eq!(start, Point::zeros());
+        assert_eq!(end.x, station.label_box.min.x);
+        assert!((station.label_box.min.y..=station.label_box.max.y).contains(&end.y));
+    }
+}
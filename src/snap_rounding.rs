@@ -0,0 +1,279 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::predicates::{line_intersection, orient2d, segments_intersect};
+use crate::street_graph::Segment;
+use crate::tensor_field::Point;
+
+/// Side length of a snapping pixel. It is a power of two so every pixel center is exactly
+/// representable, and it is coarser than every tolerance the sweep in `find_interesctions` uses
+/// to decide that two points are the same, so two different snapped vertices are never mistaken
+/// for one another. It is still far below the scale of a street.
+pub(crate) const SNAP_PIXEL_SIZE: f32 = 1.0 / 16.0;
+
+/// Segments and hot pixels are bucketed into cells of this size so that only the pairs that are
+/// close to each other get tested
+const BUCKET_SIZE: f32 = 4.0;
+
+type PixelKey = (i64, i64);
+
+fn pixel_key(point: Point) -> PixelKey {
+    (
+        (point.x / SNAP_PIXEL_SIZE).round() as i64,
+        (point.y / SNAP_PIXEL_SIZE).round() as i64,
+    )
+}
+
+fn pixel_center(key: PixelKey) -> Point {
+    Point::new(
+        key.0 as f32 * SNAP_PIXEL_SIZE,
+        key.1 as f32 * SNAP_PIXEL_SIZE,
+    )
+}
+
+fn bucket_key(point: Point) -> (i64, i64) {
+    (
+        (point.x / BUCKET_SIZE).floor() as i64,
+        (point.y / BUCKET_SIZE).floor() as i64,
+    )
+}
+
+/// Moves a point to the center of the snapping pixel it falls in
+#[cfg(test)]
+pub(crate) fn snap_point(point: Point) -> Point {
+    pixel_center(pixel_key(point))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SnappedFragment {
+    pub segment: Segment,
+    /// Indices of the input segments that were rounded onto this fragment. There is more than one
+    /// when overlapping segments collapse onto the same fragment.
+    pub sources: Vec<usize>,
+}
+
+/// Snap rounds the segments to the pixel grid. Every pixel containing an endpoint or an
+/// intersection becomes a hot pixel, and each segment is replaced by the polyline through the
+/// centers of all of the hot pixels it passes through, in the order they appear along it. The
+/// routing is repeated on the resulting fragments until none of them passes through a hot pixel
+/// it doesn't end in (iterated snap rounding).
+///
+/// Points that were only nearly shared end up exactly shared, segments that nearly touched a
+/// vertex are routed through it and crossings become shared endpoints, so the fragments only
+/// ever meet at their endpoints. Fragments that collapse to a single pixel or onto another
+/// fragment are dropped.
+pub(crate) fn snap_round(segments: &[Segment]) -> Vec<SnappedFragment> {
+    let hot_pixels = find_hot_pixels(segments);
+
+    let mut buckets: HashMap<(i64, i64), Vec<PixelKey>> = HashMap::new();
+    for &pixel in &hot_pixels {
+        buckets
+            .entry(bucket_key(pixel_center(pixel)))
+            .or_default()
+            .push(pixel);
+    }
+
+    let unrouted: Vec<SnappedFragment> = segments
+        .iter()
+        .enumerate()
+        .map(|(i, &segment)| SnappedFragment {
+            segment,
+            sources: vec![i],
+        })
+        .collect();
+
+    let (mut fragments, _) = route_through_hot_pixels(&unrouted, &buckets);
+    loop {
+        let (rerouted, changed) = route_through_hot_pixels(&fragments, &buckets);
+        fragments = rerouted;
+        if !changed {
+            return fragments;
+        }
+    }
+}
+
+/// Hot pixels are found with the exact predicates rather than the sweep, by testing every pair
+/// of segments that share a bucket. The segments are short compared to the buckets, so this stays
+/// close to linear in practice.
+fn find_hot_pixels(segments: &[Segment]) -> HashSet<PixelKey> {
+    let mut hot_pixels: HashSet<PixelKey> =
+        segments.iter().flatten().copied().map(pixel_key).collect();
+
+    let mut buckets: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (segment_index, &segment) in segments.iter().enumerate() {
+        for bucket in buckets_around_segment(segment) {
+            buckets.entry(bucket).or_default().push(segment_index);
+        }
+    }
+
+    for segment_indices in buckets.values() {
+        for (i, &segment_index_0) in segment_indices.iter().enumerate() {
+            for &segment_index_1 in &segment_indices[i + 1..] {
+                let segment_0 = segments[segment_index_0];
+                let segment_1 = segments[segment_index_1];
+                if segments_intersect(segment_0, segment_1)
+                    && let Some(intersection) = line_intersection(segment_0, segment_1)
+                {
+                    hot_pixels.insert(pixel_key(intersection));
+                }
+            }
+        }
+    }
+
+    hot_pixels
+}
+
+/// Replaces every segment with the fragments between the hot pixels it passes through. The flag
+/// is set when any segment had to be routed through more than just its own endpoints.
+fn route_through_hot_pixels(
+    fragments: &[SnappedFragment],
+    buckets: &HashMap<(i64, i64), Vec<PixelKey>>,
+) -> (Vec<SnappedFragment>, bool) {
+    let mut fragment_indices: HashMap<(PixelKey, PixelKey), usize> = HashMap::new();
+    let mut routed_fragments: Vec<SnappedFragment> = Vec::new();
+    let mut changed = false;
+
+    for SnappedFragment { segment, sources } in fragments {
+        let segment = *segment;
+        let direction = segment[1] - segment[0];
+        let along_segment = |pixel: PixelKey| {
+            let offset = pixel_center(pixel) - segment[0];
+            offset.x as f64 * direction.x as f64 + offset.y as f64 * direction.y as f64
+        };
+
+        // The endpoints are added explicitly, an endpoint can sit right on the border of its own
+        // pixel where the interior test wouldn't pick it up
+        let mut pixels_on_segment: Vec<(f64, PixelKey)> = buckets_around_segment(segment)
+            .filter_map(|bucket| buckets.get(&bucket))
+            .flatten()
+            .copied()
+            .filter(|&pixel| segment_passes_through_pixel(segment, pixel))
+            .chain(segment.map(pixel_key))
+            .map(|pixel| (along_segment(pixel), pixel))
+            .collect();
+
+        pixels_on_segment.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        pixels_on_segment.dedup_by_key(|(_, pixel)| *pixel);
+
+        changed |= pixels_on_segment.len() > 2;
+
+        for window in pixels_on_segment.windows(2) {
+            let (start, end) = (window[0].1, window[1].1);
+            let key = if start < end {
+                (start, end)
+            } else {
+                (end, start)
+            };
+            match fragment_indices.get(&key) {
+                Some(&index) => {
+                    let existing_sources = &mut routed_fragments[index].sources;
+                    for source in sources {
+                        if !existing_sources.contains(source) {
+                            existing_sources.push(*source);
+                        }
+                    }
+                }
+                None => {
+                    fragment_indices.insert(key, routed_fragments.len());
+                    routed_fragments.push(SnappedFragment {
+                        segment: [pixel_center(start), pixel_center(end)],
+                        sources: sources.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    (routed_fragments, changed)
+}
+
+fn buckets_around_segment(segment: Segment) -> impl Iterator<Item = (i64, i64)> {
+    let half_pixel = Point::new(SNAP_PIXEL_SIZE, SNAP_PIXEL_SIZE) / 2.0;
+    let min_bucket = bucket_key(segment[0].inf(&segment[1]) - half_pixel);
+    let max_bucket = bucket_key(segment[0].sup(&segment[1]) + half_pixel);
+
+    (min_bucket.0..=max_bucket.0)
+        .flat_map(move |x| (min_bucket.1..=max_bucket.1).map(move |y| (x, y)))
+}
+
+/// Whether the segment goes through the open square of the pixel. Only touching the border
+/// doesn't count, otherwise a segment through the corner of a pixel would be routed through all
+/// four pixels around that corner.
+fn segment_passes_through_pixel(segment: Segment, pixel: PixelKey) -> bool {
+    let center = pixel_center(pixel);
+    let half_size = SNAP_PIXEL_SIZE / 2.0;
+    let pixel_min = center - Point::new(half_size, half_size);
+    let pixel_max = center + Point::new(half_size, half_size);
+
+    let segment_min = segment[0].inf(&segment[1]);
+    let segment_max = segment[0].sup(&segment[1]);
+    if segment_max.x <= pixel_min.x
+        || segment_min.x >= pixel_max.x
+        || segment_max.y <= pixel_min.y
+        || segment_min.y >= pixel_max.y
+    {
+        return false;
+    }
+
+    let sides: Vec<f64> = [
+        pixel_min,
+        Point::new(pixel_max.x, pixel_min.y),
+        pixel_max,
+        Point::new(pixel_min.x, pixel_max.y),
+    ]
+    .into_iter()
+    .map(|corner| orient2d(segment[0], segment[1], corner))
+    .collect();
+
+    !(sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tensor_field::Point;
+
+    use super::{SNAP_PIXEL_SIZE, snap_point, snap_round};
+
+    #[test]
+    fn nearly_shared_endpoints_become_shared() {
+        let segments = [
+            [Point::new(0.0, 0.0), Point::new(10.0, 10.0)],
+            [Point::new(10.0003, 9.9998), Point::new(20.0, 0.0)],
+        ];
+
+        let fragments = snap_round(&segments);
+
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(fragments[0].segment[1], fragments[1].segment[0]);
+        assert_eq!(fragments[0].segment[1], Point::new(10.0, 10.0));
+    }
+
+    #[test]
+    fn segment_passing_next_to_a_vertex_is_routed_through_it() {
+        let segments = [
+            [Point::new(0.0, 0.0), Point::new(10.0, 0.0)],
+            [Point::new(5.0, 0.0004), Point::new(5.0, 10.0)],
+        ];
+
+        let fragments = snap_round(&segments);
+
+        let vertex = snap_point(Point::new(5.0, 0.0004));
+        assert_eq!(vertex, Point::new(5.0, 0.0));
+        assert_eq!(fragments.len(), 3);
+        assert_eq!(
+            fragments
+                .iter()
+                .filter(|fragment| fragment.segment.contains(&vertex))
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn snapped_points_lie_on_the_grid() {
+        let point = snap_point(Point::new(123.45678, -9.87654));
+
+        assert!(((point.x / SNAP_PIXEL_SIZE).fract()).abs() < f32::EPSILON);
+        assert!(((point.y / SNAP_PIXEL_SIZE).fract()).abs() < f32::EPSILON);
+        assert!((point - Point::new(123.45678, -9.87654)).abs().max() <= SNAP_PIXEL_SIZE / 2.0);
+    }
+}
@@ -1,13 +1,16 @@
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
-use std::iter;
 
 use cool_utils::data_structures::dcel::DCEL;
 use nalgebra::{Matrix2, Vector2};
 use ordered_float::OrderedFloat;
 
+use crate::block_trace::{BlockPass, FaceTrace, PassSnapshot};
+use crate::config::BlockConfig;
 use crate::event_queue::EventQueue;
+use crate::predicates::{line_intersection, segments_intersect};
+use crate::snap_rounding::snap_round;
 use crate::status::{SkipList, get_x_val_of_segment_at_height};
 use crate::street_plan::HermiteCurve;
 use crate::tensor_field::Point;
@@ -48,7 +51,7 @@ impl PartialEq for IntersectionPoint {
 impl Eq for IntersectionPoint {}
 
 #[derive(Debug, Clone)]
-pub struct EventPoint {
+pub(crate) struct EventPoint {
     pub position: Point,
     pub segment_indices: HashSet<usize>,
     pub event_type: EventPointType,
@@ -89,7 +92,7 @@ impl EventPoint {
 }
 
 #[derive(PartialEq, PartialOrd, Eq, Debug, Clone, Copy)]
-pub enum EventPointType {
+pub(crate) enum EventPointType {
     StartPoint,
     Intersection,
     EndPoint,
@@ -148,6 +151,94 @@ impl Ord for EventPoint {
 
 pub type Segment = [Point; 2];
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeometryError {
+    /// The sweep popped an event that lies below the next event in the queue
+    EventQueueOutOfOrder { event: Point, next_event: Point },
+    /// The status stopped being ordered left to right at the given sweep height
+    UnsortedStatus { height: f32 },
+    /// An event changed the number of segments in the status by a different amount than the
+    /// number of segments it carries
+    StatusSizeMismatch {
+        position: Point,
+        expected: usize,
+        found: usize,
+    },
+    /// Two graph vertices truncate to the same position and can't be told apart
+    DuplicateVertex { position: Point },
+    /// A segment ends at a position that is not one of the graph's vertices
+    MissingVertex { position: Point },
+    /// A ray cast from a block vertex did not hit any of the block's edges
+    RaycastMissed {
+        origin: Point,
+        direction: Vector2<f32>,
+    },
+    /// A block polygon degenerated to the point where no faces could be extracted from it
+    NoFaces,
+}
+
+impl std::fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeometryError::EventQueueOutOfOrder { event, next_event } => write!(
+                f,
+                "event at {:?} was processed before the higher event at {:?}",
+                (event.x, event.y),
+                (next_event.x, next_event.y)
+            ),
+            GeometryError::UnsortedStatus { height } => {
+                write!(f, "sweep line status is not sorted at height {height}")
+            }
+            GeometryError::StatusSizeMismatch {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "sweep line status has {found} segments after the event at {:?}, \
+                 expected {expected}",
+                (position.x, position.y)
+            ),
+            GeometryError::DuplicateVertex { position } => write!(
+                f,
+                "multiple graph vertices collapse onto {:?}",
+                (position.x, position.y)
+            ),
+            GeometryError::MissingVertex { position } => write!(
+                f,
+                "no graph vertex exists at segment end {:?}",
+                (position.x, position.y)
+            ),
+            GeometryError::RaycastMissed { origin, direction } => write!(
+                f,
+                "ray from {:?} in direction {:?} did not hit the face",
+                (origin.x, origin.y),
+                (direction.x, direction.y)
+            ),
+            GeometryError::NoFaces => write!(f, "polygon has no faces"),
+        }
+    }
+}
+
+impl std::error::Error for GeometryError {}
+
+/// A street face that could not be turned into blocks, kept so it can be reported instead of
+/// taking the rest of the city down with it
+#[derive(Debug, Clone)]
+pub struct FaceFailure {
+    pub face: Vec<Point>,
+    pub error: GeometryError,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Blocks {
+    pub faces: Vec<Vec<Point>>,
+    pub failures: Vec<FaceFailure>,
+    /// Every street face with its polygons after each pass, only filled in when
+    /// `BlockConfig::trace_passes` is set
+    pub traces: Vec<FaceTrace>,
+}
+
 fn point_cmp(p_0: Point, p_1: Point) -> Ordering {
     match p_0.y.total_cmp(&p_1.y) {
         Ordering::Less => Ordering::Less,
@@ -162,7 +253,7 @@ fn point_cmp(p_0: Point, p_1: Point) -> Ordering {
     }
 }
 
-pub fn segment_start(segment: Segment) -> Point {
+pub(crate) fn segment_start(segment: Segment) -> Point {
     if point_cmp(segment[0], segment[1]) == Ordering::Greater {
         segment[0]
     } else {
@@ -170,7 +261,7 @@ pub fn segment_start(segment: Segment) -> Point {
     }
 }
 
-pub fn segment_end(segment: Segment) -> Point {
+pub(crate) fn segment_end(segment: Segment) -> Point {
     if point_cmp(segment[0], segment[1]) == Ordering::Less {
         segment[0]
     } else {
@@ -178,7 +269,7 @@ pub fn segment_end(segment: Segment) -> Point {
     }
 }
 
-pub fn new_point_lower_than_event(event: Point, new_point: Point) -> bool {
+pub(crate) fn new_point_lower_than_event(event: Point, new_point: Point) -> bool {
     match event.y.total_cmp(&new_point.y) {
         Ordering::Less => false,
         Ordering::Equal => event.x <= new_point.x + 0.001,
@@ -189,7 +280,7 @@ pub fn new_point_lower_than_event(event: Point, new_point: Point) -> bool {
 pub fn find_interesctions(
     segments: &[Segment],
     start_and_end_points_are_intersections: bool,
-) -> Vec<IntersectionPoint> {
+) -> Result<Vec<IntersectionPoint>, GeometryError> {
     let mut event_queue = EventQueue::from_segments(segments);
     let mut status = SkipList::new();
 
@@ -202,20 +293,44 @@ pub fn find_interesctions(
             segments,
             intersections.last_mut(),
             start_and_end_points_are_intersections,
-        );
+        )?;
         intersections.extend(possible_intersection);
         for new_event in new_events {
             event_queue.push(new_event);
         }
-        if let Some(peek) = event_queue.peek() {
-            assert!(event.position().y >= peek.position().y);
+        if let Some(peek) = event_queue.peek()
+            && event.position().y < peek.position().y
+        {
+            return Err(GeometryError::EventQueueOutOfOrder {
+                event: event.position(),
+                next_event: peek.position(),
+            });
         }
 
-        let sorted = status.is_sorted(event.position().y, segments);
-        assert!(sorted);
+        if !status.is_sorted(event.position().y, segments) {
+            return Err(GeometryError::UnsortedStatus {
+                height: event.position().y,
+            });
+        }
     }
 
-    intersections
+    Ok(intersections)
+}
+
+fn check_status_size(
+    status: &SkipList,
+    expected: usize,
+    position: Point,
+) -> Result<(), GeometryError> {
+    if status.len() == expected {
+        Ok(())
+    } else {
+        Err(GeometryError::StatusSizeMismatch {
+            position,
+            expected,
+            found: status.len(),
+        })
+    }
 }
 
 fn update_status(
@@ -224,7 +339,7 @@ fn update_status(
     segments: &[Segment],
     last_intersection: Option<&mut IntersectionPoint>,
     start_and_end_points_are_intersections: bool,
-) -> (Vec<EventPoint>, Option<IntersectionPoint>) {
+) -> Result<(Vec<EventPoint>, Option<IntersectionPoint>), GeometryError> {
     match event.event_type {
         EventPointType::StartPoint => update_status_with_start_point(
             event,
@@ -330,7 +445,7 @@ fn update_status_with_start_point(
     status: &mut SkipList,
     segments: &[Segment],
     start_points_are_intersections: bool,
-) -> (Vec<EventPoint>, Option<IntersectionPoint>) {
+) -> Result<(Vec<EventPoint>, Option<IntersectionPoint>), GeometryError> {
     let original_status_len = status.len();
 
     let event_segment_indices_vec: Vec<usize> = event.segment_indices.iter().copied().collect();
@@ -394,12 +509,13 @@ fn update_status_with_start_point(
         start_intersection_segments.extend(sorted_event_segment_indices);
     }
 
-    assert_eq!(
-        status.len(),
-        original_status_len + event_segment_indices_vec.len()
-    );
+    check_status_size(
+        status,
+        original_status_len + event_segment_indices_vec.len(),
+        event.position(),
+    )?;
 
-    (
+    Ok((
         later_intersection_events,
         if start_points_are_intersections || !start_intersection_segments.is_empty() {
             Some(IntersectionPoint {
@@ -409,14 +525,14 @@ fn update_status_with_start_point(
         } else {
             None
         },
-    )
+    ))
 }
 
 fn update_status_with_intersection_point(
     event: EventPoint,
     status: &mut SkipList,
     segments: &[Segment],
-) -> (Vec<EventPoint>, Option<IntersectionPoint>) {
+) -> Result<(Vec<EventPoint>, Option<IntersectionPoint>), GeometryError> {
     let original_status_len = status.len();
 
     let event_segment_indices_vec: Vec<usize> = event.segment_indices().iter().copied().collect();
@@ -470,15 +586,15 @@ fn update_status_with_intersection_point(
         });
     }
 
-    assert_eq!(status.len(), original_status_len);
+    check_status_size(status, original_status_len, event.position())?;
 
-    (
+    Ok((
         new_events,
         Some(IntersectionPoint {
             position: event.position(),
             intersecting_segment_indices: event_segment_indices_vec,
         }),
-    )
+    ))
 }
 
 fn update_status_with_end_point(
@@ -487,7 +603,7 @@ fn update_status_with_end_point(
     segments: &[Segment],
     potential_last_intersection: Option<&mut IntersectionPoint>,
     mut end_points_are_intersections: bool,
-) -> (Vec<EventPoint>, Option<IntersectionPoint>) {
+) -> Result<(Vec<EventPoint>, Option<IntersectionPoint>), GeometryError> {
     let original_status_len = status.len();
 
     let event_segment_indices_vec: Vec<usize> = event.segment_indices().iter().copied().collect();
@@ -566,11 +682,12 @@ fn update_status_with_end_point(
         end_intersection_segments.extend(sorted_segment_indices);
     }
 
-    assert_eq!(
-        status.len(),
-        original_status_len - event_segment_indices_vec.len()
-    );
-    (
+    check_status_size(
+        status,
+        original_status_len.saturating_sub(event_segment_indices_vec.len()),
+        event.position(),
+    )?;
+    Ok((
         potential_lower_intersection_event,
         if end_points_are_intersections || !end_intersection_segments.is_empty() {
             Some(IntersectionPoint {
@@ -580,21 +697,12 @@ fn update_status_with_end_point(
         } else {
             None
         },
-    )
+    ))
 }
 
 fn calc_intersection_point(segment_0: Segment, segment_1: Segment) -> Option<Point> {
-    let p = segment_0[0];
-    let q = segment_1[0];
-    let r = segment_0[1];
-    let s = segment_1[1];
-
-    let denominator = cross_2d(s - q, r - p);
-
-    let u = cross_2d(p - q, s - q) / denominator;
-    let t = cross_2d(p - q, r - p) / denominator;
-    if (u >= 0.0 && u <= 1.0) && (t >= 0.0 && t <= 1.0) {
-        Some(p + u * (r - p))
+    if segments_intersect(segment_0, segment_1) {
+        line_intersection(segment_0, segment_1)
     } else {
         None
     }
@@ -605,22 +713,11 @@ fn cross_2d(p_0: Point, p_1: Point) -> f32 {
 }
 
 fn calc_intersection_point_unbounded(segment_0: Segment, segment_1: Segment) -> Point {
-    let p = segment_0[0];
-    let q = segment_1[0];
-    let r = segment_0[1];
-    let s = segment_1[1];
-
-    let denominator = cross_2d(s - q, r - p);
-    if denominator == 0.0 {
-        if segment_0[1] == segment_1[0] {
-            segment_0[1]
-        } else {
-            segment_0[0]
-        }
+    line_intersection(segment_0, segment_1).unwrap_or(if segment_0[1] == segment_1[0] {
+        segment_0[1]
     } else {
-        let u = cross_2d(p - q, s - q) / denominator;
-        p + u * (r - p)
-    }
+        segment_0[0]
+    })
 }
 
 pub fn points_are_close(p_1: Point, p_2: Point) -> bool {
@@ -630,7 +727,23 @@ pub fn points_are_close(p_1: Point, p_2: Point) -> bool {
     (p_1 - p_2).norm_squared() < 0.0001
 }
 
-pub fn path_to_graph(paths: &[HermiteCurve], min_face_area: f32) -> Vec<Vec<Point>> {
+/// Turns the street curves into blocks. Only a failure to build the street graph itself is
+/// returned as an error, faces that break while being processed are skipped and listed in
+/// `Blocks::failures` instead.
+pub fn path_to_graph(
+    paths: &[HermiteCurve],
+    block_config: &BlockConfig,
+) -> Result<Blocks, GeometryError> {
+    Ok(path_to_selected_blocks(paths, block_config, |_| true)?.0)
+}
+
+/// Like `path_to_graph`, but only runs block extraction on the street faces `select` picks.
+/// The picked faces are returned alongside the blocks.
+pub(crate) fn path_to_selected_blocks(
+    paths: &[HermiteCurve],
+    block_config: &BlockConfig,
+    select: impl Fn(&[Point]) -> bool,
+) -> Result<(Blocks, Vec<Vec<Point>>), GeometryError> {
     let all_segment_points = paths.iter().map(|curve| {
         curve
             .into_iter()
@@ -638,7 +751,7 @@ pub fn path_to_graph(paths: &[HermiteCurve], min_face_area: f32) -> Vec<Vec<Poin
             .collect::<Vec<_>>()
     });
 
-    let mut segments: Vec<[Point; 2]> = all_segment_points
+    let segments: Vec<[Point; 2]> = all_segment_points
         .flat_map(|curve| {
             curve[..curve.len() - 1]
                 .into_iter()
@@ -648,57 +761,122 @@ pub fn path_to_graph(paths: &[HermiteCurve], min_face_area: f32) -> Vec<Vec<Poin
         })
         .collect();
 
-    let (vertices, adjacency_list) = segments_to_adjacency_list(&mut segments);
+    let (vertices, adjacency_list) = segments_to_adjacency_list(&segments)?;
 
     let dcel = DCEL::new(&vertices, &adjacency_list);
 
-    let new_faces: Vec<Vec<Point>> = dcel
-        .faces()
-        .iter()
-        .flat_map(|face_from_indices| {
-            process_raw_block_verts(
-                face_from_indices
-                    .iter()
-                    .map(|index| vertices[*index])
-                    .collect(),
-                min_face_area,
-            )
-        })
-        .flat_map(|face| {
-            let merged_face = merge_near_points(face, 1.0);
-            if merged_face.len() > 2 {
-                Some(merged_face)
-            } else {
-                None
-            }
-        })
-        .collect();
+    let mut blocks = Blocks::default();
+    let mut selected_faces = Vec::new();
+
+    for face_from_indices in dcel.faces() {
+        let face: Vec<Point> = face_from_indices
+            .iter()
+            .map(|index| vertices[*index])
+            .collect();
+        if !select(&face) {
+            continue;
+        }
+        selected_faces.push(face.clone());
+
+        let mut passes = Vec::new();
+        let result = extract_blocks(
+            face.clone(),
+            block_config,
+            block_config.trace_passes.then_some(&mut passes),
+        );
+        if block_config.trace_passes {
+            blocks.traces.push(FaceTrace {
+                face: face.clone(),
+                passes,
+                error: result.as_ref().err().cloned(),
+            });
+        }
+
+        match result {
+            Ok(new_faces) => blocks.faces.extend(new_faces),
+            Err(error) => blocks.failures.push(FaceFailure { face, error }),
+        }
+    }
 
-    new_faces
+    Ok((blocks, selected_faces))
+}
+
+/// Runs block extraction on a single street face, keeping its polygons after every pass
+pub(crate) fn trace_face(face: Vec<Point>, block_config: &BlockConfig) -> FaceTrace {
+    let mut passes = Vec::new();
+    let error = extract_blocks(face.clone(), block_config, Some(&mut passes)).err();
+    FaceTrace {
+        face,
+        passes,
+        error,
+    }
+}
+
+fn extract_blocks(
+    face: Vec<Point>,
+    block_config: &BlockConfig,
+    mut trace: Option<&mut Vec<PassSnapshot>>,
+) -> Result<Vec<Vec<Point>>, GeometryError> {
+    let merged_faces: Vec<Vec<Point>> = process_raw_block_verts(face, block_config, &mut trace)?
         .into_iter()
-        .flat_map(|face| subdivide_face(face, min_face_area))
-        .collect()
+        .map(|face| merge_near_points(face, block_config.merge_distance))
+        .filter(|merged_face| merged_face.len() > 2)
+        .collect();
+    record_pass(&mut trace, BlockPass::MergedPoints, &merged_faces);
+    Ok(merged_faces)
+}
+
+fn record_pass(
+    trace: &mut Option<&mut Vec<PassSnapshot>>,
+    pass: BlockPass,
+    polygons: &[Vec<Point>],
+) {
+    if let Some(trace) = trace {
+        trace.push(PassSnapshot {
+            pass,
+            polygons: polygons.to_vec(),
+        });
+    }
 }
 
 type AdjacencyList = HashMap<usize, HashSet<usize>>;
 
-fn segments_to_adjacency_list(segments: &mut [Segment]) -> (Vec<Point>, AdjacencyList) {
+fn segments_to_adjacency_list(
+    segments: &[Segment],
+) -> Result<(Vec<Point>, AdjacencyList), GeometryError> {
+    // Snapping first means the sweep only ever sees points that are either exactly shared or
+    // clearly apart
+    let mut snapped_segments: Vec<Segment> = snap_round(segments)
+        .into_iter()
+        .map(|fragment| fragment.segment)
+        .collect();
+
     let intersections: HashSet<IntersectionPoint> =
-        HashSet::from_iter(find_interesctions(&segments, true));
+        HashSet::from_iter(find_interesctions(&snapped_segments, true)?);
 
     let mut intersections_vec: Vec<IntersectionPoint> = intersections.iter().cloned().collect();
 
-    let new_segments = split_segments_at_intersections(&mut intersections_vec, segments);
+    let new_segments =
+        split_segments_at_intersections(&mut intersections_vec, &mut snapped_segments);
 
-    let all_segments: Vec<Segment> = segments.to_vec().into_iter().chain(new_segments).collect();
+    let all_segments: Vec<Segment> = snapped_segments.into_iter().chain(new_segments).collect();
 
     vertices_to_adjacency_list(intersections_vec, &all_segments)
 }
 
-pub fn split_segments_at_intersections(
+pub(crate) fn split_segments_at_intersections(
     all_intersections: &mut [IntersectionPoint],
     segments: &mut [Segment],
 ) -> Vec<Segment> {
+    split_segments_at_intersections_with_parents(all_intersections, segments).0
+}
+
+/// Same as `split_segments_at_intersections`, but also returns the index of the segment each of
+/// the new segments was split off from
+pub(crate) fn split_segments_at_intersections_with_parents(
+    all_intersections: &mut [IntersectionPoint],
+    segments: &mut [Segment],
+) -> (Vec<Segment>, Vec<usize>) {
     let which_intersections_are_on_which_segments =
         list_segments_and_the_points_that_intersect_them(&all_intersections, segments);
 
@@ -713,6 +891,7 @@ pub fn split_segments_at_intersections(
     });
 
     let mut new_segments = Vec::new();
+    let mut parents = Vec::new();
     let mut new_segment_index = segments.len();
 
     for (segment_index, (segment_start_point_index, intersection_indices_on_segment)) in
@@ -798,6 +977,7 @@ pub fn split_segments_at_intersections(
             let previous_position = all_intersections[previous_intersection_index].position();
             let current_position = all_intersections[intersection_index].position();
             new_segments.push([previous_position, current_position]);
+            parents.push(segment_index);
             all_intersections[previous_intersection_index]
                 .intersecting_segment_indices
                 .push(new_segment_index);
@@ -808,13 +988,13 @@ pub fn split_segments_at_intersections(
         }
     }
 
-    new_segments
+    (new_segments, parents)
 }
 
 fn vertices_to_adjacency_list(
     vertices: Vec<IntersectionPoint>,
     segments: &[Segment],
-) -> (Vec<Point>, AdjacencyList) {
+) -> Result<(Vec<Point>, AdjacencyList), GeometryError> {
     let inverse_vertices: HashMap<IntersectionPoint, usize> =
         HashMap::from_iter(vertices.iter().enumerate().map(
             |(
@@ -834,7 +1014,20 @@ fn vertices_to_adjacency_list(
             },
         ));
 
-    assert_eq!(inverse_vertices.len(), vertices.len());
+    if inverse_vertices.len() != vertices.len() {
+        let mut seen_positions = HashSet::new();
+        let duplicate = vertices
+            .iter()
+            .find(|vertex| {
+                let truncated = truncate_point_to_decimal_place(vertex.position, 3);
+                !seen_positions.insert((OrderedFloat(truncated.x), OrderedFloat(truncated.y)))
+            })
+            .map(IntersectionPoint::position)
+            .unwrap_or_default();
+        return Err(GeometryError::DuplicateVertex {
+            position: duplicate,
+        });
+    }
 
     let adjacency_list = vertices
         .iter()
@@ -846,26 +1039,27 @@ fn vertices_to_adjacency_list(
                 .flat_map(|&segment_index| segments[segment_index]);
             let all_conneced_vertices = all_connected_vertex_indices
                 .map(|vertex| {
-                    *inverse_vertices
+                    inverse_vertices
                         .get(&IntersectionPoint {
                             position: truncate_point_to_decimal_place(vertex, 3),
                             intersecting_segment_indices: Vec::new(),
                         })
-                        .expect(&format!("No inverse for {vertex:?}"))
+                        .copied()
+                        .ok_or(GeometryError::MissingVertex { position: vertex })
                 })
-                .filter(|&vertex_index| vertex_index != i)
-                .collect();
-            (i, all_conneced_vertices)
+                .filter(|vertex_index| *vertex_index != Ok(i))
+                .collect::<Result<HashSet<usize>, GeometryError>>()?;
+            Ok((i, all_conneced_vertices))
         })
-        .collect();
+        .collect::<Result<AdjacencyList, GeometryError>>()?;
 
-    (
+    Ok((
         vertices
             .into_iter()
             .map(|IntersectionPoint { position, .. }| position)
             .collect(),
         adjacency_list,
-    )
+    ))
 }
 
 fn truncate_point_to_decimal_place(point: Point, decimal_places: u32) -> Point {
@@ -904,37 +1098,50 @@ fn list_segments_and_the_points_that_intersect_them(
     points_on_each_segment
 }
 
-fn process_raw_block_verts(face: Vec<Point>, min_block_area: f32) -> Vec<Vec<Point>> {
+fn process_raw_block_verts(
+    face: Vec<Point>,
+    block_config: &BlockConfig,
+    trace: &mut Option<&mut Vec<PassSnapshot>>,
+) -> Result<Vec<Vec<Point>>, GeometryError> {
     let (mut full_face, mut adjacency_list) = verts_to_adjacency_list(&face);
 
-    let corrected_faces = correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list);
+    let corrected_faces = correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list)?;
+    record_pass(trace, BlockPass::DegeneratePoints, &corrected_faces);
 
     let flattened_faces: Vec<Vec<Point>> = corrected_faces
         .into_iter()
         .map(|face| {
             let (face, face_adjacency_list) = verts_to_adjacency_list(&face);
             let (_, concave_vert_indices) = detect_convex_and_concave_vertices(&face);
-            flatten_face(face, face_adjacency_list, 0.98, 0.07, concave_vert_indices)
+            flatten_face(
+                face,
+                face_adjacency_list,
+                block_config.flatten_threshold,
+                block_config.area_difference_threshold,
+                concave_vert_indices,
+            )
         })
         .collect();
+    record_pass(trace, BlockPass::Flattened, &flattened_faces);
 
     let scaled_faces: Vec<Vec<Point>> = flattened_faces
         .into_iter()
-        .flat_map(|face| {
-            let new_face = scale_face(face, 1.0);
-            if face_area(&new_face) > min_block_area {
-                Some(fix_non_manifold_face(merge_near_points(new_face, 0.1)))
-            } else {
-                None
-            }
-        })
+        .map(|face| scale_face(face, block_config.inset_distance))
+        .filter(|new_face| face_area(new_face) > block_config.min_face_area)
         .collect();
+    record_pass(trace, BlockPass::Scaled, &scaled_faces);
+
+    let manifold_faces: Vec<Vec<Point>> = scaled_faces
+        .into_iter()
+        .map(|face| fix_non_manifold_face(merge_near_points(face, 0.1)))
+        .collect::<Result<_, _>>()?;
+    record_pass(trace, BlockPass::NonManifoldFixed, &manifold_faces);
 
-    let split_faces: Vec<Vec<Point>> = scaled_faces
+    let split_faces: Vec<Vec<Vec<Point>>> = manifold_faces
         .into_iter()
-        .flat_map(|face| {
+        .map(|face| {
             let (face, mut adjacency_list) = verts_to_adjacency_list(&face);
-            let vertices_of_new_faces = split_face_at_concave_vertices(face, &mut adjacency_list);
+            let vertices_of_new_faces = split_face_at_concave_vertices(face, &mut adjacency_list)?;
             let all_new_faces: Vec<Vec<Point>> = DCEL::new(&vertices_of_new_faces, &adjacency_list)
                 .faces()
                 .iter()
@@ -944,11 +1151,13 @@ fn process_raw_block_verts(face: Vec<Point>, min_block_area: f32) -> Vec<Vec<Poi
                         .collect::<Vec<_>>()
                 })
                 .collect();
-            all_new_faces
+            Ok(all_new_faces)
         })
-        .collect();
+        .collect::<Result<_, GeometryError>>()?;
 
-    split_faces
+    let split_faces: Vec<Vec<Point>> = split_faces.into_iter().flatten().collect();
+    record_pass(trace, BlockPass::Split, &split_faces);
+    Ok(split_faces)
 }
 
 fn verts_to_adjacency_list(face: &[Point]) -> (Vec<Point>, AdjacencyList) {
@@ -1018,7 +1227,7 @@ fn verts_to_adjacency_list(face: &[Point]) -> (Vec<Point>, AdjacencyList) {
 fn correct_face_with_degenerate_points(
     full_face: &mut Vec<Point>,
     adjacency_list: &mut AdjacencyList,
-) -> Vec<Vec<Point>> {
+) -> Result<Vec<Vec<Point>>, GeometryError> {
     let mut degenerate_points_indices: Vec<usize> = (0..full_face.len())
         .filter(|i| adjacency_list[i].len() == 1)
         .collect();
@@ -1062,11 +1271,9 @@ fn correct_face_with_degenerate_points(
             .map(|&(p_0, p_1)| [full_face[p_0], full_face[p_1]])
             .collect();
 
-        let mut raycast_points = raycast_through_segments(
-            degenerate_point,
-            degenerate_point - degenerate_previous_point,
-            &segments,
-        );
+        let raycast_direction = degenerate_point - degenerate_previous_point;
+        let mut raycast_points =
+            raycast_through_segments(degenerate_point, raycast_direction, &segments);
 
         raycast_points.sort_by(|a, b| {
             (degenerate_point - a.0)
@@ -1074,7 +1281,13 @@ fn correct_face_with_degenerate_points(
                 .total_cmp(&(degenerate_point - b.0).norm_squared())
         });
 
-        let (raycast_intersection, intersecting_segment_index) = raycast_points[0];
+        let Some(&(raycast_intersection, intersecting_segment_index)) = raycast_points.first()
+        else {
+            return Err(GeometryError::RaycastMissed {
+                origin: degenerate_point,
+                direction: raycast_direction,
+            });
+        };
 
         let new_point_index = full_face.len();
         let (left_intersection_index, right_intersection_index) =
@@ -1105,7 +1318,8 @@ fn correct_face_with_degenerate_points(
 
     let dcel = DCEL::new(&full_face, adjacency_list);
 
-    dcel.faces()
+    Ok(dcel
+        .faces()
         .iter()
         .map(|current_face| {
             current_face
@@ -1113,7 +1327,7 @@ fn correct_face_with_degenerate_points(
                 .map(|index| full_face[*index])
                 .collect::<Vec<Point>>()
         })
-        .collect()
+        .collect())
 }
 
 fn raycast_through_segments(
@@ -1150,7 +1364,7 @@ fn raycast_through_segments(
         .collect()
 }
 
-fn scale_face(face: Vec<Point>, translation_distance: f32) -> Vec<Point> {
+pub(crate) fn scale_face(face: Vec<Point>, translation_distance: f32) -> Vec<Point> {
     let translated_edges: Vec<Segment> = (0..face.len())
         .map(|i| {
             let p_0 = face[i];
@@ -1183,11 +1397,11 @@ fn scale_face(face: Vec<Point>, translation_distance: f32) -> Vec<Point> {
         .collect()
 }
 
-fn fix_non_manifold_face(face: Vec<Point>) -> Vec<Point> {
-    let mut segments: Vec<Segment> = (0..face.len())
+fn fix_non_manifold_face(face: Vec<Point>) -> Result<Vec<Point>, GeometryError> {
+    let segments: Vec<Segment> = (0..face.len())
         .map(|i| [face[i], face[(i + 1) % face.len()]])
         .collect();
-    let (vertices, adjacency_list) = segments_to_adjacency_list(&mut segments);
+    let (vertices, adjacency_list) = segments_to_adjacency_list(&segments)?;
 
     let dcel = DCEL::new(&vertices, &adjacency_list);
 
@@ -1199,7 +1413,7 @@ fn fix_non_manifold_face(face: Vec<Point>) -> Vec<Point> {
                 .collect::<Vec<_>>()
         })
         .max_by(|a, b| face_area(a).total_cmp(&face_area(b)))
-        .unwrap()
+        .ok_or(GeometryError::NoFaces)
 }
 
 fn detect_convex_and_concave_vertices(face: &[Point]) -> (Vec<usize>, Vec<usize>) {
@@ -1277,7 +1491,7 @@ fn flatten_face(
     flattened_face
 }
 
-fn face_area(face: &[Point]) -> f32 {
+pub(crate) fn face_area(face: &[Point]) -> f32 {
     (0..face.len())
         .map(|i| Matrix2::from_columns(&[face[i], face[(i + 1) % face.len()]]).determinant())
         .sum::<f32>()
@@ -1293,27 +1507,31 @@ fn merge_near_points(verts: Vec<Point>, merge_distance: f32) -> Vec<Point> {
     }
 
     let mut merged_verts = Vec::new();
+    let mut merged_count = 0;
 
     let mut group_start_index = start_index;
     loop {
         let group_end_index =
             get_index_of_next_far_point(&verts, merge_distance_squared, group_start_index);
 
-        if group_end_index == group_start_index + 1 {
+        let count = if group_end_index > group_start_index {
+            group_end_index - group_start_index
+        } else {
+            verts.len() - group_start_index + group_end_index
+        };
+        if count == 1 {
             merged_verts.push(verts[group_start_index]);
         } else {
-            let count = if group_end_index > group_start_index {
-                group_end_index - group_start_index
-            } else {
-                verts.len() - group_start_index + group_end_index
-            };
             let merged_center = (group_start_index..(group_start_index + count))
                 .fold(Point::zeros(), |acc, idx| acc + verts[idx % verts.len()])
                 / count as f32;
             merged_verts.push(merged_center);
         }
+        merged_count += count;
 
-        if group_end_index == start_index {
+        // A group can run past the start when the vertices around it are all close together, in
+        // which case the end never lands on it exactly
+        if group_end_index == start_index || merged_count >= verts.len() {
             break;
         }
 
@@ -1340,7 +1558,7 @@ fn get_index_of_next_far_point(
 fn split_face_at_concave_vertices(
     face: Vec<Point>,
     adjacency_list: &mut AdjacencyList,
-) -> Vec<Point> {
+) -> Result<Vec<Point>, GeometryError> {
     let (_, concave_indices) = detect_convex_and_concave_vertices(&face);
 
     let mut full_face = face.clone();
@@ -1383,18 +1601,16 @@ fn split_face_at_concave_vertices(
         let all_raycast_points =
             raycast_through_segments(concave_vert, raycast_dir, &filtered_segments);
 
-        if all_raycast_points.is_empty() {
-            println!(
-                "{:?} | {:?} - Points: {:?}",
-                (concave_vert.x, concave_vert.y),
-                (raycast_dir.x, raycast_dir.y),
-                face.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>(),
-            );
-        }
+        let Some(&first_raycast_point) = all_raycast_points.first() else {
+            return Err(GeometryError::RaycastMissed {
+                origin: concave_vert,
+                direction: raycast_dir,
+            });
+        };
         let (raycast_res, intersecting_segment_index) =
             all_raycast_points
                 .iter()
-                .fold(all_raycast_points[0], |acc, val| {
+                .fold(first_raycast_point, |acc, val| {
                     if (concave_vert - val.0).norm_squared() < (concave_vert - acc.0).norm_squared()
                     {
                         *val
@@ -1424,56 +1640,7 @@ fn split_face_at_concave_vertices(
             .insert(new_point_index);
     }
 
-    full_face
-}
-
-fn subdivide_face(face: Vec<Point>, min_face_area: f32) -> Vec<Vec<Point>> {
-    if face_area(&face) < min_face_area {
-        return vec![face];
-    }
-
-    let mut segment_indices: Vec<(usize, usize)> =
-        (0..face.len()).map(|i| (i, (i + 1) % face.len())).collect();
-    segment_indices.sort_by(|a, b| {
-        let a_length_squared = (face[a.1] - face[a.0]).norm_squared();
-        let b_length_squared = (face[b.1] - face[b.0]).norm_squared();
-
-        a_length_squared.total_cmp(&b_length_squared).reverse()
-    });
-
-    let (first_subdivision_segment, second_subdivision_segment) =
-        if segment_indices[0].0 < segment_indices[1].0 {
-            (segment_indices[0], segment_indices[1])
-        } else {
-            (segment_indices[1], segment_indices[0])
-        };
-
-    let first_subdivision_midpoint =
-        (face[first_subdivision_segment.0] + face[first_subdivision_segment.1]) / 2.0;
-    let second_subdivision_midpoint =
-        (face[second_subdivision_segment.0] + face[second_subdivision_segment.1]) / 2.0;
-
-    let first_face_subdivision: Vec<Point> = iter::once(first_subdivision_midpoint)
-        .chain(face[first_subdivision_segment.1..=second_subdivision_segment.0].to_vec())
-        .chain(iter::once(second_subdivision_midpoint))
-        .collect();
-
-    let second_face_subdivision: Vec<Point> = face[0..=first_subdivision_segment.0]
-        .to_vec()
-        .into_iter()
-        .chain(vec![
-            first_subdivision_midpoint,
-            second_subdivision_midpoint,
-        ])
-        .chain(face[(second_subdivision_segment.0 + 1)..face.len()].to_vec())
-        .collect();
-
-    // vec![first_face_subdivision, second_face_subdivision]
-
-    subdivide_face(first_face_subdivision, min_face_area)
-    .into_iter()
-    .chain(subdivide_face(second_face_subdivision, min_face_area))
-    .collect()
+    Ok(full_face)
 }
 
 #[cfg(test)]
@@ -1482,10 +1649,11 @@ mod test {
     use ordered_float::OrderedFloat;
 
     use crate::{
+        config::BlockConfig,
         street_graph::{
             AdjacencyList, calc_intersection_point_unbounded, detect_convex_and_concave_vertices,
             fix_non_manifold_face, path_to_graph, points_are_close, scale_face, segment_end,
-            segment_start, sort_joint_segments, subdivide_face, truncate_point_to_decimal_place,
+            segment_start, sort_joint_segments, truncate_point_to_decimal_place,
             verts_to_adjacency_list,
         },
         street_plan::ControlPoint,
@@ -1495,10 +1663,13 @@ mod test {
         iter,
     };
 
-    use crate::{event_queue::EventQueue, street_graph::IntersectionPoint, tensor_field::Point};
+    use crate::{
+        event_queue::EventQueue, snap_rounding::snap_point, street_graph::IntersectionPoint,
+        tensor_field::Point,
+    };
 
     use super::{
-        EventPoint, EventPointType, Segment, SkipList, calc_intersection_point,
+        EventPoint, EventPointType, GeometryError, Segment, SkipList, calc_intersection_point,
         correct_face_with_degenerate_points, face_area, find_interesctions, flatten_face,
         merge_near_points, segments_to_adjacency_list, split_face_at_concave_vertices,
         split_segments_at_intersections, update_status, vertices_to_adjacency_list,
@@ -1723,7 +1894,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             new_events.extend(result_events.into_iter());
             if let Some(intersection) = result_intersection {
@@ -1763,7 +1934,7 @@ mod test {
             };
 
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             new_events.extend(result_events.into_iter());
             if let Some(intersection) = result_intersection {
@@ -1812,7 +1983,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             new_events.extend(result_events.into_iter());
             if let Some(intersection) = result_intersection {
@@ -1868,7 +2039,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             new_events.extend(result_events.into_iter());
             if let Some(intersection) = result_intersection {
@@ -1923,7 +2094,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             new_events.extend(result_events.into_iter());
             if let Some(intersection) = result_intersection {
@@ -1972,7 +2143,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             new_events.extend(result_events.into_iter());
             if let Some(intersection) = result_intersection {
@@ -2015,7 +2186,8 @@ mod test {
                 &segments,
                 new_intersections.last_mut(),
                 false,
-            );
+            )
+            .unwrap();
 
             new_events.extend(result_events.into_iter());
             if let Some(intersection) = result_intersection {
@@ -2051,7 +2223,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             for event in result_events {
                 new_events.push(event);
@@ -2072,7 +2244,8 @@ mod test {
             &segments,
             None,
             false,
-        );
+        )
+        .unwrap();
         assert!(result_events.is_empty());
 
         assert!(result_intersection.is_some(),);
@@ -2105,7 +2278,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             for event in result_events {
                 new_events.push(event);
@@ -2124,7 +2297,8 @@ mod test {
             &segments,
             None,
             false,
-        );
+        )
+        .unwrap();
         assert!(result_events.is_empty());
 
         assert!(result_intersection.is_some(),);
@@ -2160,7 +2334,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             for event in result_events {
                 new_events.push(event);
@@ -2181,7 +2355,8 @@ mod test {
             &segments,
             None,
             false,
-        );
+        )
+        .unwrap();
         assert!(result_events.is_empty());
 
         assert!(result_intersection.is_some(),);
@@ -2215,7 +2390,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event.clone(), &segments, None, false);
+                update_status(&mut status, event.clone(), &segments, None, false).unwrap();
 
             for event in result_events {
                 new_events.push(event);
@@ -2236,7 +2411,8 @@ mod test {
             &segments,
             None,
             false,
-        );
+        )
+        .unwrap();
         assert!(result_events.is_empty());
 
         assert!(result_intersection.is_some(),);
@@ -2269,7 +2445,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             for event in result_events {
                 new_events.push(event);
@@ -2290,7 +2466,8 @@ mod test {
             &segments,
             None,
             false,
-        );
+        )
+        .unwrap();
         assert_eq!(result_events.len(), 1);
         assert!(points_are_close(
             result_events[0].position(),
@@ -2334,7 +2511,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             for event in result_events {
                 new_events.push(event);
@@ -2355,7 +2532,8 @@ mod test {
             &segments,
             None,
             false,
-        );
+        )
+        .unwrap();
         assert_eq!(result_events.len(), 1);
         assert!(points_are_close(
             result_events[0].position(),
@@ -2399,7 +2577,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             for event in result_events {
                 new_events.push(event);
@@ -2420,7 +2598,8 @@ mod test {
             &segments,
             None,
             false,
-        );
+        )
+        .unwrap();
         assert_eq!(result_events.len(), 2);
         assert!(points_are_close(
             result_events[0].position(),
@@ -2476,7 +2655,7 @@ mod test {
 
         while let Some(event) = event_queue.pop() {
             let (result_events, result_intersection) =
-                update_status(&mut status, event, &segments, None, false);
+                update_status(&mut status, event, &segments, None, false).unwrap();
 
             for event in result_events {
                 new_events.push(event);
@@ -2497,7 +2676,8 @@ mod test {
             &segments,
             None,
             false,
-        );
+        )
+        .unwrap();
         assert_eq!(result_events.len(), 2);
         assert!(points_are_close(
             result_events[0].position(),
@@ -2543,7 +2723,7 @@ mod test {
         let mut status = SkipList::new();
 
         while let Some(event) = event_queue.pop() {
-            let _ = update_status(&mut status, event.clone(), &segments, None, false);
+            let _ = update_status(&mut status, event.clone(), &segments, None, false).unwrap();
         }
 
         assert_eq!(status.len(), 0);
@@ -2568,7 +2748,8 @@ mod test {
                 &segments,
                 all_new_intersections.last_mut(),
                 false,
-            );
+            )
+            .unwrap();
             all_new_intersections.extend(potential_new_intersection);
         }
 
@@ -2597,7 +2778,8 @@ mod test {
         let mut new_event_points = Vec::new();
 
         while let Some(event) = event_queue.pop() {
-            let (new_events, _) = update_status(&mut status, event.clone(), &segments, None, false);
+            let (new_events, _) =
+                update_status(&mut status, event.clone(), &segments, None, false).unwrap();
             new_event_points.extend(new_events.clone());
             for event in new_events {
                 event_queue.push(event);
@@ -2646,7 +2828,8 @@ mod test {
                 &segments,
                 all_new_intersections.last_mut(),
                 false,
-            );
+            )
+            .unwrap();
             all_new_event_points.extend(new_events.clone());
             all_new_intersections.extend(new_intersections);
             for new_event in new_events {
@@ -2703,7 +2886,8 @@ mod test {
                 &segments,
                 all_new_intersections.last_mut(),
                 false,
-            );
+            )
+            .unwrap();
             all_new_event_points.extend(new_events.clone());
             all_new_intersections.extend(new_intersections);
             for new_event in new_events {
@@ -2744,7 +2928,8 @@ mod test {
                 &segments,
                 all_new_intersections.last_mut(),
                 false,
-            );
+            )
+            .unwrap();
             all_new_event_points.extend(new_events.clone());
             all_new_intersections.extend(new_intersections);
             for new_event in new_events {
@@ -2785,7 +2970,8 @@ mod test {
                 &segments,
                 all_new_intersections.last_mut(),
                 false,
-            );
+            )
+            .unwrap();
             all_new_event_points.extend(new_events.clone());
             all_new_intersections.extend(new_intersections);
             for new_event in new_events {
@@ -2827,7 +3013,8 @@ mod test {
                 &segments,
                 all_new_intersections.last_mut(),
                 false,
-            );
+            )
+            .unwrap();
             all_new_event_points.extend(new_events.clone());
             all_new_intersections.extend(new_intersections);
             for new_event in new_events {
@@ -2873,7 +3060,8 @@ mod test {
                 &segments,
                 all_new_intersections.last_mut(),
                 false,
-            );
+            )
+            .unwrap();
             all_new_event_points.extend(new_events.clone());
             all_new_intersections.extend(new_intersections);
             for new_event in new_events {
@@ -2923,7 +3111,8 @@ mod test {
                 &segments,
                 all_new_intersections.last_mut(),
                 false,
-            );
+            )
+            .unwrap();
             all_new_event_points.extend(new_events.clone());
             all_new_intersections.extend(new_intersections);
             for new_event in new_events {
@@ -2960,7 +3149,7 @@ mod test {
             ],
         ];
 
-        let intersections = find_interesctions(&segments, false);
+        let intersections = find_interesctions(&segments, false).unwrap();
 
         assert_eq!(intersections.len(), 1);
         assert!(points_are_close(
@@ -2977,7 +3166,7 @@ mod test {
             [Point::new(4.0, 2.0), Point::new(4.0, 4.0)],
         ];
 
-        let intersections = find_interesctions(&segments, false);
+        let intersections = find_interesctions(&segments, false).unwrap();
 
         assert_eq!(intersections.len(), 2);
         assert!(intersections[0].position() == Point::new(2.0, 4.0));
@@ -2993,7 +3182,7 @@ mod test {
             [Point::new(4.0, 2.0), Point::new(4.0, 4.0)],
         ];
 
-        let intersections = find_interesctions(&segments, false);
+        let intersections = find_interesctions(&segments, false).unwrap();
 
         let mut all_intersections_set =
             HashSet::<IntersectionPoint>::from_iter(intersections.clone());
@@ -3034,7 +3223,7 @@ mod test {
             [Point::new(6.0, 2.0), Point::new(0.0, 1.0)],
         ];
 
-        let intersections = find_interesctions(&segments, false);
+        let intersections = find_interesctions(&segments, false).unwrap();
 
         let mut all_intersections_set =
             HashSet::<IntersectionPoint>::from_iter(intersections.clone());
@@ -3090,7 +3279,7 @@ mod test {
             [Point::new(4.0, 2.0), Point::new(4.0, 4.0)],
         ];
 
-        let intersections = find_interesctions(&segments, true);
+        let intersections = find_interesctions(&segments, true).unwrap();
 
         let mut all_intersections_set =
             HashSet::<IntersectionPoint>::from_iter(intersections.clone());
@@ -3114,7 +3303,7 @@ mod test {
         let all_segments: Vec<Segment> = segments.into_iter().chain(new_segments).collect();
 
         let (vertices, adjacency_list) =
-            vertices_to_adjacency_list(all_intersections, &all_segments);
+            vertices_to_adjacency_list(all_intersections, &all_segments).unwrap();
 
         let expected_vertices = [
             Point::new(0.0, 0.0),
@@ -3165,9 +3354,35 @@ mod test {
         }
     }
 
+    #[test]
+    fn nearly_shared_points_become_one_vertex() {
+        let segments = vec![
+            [Point::new(0.0, 0.0), Point::new(10.0, 10.0)],
+            [Point::new(10.0003, 9.9998), Point::new(20.0, 0.0)],
+            [Point::new(10.0, 10.0002), Point::new(10.0, 20.0)],
+            [Point::new(5.0, 10.0004), Point::new(15.0, 10.0004)],
+        ];
+
+        let (vertices, adjacency_list) = segments_to_adjacency_list(&segments).unwrap();
+
+        assert_eq!(vertices.len(), 6);
+
+        let junction = vertices
+            .iter()
+            .position(|vertex| *vertex == Point::new(10.0, 10.0))
+            .unwrap();
+        assert_eq!(adjacency_list[&junction].len(), 5);
+
+        for (vertex, neighbors) in &adjacency_list {
+            for neighbor in neighbors {
+                assert!(adjacency_list[neighbor].contains(vertex));
+            }
+        }
+    }
+
     #[test]
     fn straight_line_to_adjacency_list() {
-        let mut segments = vec![
+        let segments = vec![
             [
                 Point::new(466.0258, 498.15332),
                 Point::new(469.41873, 464.32245),
@@ -3190,7 +3405,7 @@ mod test {
             ],
         ];
 
-        let (vertices, adjacency_list) = segments_to_adjacency_list(&mut segments);
+        let (vertices, adjacency_list) = segments_to_adjacency_list(&segments).unwrap();
 
         let expected_vertices = vec![
             Point::new(466.0258, 498.15332),
@@ -3199,7 +3414,10 @@ mod test {
             Point::new(473.41043, 424.52142),
             Point::new(475.40628, 404.6209),
             Point::new(477.3223, 385.51642),
-        ];
+        ]
+        .into_iter()
+        .map(snap_point)
+        .collect::<Vec<_>>();
 
         let expected_adjacency_list: AdjacencyList = HashMap::from_iter(vec![
             (0, HashSet::from_iter(vec![1])),
@@ -3279,9 +3497,34 @@ mod test {
             },
         ];
 
-        let faces = path_to_graph(&[curve], 10.0);
+        let faces = path_to_graph(
+            &[curve],
+            &BlockConfig {
+                min_face_area: 10.0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
 
-        assert!(faces.is_empty());
+        assert!(faces.faces.is_empty());
+    }
+
+    #[test]
+    fn merging_stops_when_a_group_runs_past_the_start() {
+        // The last vertex is close to the first three, so its group wraps around past the
+        // third vertex, where merging starts
+        let face = vec![
+            Point::new(0.0, 0.0),
+            Point::new(0.5, 0.0),
+            Point::new(1.8, 0.0),
+            Point::new(10.0, 10.0),
+            Point::new(0.9, 0.0),
+        ];
+
+        let merged = merge_near_points(face, 1.0);
+
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[1], Point::new(10.0, 10.0));
     }
 
     #[test]
@@ -3301,7 +3544,7 @@ mod test {
             ], */
         ];
 
-        let intersections = find_interesctions(&segments, true);
+        let intersections = find_interesctions(&segments, true).unwrap();
 
         assert_eq!(intersections.len(), 5);
         let intersections_set: HashSet<IntersectionPoint> = HashSet::from_iter(intersections);
@@ -3381,7 +3624,7 @@ mod test {
             [Point::new(472.4, 434.5), Point::new(489.5, 436.2)],
         ];
 
-        let intersections = find_interesctions(&segments, false);
+        let intersections = find_interesctions(&segments, false).unwrap();
 
         assert!(!intersections.is_empty());
     }
@@ -3411,7 +3654,7 @@ mod test {
             ],
         ];
 
-        let intersections = find_interesctions(&segments, false);
+        let intersections = find_interesctions(&segments, false).unwrap();
         assert_eq!(intersections.len(), 5);
     }
 
@@ -3464,7 +3707,7 @@ mod test {
             ],
         ];
 
-        let mut intersections_vec = find_interesctions(&segments, true);
+        let mut intersections_vec = find_interesctions(&segments, true).unwrap();
 
         for intersection in &intersections_vec {
             assert!(!intersection.intersecting_segment_indices.is_empty());
@@ -3649,7 +3892,8 @@ mod test {
             ),
         ];
 
-        let (verts, adjacency_list) = vertices_to_adjacency_list(intersections_vec, &all_segments);
+        let (verts, adjacency_list) =
+            vertices_to_adjacency_list(intersections_vec, &all_segments).unwrap();
 
         for (vert_index, vert_neighbors_indices) in &adjacency_list {
             let vert_pos = verts[*vert_index];
@@ -3681,7 +3925,7 @@ mod test {
 
     #[test]
     fn complex_intersections_correctly_detects_faces() {
-        let mut segments = vec![
+        let segments = vec![
             [
                 Point::new(93.185, 311.78308),
                 Point::new(69.25536, 282.38647),
@@ -3728,7 +3972,7 @@ mod test {
             ],
         ];
 
-        let (vertices, adjacency_list) = segments_to_adjacency_list(&mut segments);
+        let (vertices, adjacency_list) = segments_to_adjacency_list(&segments).unwrap();
         let dcel = DCEL::new(&vertices, &adjacency_list);
 
         assert_eq!(dcel.faces().len(), 5);
@@ -3743,7 +3987,7 @@ mod test {
             [Point::new(3.0, 0.0), Point::new(4.0, 1.0)],
         ];
 
-        let mut intersections = find_interesctions(&segments, true);
+        let mut intersections = find_interesctions(&segments, true).unwrap();
 
         let new_segments = split_segments_at_intersections(&mut intersections, &mut segments);
 
@@ -3844,7 +4088,8 @@ mod test {
 
         let (mut full_face, mut adjacency_list) = verts_to_adjacency_list(&face);
 
-        let new_faces = correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list);
+        let new_faces =
+            correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list).unwrap();
         assert_eq!(new_faces.len(), 2);
     }
 
@@ -3883,10 +4128,28 @@ mod test {
 
         let (mut full_face, mut adjacency_list) = verts_to_adjacency_list(&face);
 
-        let new_faces = correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list);
+        let new_faces =
+            correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list).unwrap();
         assert_eq!(new_faces.len(), 5);
     }
 
+    #[test]
+    fn degenerate_point_pointing_out_of_the_face_is_reported() {
+        let face = vec![
+            Point::new(0.0, 0.0),
+            Point::new(4.0, 0.0),
+            Point::new(4.0, 4.0),
+            Point::new(6.0, 6.0),
+            Point::new(4.0, 4.0),
+            Point::new(0.0, 4.0),
+        ];
+
+        let (mut full_face, mut adjacency_list) = verts_to_adjacency_list(&face);
+
+        let result = correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list);
+        assert!(matches!(result, Err(GeometryError::RaycastMissed { .. })));
+    }
+
     #[test]
     fn correcting_degenerate_points_does_nothing_with_no_degenerate_points() {
         let face = vec![
@@ -3898,7 +4161,8 @@ mod test {
 
         let (mut full_face, mut adjacency_list) = verts_to_adjacency_list(&face);
 
-        let new_faces = correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list);
+        let new_faces =
+            correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list).unwrap();
         assert_eq!(new_faces.len(), 1);
     }
 
@@ -3926,7 +4190,8 @@ mod test {
 
         let (mut full_face, mut adjacency_list) = verts_to_adjacency_list(&face);
 
-        let new_faces = correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list);
+        let new_faces =
+            correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list).unwrap();
 
         assert_eq!(new_faces.len(), 3);
 
@@ -3967,7 +4232,8 @@ mod test {
 
         let (mut full_face, mut adjacency_list) = verts_to_adjacency_list(&face);
 
-        let new_faces = correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list);
+        let new_faces =
+            correct_face_with_degenerate_points(&mut full_face, &mut adjacency_list).unwrap();
 
         assert_eq!(new_faces.len(), 4);
     }
@@ -4087,7 +4353,8 @@ mod test {
 
         let (_, mut adjacency_list) = verts_to_adjacency_list(&face);
 
-        let verts_of_split_face = split_face_at_concave_vertices(face, &mut adjacency_list);
+        let verts_of_split_face =
+            split_face_at_concave_vertices(face, &mut adjacency_list).unwrap();
 
         assert_eq!(verts_of_split_face.len(), face_len);
 
@@ -4110,7 +4377,8 @@ mod test {
 
         let (_, mut adjacency_list) = verts_to_adjacency_list(&face);
 
-        let verts_of_split_face = split_face_at_concave_vertices(face, &mut adjacency_list);
+        let verts_of_split_face =
+            split_face_at_concave_vertices(face, &mut adjacency_list).unwrap();
 
         assert_eq!(verts_of_split_face.len(), 7);
 
@@ -4142,7 +4410,8 @@ mod test {
 
         let (_, mut adjacency_list) = verts_to_adjacency_list(&face);
 
-        let verts_of_split_face = split_face_at_concave_vertices(face, &mut adjacency_list);
+        let verts_of_split_face =
+            split_face_at_concave_vertices(face, &mut adjacency_list).unwrap();
 
         assert_eq!(verts_of_split_face.len(), 15);
 
@@ -4196,7 +4465,7 @@ mod test {
             Point::new(399.0, 254.0),
         ];
 
-        let fixed_face = fix_non_manifold_face(face);
+        let fixed_face = fix_non_manifold_face(face).unwrap();
 
         let expected_face = vec![
             Point::new(391.0, 247.0),
@@ -4214,7 +4483,7 @@ mod test {
 
         let expected_set: HashSet<(OrderedFloat<f32>, OrderedFloat<f32>)> =
             HashSet::from_iter(expected_face.into_iter().map(|p| {
-                let trunc = truncate_point_to_decimal_place(p, 3);
+                let trunc = truncate_point_to_decimal_place(snap_point(p), 3);
 
                 (OrderedFloat(trunc.x), OrderedFloat(trunc.y))
             }));
@@ -4223,71 +4492,4 @@ mod test {
 
         assert_eq!(fixed_set.difference(&expected_set).count(), 0);
     }
-
-    #[test]
-    fn simple_subdivision() {
-        let face = vec![
-            Point::new(0.0, 0.0),
-            Point::new(2.0, 0.0),
-            Point::new(2.0, 1.0),
-            Point::new(0.0, 1.0),
-        ];
-
-        let subdivided_faces = subdivide_face(face, 1.5);
-
-        let expected_faces = vec![
-            vec![
-                Point::new(1.0, 1.0),
-                Point::new(0.0, 1.0),
-                Point::new(0.0, 2.0),
-                Point::new(1.0, 0.0),
-            ],
-            vec![
-                Point::new(1.0, 1.0),
-                Point::new(1.0, 0.0),
-                Point::new(2.0, 0.0),
-                Point::new(2.0, 1.0),
-            ],
-        ];
-
-        assert_eq!(subdivided_faces.len(), 2);
-
-        let subdivided_set_0: HashSet<(OrderedFloat<f32>, OrderedFloat<f32>)> = HashSet::from_iter(
-            subdivided_faces[0]
-                .iter()
-                .map(|point| (OrderedFloat(point.x), OrderedFloat(point.y))),
-        );
-
-        let subdivided_set_1: HashSet<(OrderedFloat<f32>, OrderedFloat<f32>)> = HashSet::from_iter(
-            subdivided_faces[1]
-                .iter()
-                .map(|point| (OrderedFloat(point.x), OrderedFloat(point.y))),
-        );
-
-        let expected_set_0: HashSet<(OrderedFloat<f32>, OrderedFloat<f32>)> = HashSet::from_iter(
-            expected_faces[0]
-                .iter()
-                .map(|point| (OrderedFloat(point.x), OrderedFloat(point.y))),
-        );
-
-        let expected_set_1: HashSet<(OrderedFloat<f32>, OrderedFloat<f32>)> = HashSet::from_iter(
-            expected_faces[1]
-                .iter()
-                .map(|point| (OrderedFloat(point.x), OrderedFloat(point.y))),
-        );
-
-        assert!(
-            (subdivided_set_0.len() == expected_set_0.len()
-                && subdivided_set_0.difference(&expected_set_0).count() == 0)
-                || (subdivided_set_0.len() == expected_set_1.len()
-                    && subdivided_set_0.difference(&expected_set_1).count() == 0)
-        );
-
-        assert!(
-            (subdivided_set_1.len() == expected_set_0.len()
-                && subdivided_set_1.difference(&expected_set_0).count() == 0)
-                || (subdivided_set_1.len() == expected_set_1.len()
-                    && subdivided_set_0.difference(&expected_set_1).count() == 0)
-        );
-    }
 }
@@ -0,0 +1,181 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use ordered_float::OrderedFloat;
+
+use crate::intersections::ArrangementBuilder;
+use crate::street_graph::GeometryError;
+use crate::street_plan::HermiteCurve;
+use crate::tensor_field::Point;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreetEdge {
+    pub vertices: [usize; 2],
+    pub length: f32,
+    /// The lowest of the street levels running along the edge
+    pub level: usize,
+}
+
+/// The streets of every level as a weighted graph, split wherever two streets meet. Streets
+/// follow their control points, the same way the blocks do.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreetNetwork {
+    pub vertices: Vec<Point>,
+    pub edges: Vec<StreetEdge>,
+    /// The neighbours of every vertex along with the edge leading to them
+    pub adjacency: Vec<Vec<(usize, usize)>>,
+}
+
+/// Distances from a source vertex and the edge every vertex was reached through, see
+/// `StreetNetwork::shortest_paths`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShortestPaths {
+    pub source: usize,
+    /// `f32::INFINITY` for vertices that can't be reached
+    pub distances: Vec<f32>,
+    pub previous: Vec<Option<(usize, usize)>>,
+}
+
+impl ShortestPaths {
+    /// The vertices from the source to `target`, both included, or `None` if it can't be reached
+    pub fn path_to(&self, target: usize) -> Option<Vec<usize>> {
+        if !self.distances[target].is_finite() {
+            return None;
+        }
+        let mut path = vec![target];
+        let mut current = target;
+        while let Some((previous, _)) = self.previous[current] {
+            path.push(previous);
+            current = previous;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+impl StreetNetwork {
+    pub fn from_levels(levels: &[Vec<HermiteCurve>]) -> Result<Self, GeometryError> {
+        let mut builder = ArrangementBuilder::new();
+        for (level, curves) in levels.iter().enumerate() {
+            for curve in curves {
+                let mut points: Vec<Point> = curve.iter().map(|point| point.position).collect();
+                points.dedup();
+                builder.add_polyline(&points, level);
+            }
+        }
+        let arrangement = builder.build()?;
+
+        let edges: Vec<StreetEdge> = arrangement
+            .edges
+            .iter()
+            .filter(|edge| edge.vertices[0] != edge.vertices[1])
+            .map(|edge| StreetEdge {
+                vertices: edge.vertices,
+                length: (arrangement.vertices[edge.vertices[1]]
+                    - arrangement.vertices[edge.vertices[0]])
+                    .norm(),
+                level: edge.tags.iter().copied().min().unwrap_or_default(),
+            })
+            .collect();
+
+        let mut adjacency = vec![Vec::new(); arrangement.vertices.len()];
+        for (index, edge) in edges.iter().enumerate() {
+            let [start, end] = edge.vertices;
+            adjacency[start].push((end, index));
+            adjacency[end].push((start, index));
+        }
+
+        Ok(Self {
+            vertices: arrangement.vertices,
+            edges,
+            adjacency,
+        })
+    }
+
+    pub fn degree(&self, vertex: usize) -> usize {
+        self.adjacency[vertex].len()
+    }
+
+    /// Dijkstra from `source` over the edges `use_edge` accepts
+    pub fn shortest_paths(
+        &self,
+        source: usize,
+        use_edge: impl Fn(usize, &StreetEdge) -> bool,
+    ) -> ShortestPaths {
+        let mut distances = vec![f32::INFINITY; self.vertices.len()];
+        let mut previous = vec![None; self.vertices.len()];
+        let mut queue = BinaryHeap::from([Reverse((OrderedFloat(0.0), source))]);
+        distances[source] = 0.0;
+
+        while let Some(Reverse((OrderedFloat(distance), vertex))) = queue.pop() {
+            if distance > distances[vertex] {
+                continue;
+            }
+            for &(neighbour, edge_index) in &self.adjacency[vertex] {
+                let edge = &self.edges[edge_index];
+                if !use_edge(edge_index, edge) {
+                    continue;
+                }
+                let candidate = distance + edge.length;
+                if candidate < distances[neighbour] {
+                    distances[neighbour] = candidate;
+                    previous[neighbour] = Some((vertex, edge_index));
+                    queue.push(Reverse((OrderedFloat(candidate), neighbour)));
+                }
+            }
+        }
+
+        ShortestPaths {
+            source,
+            distances,
+            previous,
+        }
+    }
+
+    /// The vertex closest to the point among the ones `accept` lets through
+    pub fn closest_vertex(&self, point: Point, accept: impl Fn(usize) -> bool) -> Option<usize> {
+        (0..self.vertices.len())
+            .filter(|&vertex| accept(vertex))
+            .min_by(|&a, &b| {
+                (self.vertices[a] - point)
+                    .norm_squared()
+                    .total_cmp(&(self.vertices[b] - point).norm_squared())
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tensor_field::Point;
+    use crate::test_support::curve;
+
+    use super::StreetNetwork;
+
+    #[test]
+    fn crossing_streets_share_a_vertex_and_paths_follow_the_levels() {
+        let levels = vec![
+            vec![curve(&[(0.0, 0.0), (20.0, 0.0)])],
+            vec![
+                curve(&[(10.0, -10.0), (10.0, 10.0)]),
+                curve(&[(0.0, 0.0), (0.0, 10.0), (10.0, 10.0)]),
+            ],
+        ];
+
+        let network = StreetNetwork::from_levels(&levels).unwrap();
+
+        let vertex = |x: f32, y: f32| network.closest_vertex(Point::new(x, y), |_| true).unwrap();
+        assert_eq!(network.degree(vertex(10.0, 0.0)), 4);
+        assert_eq!(
+            network.edges.iter().filter(|edge| edge.level == 0).count(),
+            2
+        );
+
+        let everything = network.shortest_paths(vertex(0.0, 0.0), |_, _| true);
+        assert!((everything.distances[vertex(10.0, 10.0)] - 20.0).abs() < 0.001);
+        assert_eq!(everything.path_to(vertex(10.0, 10.0)).unwrap().len(), 3);
+
+        let major_only = network.shortest_paths(vertex(0.0, 0.0), |_, edge| edge.level == 0);
+        assert_eq!(major_only.path_to(vertex(10.0, 10.0)), None);
+        assert!((major_only.distances[vertex(20.0, 0.0)] - 20.0).abs() < 0.001);
+    }
+}
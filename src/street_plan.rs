@@ -5,6 +5,8 @@ use nalgebra::Vector2;
 use rand::Rng;
 use rayon::prelude::*;
 
+use crate::config::{SmoothingConfig, TracingConfig};
+use crate::history::IterationHistory;
 use crate::tensor_field::{EvalEigenvectors, GRID_SIZE, Point, TensorField};
 
 pub fn distribute_points(point_count: u32) -> Vec<Point> {
@@ -171,11 +173,11 @@ fn sector_has_degenerate_point(
 }
 
 pub enum TraceSeeds {
-    Random(u32),
+    Random,
     Specific(Vec<SeedPoint>),
 }
 
-pub fn heap_to_vec<T: Clone + Ord>(heap: BinaryHeap<T>) -> Vec<T> {
+pub(crate) fn heap_to_vec<T: Clone + Ord>(heap: BinaryHeap<T>) -> Vec<T> {
     let mut res = Vec::new();
     let mut heap = heap.clone();
     while let Some(head) = heap.pop() {
@@ -184,6 +186,49 @@ pub fn heap_to_vec<T: Clone + Ord>(heap: BinaryHeap<T>) -> Vec<T> {
     res
 }
 
+/// The fixed set of seed points levels seeded with `TraceSeeds::Random` start from. It stands in
+/// for `distribute_points` so that runs can be compared while debugging.
+pub(crate) fn debug_seed_points() -> Vec<Point> {
+    [
+        (391.0, 113.0),
+        (10.0, 470.0),
+        (382.0, 472.0),
+        (61.0, 152.0),
+        (413.0, 291.0),
+        (191.0, 298.0),
+        (0.0, 303.0),
+        (147.0, 0.0),
+        (304.0, 294.0),
+        (298.0, 41.0),
+        (230.0, 509.0),
+        (502.0, 416.0),
+        (127.0, 205.0),
+        (285.0, 162.0),
+        (459.0, 40.0),
+        (299.0, 436.0),
+        (121.0, 472.0),
+        (508.0, 493.0),
+        (470.0, 151.0),
+        (214.0, 413.0),
+        (364.0, 355.0),
+        (171.0, 63.0),
+        (355.0, 191.0),
+        (274.0, 355.0),
+        (66.0, 336.0),
+        (230.0, 65.0),
+        (30.0, 31.0),
+        (223.0, 12.0),
+        (193.0, 146.0),
+        (447.0, 224.0),
+    ]
+    .map(|p| Point::new(p.0, p.1))
+    .to_vec()
+}
+
+/// Traces `iter_count` iterations of streets, alternating between the major and minor
+/// eigenvectors, around the curves of the previous levels. What every iteration did is appended to
+/// `history` when one is given.
+#[allow(clippy::too_many_arguments)]
 pub fn trace_street_plan(
     tensor_field: &TensorField,
     seeds: TraceSeeds,
@@ -192,50 +237,12 @@ pub fn trace_street_plan(
     iter_count: usize,
     previous_major_curves: Vec<HermiteCurve>,
     previous_minor_curves: Vec<HermiteCurve>,
+    tracing: &TracingConfig,
+    smoothing: &SmoothingConfig,
+    mut history: Option<&mut Vec<IterationHistory>>,
 ) -> (Vec<HermiteCurve>, Vec<HermiteCurve>) {
     let mut seed_points = match seeds {
-        TraceSeeds::Random(starting_seed_count) => {
-            let temp_points = [
-                (391.0, 113.0),
-                (10.0, 470.0),
-                (382.0, 472.0),
-                (61.0, 152.0),
-                (413.0, 291.0),
-                (191.0, 298.0),
-                (0.0, 303.0),
-                (147.0, 0.0),
-                (304.0, 294.0),
-                (298.0, 41.0),
-                (230.0, 509.0),
-                (502.0, 416.0),
-                (127.0, 205.0),
-                (285.0, 162.0),
-                (459.0, 40.0),
-                (299.0, 436.0),
-                (121.0, 472.0),
-                (508.0, 493.0),
-                (470.0, 151.0),
-                (214.0, 413.0),
-                (364.0, 355.0),
-                (171.0, 63.0),
-                (355.0, 191.0),
-                (274.0, 355.0),
-                (66.0, 336.0),
-                (230.0, 65.0),
-                (30.0, 31.0),
-                (223.0, 12.0),
-                (193.0, 146.0),
-                (447.0, 224.0),
-            ]
-            .map(|p| Point::new(p.0, p.1));
-
-            prioritize_points(
-                // &distribute_points(starting_seed_count),
-                &temp_points,
-                city_center,
-                &tensor_field,
-            )
-        }
+        TraceSeeds::Random => prioritize_points(&debug_seed_points(), city_center, tensor_field),
         TraceSeeds::Specific(seed_points) => BinaryHeap::from(seed_points),
     };
 
@@ -247,12 +254,13 @@ pub fn trace_street_plan(
     let d_sep_val = d_sep;
 
     for i in 0..iter_count {
-        let h = 0.2;
+        let h = tracing.h;
         let d_sep = |_point: Point| d_sep_val/*  + (point - city_center).norm() / GRID_SIZE as f32 * 15.0 */;
         let follow_major_eigenvectors = (i % 2) == 0;
 
+        let seeds = heap_to_vec(seed_points.clone());
         let traces = trace_lanes(
-            heap_to_vec(seed_points.clone())
+            seeds
                 .iter()
                 .enumerate()
                 .map(|(i, seed)| (i, seed.seed))
@@ -261,7 +269,7 @@ pub fn trace_street_plan(
             h,
             d_sep.clone(),
             follow_major_eigenvectors,
-            200.0,
+            tracing.max_len,
             if follow_major_eigenvectors {
                 &major_curves
             } else {
@@ -269,12 +277,38 @@ pub fn trace_street_plan(
             },
         );
 
+        let mut record = history.is_some().then(|| {
+            let (seeds, traces) = seeds
+                .iter()
+                .zip(&traces)
+                .filter(|(_, TraceOutput { path, .. })| !path.is_empty())
+                .map(|(seed, TraceOutput { path, .. })| (seed.seed, path.clone()))
+                .unzip();
+            IterationHistory {
+                follow_major_eigenvectors,
+                seeds,
+                traces,
+                ..IterationHistory::default()
+            }
+        });
+
         let pre: usize = traces
             .iter()
             .map(|TraceOutput { new_seeds, .. }| new_seeds.len())
             .sum();
 
-        let curve_paths = smooth_lanes(traces, 0.03, 0.3, 20, h, 0.7);
+        let curve_paths = smooth_lanes(
+            traces,
+            smoothing.alpha,
+            smoothing.beta,
+            smoothing.point_side_padding,
+            h,
+            smoothing.blend_factor,
+        );
+
+        if let Some(record) = &mut record {
+            record.smoothed = curve_paths.iter().map(|path| path.curve.clone()).collect();
+        }
 
         let (clipped_paths, new_seeds): (Vec<HermiteCurve>, Vec<Vec<Point>>) = clip_pass(
             curve_paths,
@@ -284,13 +318,18 @@ pub fn trace_street_plan(
                 &minor_curves
             },
             d_sep,
-            d_sep_val * 2.0,
+            d_sep_val * tracing.min_length_factor,
         )
         .into_iter()
         .unzip();
 
         println!("Pre: {pre}, post: {}", new_seeds.len());
 
+        if let (Some(history), Some(mut record)) = (history.as_deref_mut(), record) {
+            record.clipped = clipped_paths.clone();
+            history.push(record);
+        }
+
         seed_points.extend(new_seeds.into_iter().flatten().map(|seed| SeedPoint {
             seed,
             priority: 0.0,
@@ -306,7 +345,7 @@ pub fn trace_street_plan(
 
     (
         major_curves[prev_major_len..].to_vec(),
-        minor_curves[prev_major_len..].to_vec(),
+        minor_curves[prev_minor_len..].to_vec(),
     )
 }
 
@@ -572,7 +611,7 @@ fn smooth_lanes(
         .collect()
 }
 
-pub fn smooth_path(path: Vec<Point>, alpha: f32, beta: f32) -> Vec<Point> {
+pub(crate) fn smooth_path(path: Vec<Point>, alpha: f32, beta: f32) -> Vec<Point> {
     let first_pass: Vec<Point> = path
         .iter()
         .enumerate()
@@ -626,7 +665,7 @@ fn point_first_deriv(point_index: usize, path: &[Point]) -> Vector2<f32> {
     path[point_index + 1] - path[point_index]
 }
 
-pub fn point_second_deriv(point_index: usize, path: &[Point]) -> Vector2<f32> {
+pub(crate) fn point_second_deriv(point_index: usize, path: &[Point]) -> Vector2<f32> {
     point_first_deriv(point_index, path).normalize()
         - point_first_deriv(point_index - 1, path).normalize()
 }
@@ -654,7 +693,10 @@ pub fn resample_curve(control_points: &[ControlPoint], points_per_spline: i32) -
         .collect()
 }
 
-pub fn highest_curvature_points(path: &[Point], point_padding_per_side: usize) -> Vec<usize> {
+pub(crate) fn highest_curvature_points(
+    path: &[Point],
+    point_padding_per_side: usize,
+) -> Vec<usize> {
     let mut indices_sorted_by_curvature: Vec<usize> = (1..path.len() - 1).collect();
 
     indices_sorted_by_curvature.sort_by(|&a, &b| {
@@ -834,7 +876,21 @@ fn evaluate_hermite_curve(
         + p_0
 }
 
-fn merge_point_to_curves(
+/// Whether the curve runs closer to the major than to the minor eigenvectors of the field around
+/// its middle
+pub(crate) fn follows_major_eigenvectors(curve: &HermiteCurve, tensor_field: &TensorField) -> bool {
+    let middle = curve.len() / 2;
+    let [start, end] = [curve[middle.saturating_sub(1)], curve[middle]]
+        .map(|control_point| control_point.position);
+    let direction = (end - start).normalize();
+    let eigenvectors = tensor_field
+        .evaluate_smoothed_field_at_point((start + end) / 2.0)
+        .eigenvectors();
+    direction.dot(&eigenvectors.major.normalize()).abs()
+        >= direction.dot(&eigenvectors.minor.normalize()).abs()
+}
+
+pub(crate) fn merge_point_to_curves(
     point: ControlPoint,
     other_curves: &[&HermiteCurve],
     connection_distance_squared: f32,
@@ -858,11 +914,23 @@ fn merge_point_to_curves(
 }
 
 pub fn merge_road_endings(curves: &[HermiteCurve], connection_distance: f32) -> Vec<HermiteCurve> {
+    merge_road_endings_onto(curves, &[], connection_distance)
+}
+
+/// Same as `merge_road_endings`, but ends may also be joined onto `fixed_curves`, which are left
+/// as they are
+pub(crate) fn merge_road_endings_onto(
+    curves: &[HermiteCurve],
+    fixed_curves: &[&HermiteCurve],
+    connection_distance: f32,
+) -> Vec<HermiteCurve> {
     let mut merged_curves = Vec::with_capacity(curves.len());
     curves.iter().enumerate().for_each(|(i, curve)| {
         let (_, right_split) = curves.split_at(i);
-        let other_curves: Vec<&HermiteCurve> = merged_curves
+        let other_curves: Vec<&HermiteCurve> = fixed_curves
             .iter()
+            .copied()
+            .chain(merged_curves.iter())
             .chain(right_split[1..].into_iter())
             .collect();
 
@@ -928,9 +996,11 @@ fn snap_point_to_point(
 
 #[cfg(test)]
 mod test {
-    use crate::tensor_field::Point;
+    use crate::config::CityConfig;
+    use crate::tensor_field::{Point, TensorField};
+    use crate::test_support::curve;
 
-    use super::{ControlPoint, merge_road_endings};
+    use super::{ControlPoint, TraceSeeds, merge_road_endings, trace_street_plan};
 
     #[test]
     fn basic_1() {
@@ -985,4 +1055,32 @@ mod test {
         assert_eq!(merged_curves[0], curves[0]);
         assert_eq!(merged_curves[1], expected_new_curve);
     }
+
+    #[test]
+    fn only_new_curves_are_returned_for_uneven_previous_levels() {
+        let config = CityConfig::default();
+        let tensor_field = TensorField::new(
+            config.field.design_elements.clone(),
+            config.field.decay_constant,
+        );
+        let previous_minor = curve(&[(10.0, 10.0), (10.0, 20.0), (10.0, 30.0)]);
+
+        let (major_curves, minor_curves) = trace_street_plan(
+            &tensor_field,
+            TraceSeeds::Random,
+            config.city_center(),
+            30.0,
+            2,
+            Vec::new(),
+            vec![previous_minor.clone()],
+            &config.tracing,
+            &config.smoothing,
+            None,
+        );
+
+        assert!(!major_curves.is_empty());
+        assert!(!minor_curves.is_empty());
+        // The previous minor curve was handed in, so it doesn't come back out
+        assert!(!minor_curves.contains(&previous_minor));
+    }
 }
@@ -1,4 +1,6 @@
 use nalgebra::{Matrix2, Vector2};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub const GRID_SIZE: u32 = 512;
 
@@ -18,20 +20,17 @@ impl TensorField {
     pub fn new(design_elements: Vec<DesignElement>, decay_constant: f32) -> TensorField {
         let mut grid = vec![Tensor::default(); (GRID_SIZE * GRID_SIZE) as usize].into_boxed_slice();
 
-        let design_elements_ref = &design_elements;
-        async_scoped::TokioScope::scope_and_block(|scope| {
-            for (row, chunk) in grid.chunks_mut(GRID_SIZE as usize).enumerate() {
-                scope.spawn(async move {
-                    for col in 0..GRID_SIZE as usize {
-                        chunk[col] = Self::calculate_smoothed_field_at_point(
-                            Point::new(col as f32, row as f32),
-                            design_elements_ref,
-                            decay_constant,
-                        )
-                    }
-                });
-            }
-        });
+        grid.par_chunks_mut(GRID_SIZE as usize)
+            .enumerate()
+            .for_each(|(row, chunk)| {
+                for (col, tensor) in chunk.iter_mut().enumerate() {
+                    *tensor = Self::calculate_smoothed_field_at_point(
+                        Point::new(col as f32, row as f32),
+                        &design_elements,
+                        decay_constant,
+                    )
+                }
+            });
         TensorField {
             grid,
             design_elements,
@@ -128,6 +127,26 @@ impl TensorField {
         a * (1.0 - t) + b * t
     }
 
+    /// The major and minor eigenvectors at every `spacing` cells of the grid, scaled to one cell
+    /// short of the spacing so that neighbouring glyphs don't touch
+    pub fn eigenvector_glyphs(&self, spacing: u32) -> Vec<Glyph> {
+        let length = spacing.saturating_sub(1) as f32;
+        (0..GRID_SIZE / spacing)
+            .flat_map(|x| (0..GRID_SIZE / spacing).map(move |y| (x, y)))
+            .map(|(x, y)| {
+                let position = Point::new((x * spacing) as f32, (y * spacing) as f32);
+                let eigenvectors = self
+                    .evaluate_smoothed_field_at_point(position)
+                    .eigenvectors();
+                Glyph {
+                    position,
+                    major: eigenvectors.major.normalize() * length,
+                    minor: eigenvectors.minor.normalize() * length,
+                }
+            })
+            .collect()
+    }
+
     pub fn design_elements(&self) -> &[DesignElement] {
         &self.design_elements
     }
@@ -137,7 +156,8 @@ impl TensorField {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
 pub enum DesignElement {
     Grid {
         center: Point,
@@ -194,6 +214,31 @@ impl DesignElement {
         }
     }
 
+    /// The same element moved by `offset`
+    pub fn translated(&self, offset: Point) -> DesignElement {
+        match self {
+            DesignElement::Grid {
+                center,
+                theta,
+                length,
+            } => DesignElement::Grid {
+                center: center + offset,
+                theta: *theta,
+                length: *length,
+            },
+            DesignElement::Radial { center } => DesignElement::Radial {
+                center: center + offset,
+            },
+            DesignElement::PolyLine {
+                points,
+                decay_constant,
+            } => DesignElement::PolyLine {
+                points: points.iter().map(|point| point + offset).collect(),
+                decay_constant: *decay_constant,
+            },
+        }
+    }
+
     pub fn center(&self) -> Option<Point> {
         match self {
             DesignElement::Grid { center, .. } => Some(*center),
@@ -203,6 +248,14 @@ impl DesignElement {
     }
 }
 
+/// The eigenvectors of the field at a point, for drawing it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glyph {
+    pub position: Point,
+    pub major: Point,
+    pub minor: Point,
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Eigenvectors {
     pub major: Vector2<f32>,
@@ -0,0 +1,24 @@
+use crate::street_plan::{ControlPoint, HermiteCurve};
+use crate::tensor_field::Point;
+
+/// A street through the points, with no velocity at its control points so that it follows them
+/// in straight lines
+pub(crate) fn curve(points: &[(f32, f32)]) -> HermiteCurve {
+    points
+        .iter()
+        .map(|&(x, y)| ControlPoint {
+            position: Point::new(x, y),
+            velocity: Point::zeros(),
+        })
+        .collect()
+}
+
+/// The counter-clockwise rectangle between two opposite corners
+pub(crate) fn rectangle(min: Point, max: Point) -> Vec<Point> {
+    vec![min, Point::new(max.x, min.y), max, Point::new(min.x, max.y)]
+}
+
+/// The counter-clockwise square reaching from `min` to `max` along both axes
+pub(crate) fn square(min: f32, max: f32) -> Vec<Point> {
+    rectangle(Point::new(min, min), Point::new(max, max))
+}
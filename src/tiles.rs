@@ -0,0 +1,499 @@
+use crate::city::{City, assemble_city, build_on_blocks, trace_levels};
+use crate::config::CityConfig;
+use crate::region::clip_curve;
+use crate::street_graph::{Blocks, GeometryError, path_to_graph};
+use crate::street_plan::{HermiteCurve, merge_point_to_curves};
+use crate::tensor_field::{GRID_SIZE, Point, TensorField};
+use crate::triangulation::{Triangulation, contains_point, triangulate};
+
+/// One tile of a tiled city. Tiles are traced on a tensor field grid that reaches `overlap` past
+/// every side of the tile, so streets run up to the tile's borders instead of thinning out where
+/// the grid ends, and only the part of the streets inside the tile is kept.
+#[derive(Debug, Clone)]
+pub struct Tile {
+    pub column: u32,
+    pub row: u32,
+    /// Lower left corner of the tile in the coordinates of the whole city
+    pub origin: Point,
+    /// Width and height of the tile
+    pub size: f32,
+    /// The streets of every level as traced on the tile, moved into the coordinates of the whole
+    /// city and cut at the tile's borders, but not stitched to the neighbouring tiles
+    pub levels: Vec<Vec<HermiteCurve>>,
+    /// The points every level started tracing from inside the tile, in the coordinates of the
+    /// whole city
+    pub seeds: Vec<Vec<Point>>,
+}
+
+impl Tile {
+    /// Corners of the tile, counterclockwise from `origin`
+    pub fn corners(&self) -> [Point; 4] {
+        [
+            self.origin,
+            self.origin + Point::new(self.size, 0.0),
+            self.origin + Point::new(self.size, self.size),
+            self.origin + Point::new(0.0, self.size),
+        ]
+    }
+}
+
+/// A city generated tile by tile, as laid out by `CityConfig::tiles`
+#[derive(Debug)]
+pub struct TiledCity {
+    pub tiles: Vec<Tile>,
+    /// The streets of all tiles joined across the tile borders, with the blocks between them
+    /// zoned and built on like those of `generate_city`, so that it exports the same way. Its
+    /// tensor field is the one of the design elements at their own coordinates, which only
+    /// covers the first tile's grid.
+    pub city: City,
+}
+
+impl TiledCity {
+    /// Traces the tile again, for example after its design elements changed, and redoes the
+    /// stitching, the blocks and everything built on them. The streets of the other tiles are
+    /// kept as they are.
+    pub fn regenerate_tile(
+        &mut self,
+        config: &CityConfig,
+        column: u32,
+        row: u32,
+    ) -> Result<(), GeometryError> {
+        let tile = generate_tile(config, column, row);
+        match self
+            .tiles
+            .iter_mut()
+            .find(|existing| (existing.column, existing.row) == (column, row))
+        {
+            Some(existing) => *existing = tile,
+            None => self.tiles.push(tile),
+        }
+        self.city = assemble_tiles(&self.tiles, config)?;
+        Ok(())
+    }
+}
+
+/// Traces every tile of the layout in `CityConfig::tiles`, joins the streets that meet at the
+/// tile borders and builds the blocks of the whole city on them in one go
+pub fn generate_tiled_city(config: &CityConfig) -> Result<TiledCity, GeometryError> {
+    let tiles: Vec<Tile> = (0..config.tiles.rows)
+        .flat_map(|row| (0..config.tiles.columns).map(move |column| (column, row)))
+        .map(|(column, row)| generate_tile(config, column, row))
+        .collect();
+    let city = assemble_tiles(&tiles, config)?;
+    Ok(TiledCity { tiles, city })
+}
+
+/// Stitches the tiles together, extracts and triangulates the blocks between all of their
+/// streets, and zones and builds on them like `generate_city`
+fn assemble_tiles(tiles: &[Tile], config: &CityConfig) -> Result<City, GeometryError> {
+    let levels = stitch_tiles(tiles, config.tiles.stitch_distance);
+    let mut seeds: Vec<Vec<Point>> = vec![Vec::new(); levels.len()];
+    for tile in tiles {
+        for (level, tile_seeds) in tile.seeds.iter().enumerate() {
+            seeds[level].extend(tile_seeds);
+        }
+    }
+
+    let blocks = extract_blocks(&levels, config)?;
+    let block_triangulations: Vec<Triangulation> = blocks
+        .faces
+        .iter()
+        .map(|face| triangulate(face, &[]))
+        .collect();
+    let contents = build_on_blocks(config, &levels, &blocks.faces, &block_triangulations);
+
+    let tensor_field = TensorField::new(
+        config.field.design_elements.clone(),
+        config.field.decay_constant,
+    );
+    Ok(assemble_city(
+        config,
+        tensor_field,
+        levels,
+        seeds,
+        blocks,
+        block_triangulations,
+        contents,
+    ))
+}
+
+/// Builds the tensor field of a single tile from the design elements of the whole city, traces
+/// every level on it and cuts the streets at the tile's borders
+pub fn generate_tile(config: &CityConfig, column: u32, row: u32) -> Tile {
+    let overlap = config.tiles.overlap;
+    let size = GRID_SIZE as f32 - 2.0 * overlap;
+    let origin = Point::new(column as f32, row as f32) * size;
+    let grid_origin = origin - Point::new(overlap, overlap);
+
+    let tensor_field = TensorField::new(
+        config
+            .field
+            .design_elements
+            .iter()
+            .map(|element| element.translated(-grid_origin))
+            .collect(),
+        config.field.decay_constant,
+    );
+
+    // The mask is given in the coordinates of the whole city as well
+    let tile_config = CityConfig {
+        mask: config.mask.translated(-grid_origin),
+        ..config.clone()
+    };
+    let (levels, seeds) = trace_levels(
+        &tensor_field,
+        &tile_config,
+        config.city_center() - grid_origin,
+        &[],
+        None,
+    );
+
+    let mut tile = Tile {
+        column,
+        row,
+        origin,
+        size,
+        levels: Vec::new(),
+        seeds: Vec::new(),
+    };
+    let corners = tile.corners();
+    tile.seeds = seeds
+        .into_iter()
+        .map(|seeds| {
+            seeds
+                .into_iter()
+                .map(|seed| seed + grid_origin)
+                .filter(|&seed| contains_point(&corners, seed))
+                .collect()
+        })
+        .collect();
+    tile.levels = levels
+        .into_iter()
+        .map(|curves| {
+            curves
+                .into_iter()
+                .flat_map(|mut curve| {
+                    for control_point in &mut curve {
+                        control_point.position += grid_origin;
+                    }
+                    clip_curve(&curve, &corners, true).0
+                })
+                .collect()
+        })
+        .collect();
+    tile
+}
+
+fn extract_blocks(
+    levels: &[Vec<HermiteCurve>],
+    config: &CityConfig,
+) -> Result<Blocks, GeometryError> {
+    let all_curves: Vec<HermiteCurve> = levels.iter().rev().flatten().cloned().collect();
+    let mut blocks = path_to_graph(&all_curves, &config.blocks)?;
+    blocks.faces.retain(|face| config.mask.allows_block(face));
+    Ok(blocks)
+}
+
+/// An end of a street that may get joined to a street of a neighbouring tile
+#[derive(Debug, Clone, Copy)]
+struct StreetEnd {
+    tile: usize,
+    level: usize,
+    curve: usize,
+    last: bool,
+    position: Point,
+}
+
+/// Puts the streets of all tiles together level by level. Tracing stops at the edge of a tile's
+/// grid, so streets running across a border end close to it on both sides. Pairs of such ends
+/// within `stitch_distance` of each other are moved onto a shared point on the border, closest
+/// pairs first. Ends left without a partner are joined onto the closest street of a neighbouring
+/// tile within the same distance, the way `merge_road_endings` joins streets within a tile.
+pub fn stitch_tiles(tiles: &[Tile], stitch_distance: f32) -> Vec<Vec<HermiteCurve>> {
+    let level_count = tiles
+        .iter()
+        .map(|tile| tile.levels.len())
+        .max()
+        .unwrap_or(0);
+    let mut levels: Vec<Vec<HermiteCurve>> = vec![Vec::new(); level_count];
+    let mut curve_tiles: Vec<Vec<usize>> = vec![Vec::new(); level_count];
+    let mut ends: Vec<StreetEnd> = Vec::new();
+    for (tile_index, tile) in tiles.iter().enumerate() {
+        for (level, curves) in tile.levels.iter().enumerate() {
+            for curve in curves {
+                if curve.len() >= 2 {
+                    for last in [false, true] {
+                        ends.push(StreetEnd {
+                            tile: tile_index,
+                            level,
+                            curve: levels[level].len(),
+                            last,
+                            position: if last {
+                                curve[curve.len() - 1]
+                            } else {
+                                curve[0]
+                            }
+                            .position,
+                        });
+                    }
+                }
+                levels[level].push(curve.clone());
+                curve_tiles[level].push(tile_index);
+            }
+        }
+    }
+
+    let near_border = |end: &StreetEnd| {
+        let tile = &tiles[end.tile];
+        let local = end.position - tile.origin;
+        local
+            .iter()
+            .any(|&coord| coord <= stitch_distance || coord >= tile.size - stitch_distance)
+    };
+    let border_ends: Vec<usize> = (0..ends.len())
+        .filter(|&index| near_border(&ends[index]))
+        .collect();
+
+    let mut pairs: Vec<(f32, usize, usize, Point)> = Vec::new();
+    for &first_index in &border_ends {
+        for &second_index in &border_ends {
+            let (first, second) = (&ends[first_index], &ends[second_index]);
+            let first_tile = &tiles[first.tile];
+            let second_tile = &tiles[second.tile];
+            let distance = (first.position - second.position).norm();
+            if distance > stitch_distance {
+                continue;
+            }
+            // Every border is looked at from the tile on its left or below it
+            let joint = if (second_tile.column, second_tile.row)
+                == (first_tile.column + 1, first_tile.row)
+            {
+                let border = second_tile.origin.x;
+                Point::new(border, (first.position.y + second.position.y) / 2.0)
+            } else if (second_tile.column, second_tile.row)
+                == (first_tile.column, first_tile.row + 1)
+            {
+                let border = second_tile.origin.y;
+                Point::new((first.position.x + second.position.x) / 2.0, border)
+            } else {
+                continue;
+            };
+            pairs.push((distance, first_index, second_index, joint));
+        }
+    }
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut stitched = vec![false; ends.len()];
+    for (_, first, second, joint) in pairs {
+        if stitched[first] || stitched[second] {
+            continue;
+        }
+        stitched[first] = true;
+        stitched[second] = true;
+        for end in [ends[first], ends[second]] {
+            let curve = &mut levels[end.level][end.curve];
+            let index = if end.last { curve.len() - 1 } else { 0 };
+            curve[index].position = joint;
+        }
+    }
+
+    let connection_distance_squared = stitch_distance * stitch_distance;
+    let joined: Vec<(StreetEnd, Point)> = border_ends
+        .iter()
+        .filter(|&&index| !stitched[index])
+        .map(|&index| {
+            let end = ends[index];
+            let tile = &tiles[end.tile];
+            let neighbouring_curves: Vec<&HermiteCurve> = levels
+                .iter()
+                .zip(&curve_tiles)
+                .flat_map(|(curves, tile_indices)| curves.iter().zip(tile_indices))
+                .filter(|&(_, &other)| {
+                    let other = &tiles[other];
+                    tile.column.abs_diff(other.column) + tile.row.abs_diff(other.row) == 1
+                })
+                .map(|(curve, _)| curve)
+                .collect();
+            let curve = &levels[end.level][end.curve];
+            let control_point = if end.last {
+                curve[curve.len() - 1]
+            } else {
+                curve[0]
+            };
+            let merged = merge_point_to_curves(
+                control_point,
+                &neighbouring_curves,
+                connection_distance_squared,
+            );
+            (end, merged.position)
+        })
+        .collect();
+    for (end, position) in joined {
+        let curve = &mut levels[end.level][end.curve];
+        let index = if end.last { curve.len() - 1 } else { 0 };
+        curve[index].position = position;
+    }
+
+    levels
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{CityConfig, TileConfig};
+    use crate::mesh_export::city_meshes;
+    use crate::region::clip_curve;
+    use crate::street_plan::HermiteCurve;
+    use crate::tensor_field::{DesignElement, Point};
+    use crate::test_support::curve;
+
+    use super::{Tile, generate_tiled_city, stitch_tiles};
+
+    fn tile(column: u32, row: u32, levels: Vec<Vec<HermiteCurve>>) -> Tile {
+        Tile {
+            column,
+            row,
+            origin: Point::new(column as f32, row as f32) * 512.0,
+            size: 512.0,
+            levels,
+            seeds: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn streets_are_cut_at_the_tile_borders() {
+        let tile = tile(1, 0, Vec::new());
+        // Leaves the tile on the left and comes back in
+        let street = curve(&[
+            (600.0, 100.0),
+            (480.0, 100.0),
+            (480.0, 200.0),
+            (600.0, 200.0),
+            (700.0, 300.0),
+        ]);
+
+        let (pieces, _) = clip_curve(&street, &tile.corners(), true);
+
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(
+            pieces[0]
+                .iter()
+                .map(|point| point.position)
+                .collect::<Vec<_>>(),
+            [Point::new(600.0, 100.0), Point::new(512.0, 100.0)]
+        );
+        assert_eq!(pieces[1][0].position, Point::new(512.0, 200.0));
+        assert_eq!(pieces[1].len(), 3);
+        assert!(
+            clip_curve(&curve(&[(10.0, 10.0), (20.0, 10.0)]), &tile.corners(), true)
+                .0
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn streets_meeting_at_a_border_are_joined_on_it() {
+        let tiles = vec![
+            tile(
+                0,
+                0,
+                vec![vec![
+                    curve(&[(400.0, 100.0), (511.5, 100.0)]),
+                    curve(&[(300.0, 400.0), (300.0, 510.0)]),
+                    // Too far from the other tile's streets
+                    curve(&[(400.0, 300.0), (511.0, 300.0)]),
+                    curve(&[(450.0, 200.0), (510.0, 200.0)]),
+                ]],
+            ),
+            tile(
+                1,
+                0,
+                vec![vec![
+                    curve(&[(513.0, 103.0), (600.0, 110.0)]),
+                    curve(&[(512.5, 320.0), (600.0, 320.0)]),
+                    // Runs along the border, so the street ending next to it is joined onto its
+                    // side
+                    curve(&[(514.0, 150.0), (514.0, 250.0)]),
+                ]],
+            ),
+            tile(0, 1, vec![vec![curve(&[(400.0, 700.0), (302.0, 513.0)])]]),
+        ];
+
+        let levels = stitch_tiles(&tiles, 8.0);
+
+        assert_eq!(levels.len(), 1);
+        let streets = &levels[0];
+        assert_eq!(streets.len(), 8);
+        assert_eq!(streets[0][1].position, Point::new(512.0, 101.5));
+        assert_eq!(streets[4][0].position, Point::new(512.0, 101.5));
+        assert_eq!(streets[1][1].position, Point::new(301.0, 512.0));
+        assert_eq!(streets[7][1].position, Point::new(301.0, 512.0));
+        assert_eq!(streets[2][1].position, Point::new(511.0, 300.0));
+        assert_eq!(streets[5][0].position, Point::new(512.5, 320.0));
+        assert!((streets[3][1].position - Point::new(514.0, 200.0)).norm() < 0.01);
+        // Ends away from the borders stay where they are
+        assert_eq!(streets[0][0].position, Point::new(400.0, 100.0));
+    }
+
+    #[test]
+    fn moved_design_elements_give_the_same_field() {
+        let offset = Point::new(-512.0, -1024.0);
+        let point = Point::new(37.0, 81.0);
+        for element in [
+            DesignElement::Radial {
+                center: Point::new(600.0, 1100.0),
+            },
+            DesignElement::PolyLine {
+                points: vec![Point::new(500.0, 1000.0), Point::new(700.0, 1200.0)],
+                decay_constant: 0.01,
+            },
+        ] {
+            let moved = element.translated(offset);
+            assert_eq!(
+                moved.evaluate_at_point(point),
+                element.evaluate_at_point(point - offset)
+            );
+        }
+    }
+
+    #[test]
+    fn tiled_cities_are_zoned_and_built_on_across_tiles() {
+        let mut config = CityConfig {
+            tiles: TileConfig {
+                columns: 2,
+                ..TileConfig::default()
+            },
+            ..CityConfig::default()
+        };
+        // Only the major streets, to keep the city small
+        config.levels.truncate(1);
+        // The default design elements fade out before the second tile
+        config.field.design_elements.push(DesignElement::Grid {
+            center: Point::new(700.0, 250.0),
+            theta: 0.3,
+            length: 200.0,
+        });
+
+        let tiled = generate_tiled_city(&config).unwrap();
+
+        let city = &tiled.city;
+        assert_eq!(tiled.tiles.len(), 2);
+        assert_eq!(city.levels.len(), config.levels.len());
+        assert_eq!(city.land_uses.len(), city.blocks.len());
+        assert!(!city.blocks.is_empty());
+        assert!(!city.parcels.is_empty());
+        let second_tile = tiled.tiles[1].origin.x;
+        assert!(
+            city.levels[0]
+                .iter()
+                .flatten()
+                .any(|control_point| control_point.position.x > second_tile)
+        );
+        assert!(city.seeds[0].iter().any(|seed| seed.x > second_tile));
+        // Exports take it like any other city
+        assert!(
+            city_meshes(city, &config)
+                .iter()
+                .any(|mesh| mesh.name.starts_with("blocks_") && !mesh.indices.is_empty())
+        );
+    }
+}
@@ -0,0 +1,415 @@
+use crate::predicates::{orient2d, segments_intersect};
+use crate::street_graph::face_area;
+use crate::tensor_field::Point;
+
+/// A polygon cut into triangles. The vertices are the outline followed by every hole, the
+/// triangles are counter-clockwise and index into them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Triangulation {
+    pub vertices: Vec<Point>,
+    pub triangles: Vec<[usize; 3]>,
+    /// Holes, as indices into the ones given to `triangulate`, that no bridge to the outline
+    /// could be found for. The triangles cover them as if they weren't there.
+    pub unbridged_holes: Vec<usize>,
+}
+
+impl Triangulation {
+    pub fn triangle_points(&self) -> impl Iterator<Item = [Point; 3]> + '_ {
+        self.triangles
+            .iter()
+            .map(|triangle| triangle.map(|i| self.vertices[i]))
+    }
+
+    /// Whether one of the triangles covers the point, boundaries included
+    pub fn contains(&self, point: Point) -> bool {
+        self.triangle_points().any(|[a, b, c]| {
+            orient2d(a, b, point) >= 0.0
+                && orient2d(b, c, point) >= 0.0
+                && orient2d(c, a, point) >= 0.0
+        })
+    }
+
+    /// The covered area, which for a polygon with holes leaves the holes out
+    pub fn area(&self) -> f32 {
+        self.triangle_points()
+            .map(|triangle| face_area(&triangle))
+            .sum()
+    }
+
+    /// The area weighted center of the triangles, `None` if they don't cover anything
+    pub fn centroid(&self) -> Option<Point> {
+        let (weighted_sum, area) =
+            self.triangle_points()
+                .fold((Point::zeros(), 0.0), |(weighted_sum, area), triangle| {
+                    let triangle_area = face_area(&triangle);
+                    let center = (triangle[0] + triangle[1] + triangle[2]) / 3.0;
+                    (weighted_sum + center * triangle_area, area + triangle_area)
+                });
+        (area > 0.0).then(|| weighted_sum / area)
+    }
+}
+
+/// Triangulates a polygon with holes by ear clipping. Each hole is first joined to the outline
+/// with a bridge to the closest outline vertex it can see, which turns the polygon into a single
+/// ring that touches itself along the bridges. Either ring may be given in either orientation.
+///
+/// Rings that come out of the street graph aren't always clean, when no ear is left to clip the
+/// remaining ring is split along a diagonal that stays inside it and both halves are clipped on
+/// their own. A hole that can't be bridged without crossing an edge is left out and recorded in
+/// `Triangulation::unbridged_holes`.
+pub fn triangulate(outline: &[Point], holes: &[Vec<Point>]) -> Triangulation {
+    let mut vertices: Vec<Point> = Vec::new();
+    let mut push_ring = |ring: &[Point], counter_clockwise: bool| -> Vec<usize> {
+        let mut ring: Vec<Point> = ring.to_vec();
+        ring.dedup();
+        while ring.len() > 1 && ring.first() == ring.last() {
+            ring.pop();
+        }
+        if (face_area(&ring) > 0.0) != counter_clockwise {
+            ring.reverse();
+        }
+        let start = vertices.len();
+        vertices.extend(ring);
+        (start..vertices.len()).collect()
+    };
+
+    let mut polygon = push_ring(outline, true);
+    let mut hole_rings: Vec<(usize, Vec<usize>)> = holes
+        .iter()
+        .map(|hole| push_ring(hole, false))
+        .enumerate()
+        .filter(|(_, ring)| ring.len() >= 3)
+        .collect();
+
+    if polygon.len() < 3 {
+        return Triangulation {
+            vertices,
+            ..Default::default()
+        };
+    }
+
+    // Holes reaching furthest to the right go first, the bridges of later holes then have the
+    // earlier ones to route around
+    hole_rings.sort_by(|(_, a), (_, b)| {
+        let max_x =
+            |ring: &Vec<usize>| ring.iter().map(|&i| vertices[i].x).fold(f32::MIN, f32::max);
+        max_x(b).total_cmp(&max_x(a))
+    });
+
+    let mut unbridged_holes = Vec::new();
+    for hole_index in 0..hole_rings.len() {
+        let (original_index, hole) = &hole_rings[hole_index];
+        let other_holes = &hole_rings[hole_index + 1..];
+        let Some((polygon_position, hole_position)) =
+            find_bridge(&vertices, &polygon, hole, other_holes)
+        else {
+            unbridged_holes.push(*original_index);
+            continue;
+        };
+        let bridged: Vec<usize> = hole[hole_position..]
+            .iter()
+            .chain(&hole[..=hole_position])
+            .copied()
+            .chain(std::iter::once(polygon[polygon_position]))
+            .collect();
+        polygon.splice(polygon_position + 1..polygon_position + 1, bridged);
+    }
+    unbridged_holes.sort_unstable();
+
+    let mut triangles = Vec::with_capacity(polygon.len().saturating_sub(2));
+    clip_ears(&vertices, polygon, &mut triangles);
+
+    Triangulation {
+        vertices,
+        triangles,
+        unbridged_holes,
+    }
+}
+
+/// The closest pair of a polygon vertex and a hole vertex whose connecting segment doesn't cross
+/// any edge, as positions in the polygon and the hole
+fn find_bridge(
+    vertices: &[Point],
+    polygon: &[usize],
+    hole: &[usize],
+    other_holes: &[(usize, Vec<usize>)],
+) -> Option<(usize, usize)> {
+    let ring_edges = |ring: &[usize]| -> Vec<[usize; 2]> {
+        (0..ring.len())
+            .map(|i| [ring[i], ring[(i + 1) % ring.len()]])
+            .collect()
+    };
+    let edges: Vec<[usize; 2]> = ring_edges(polygon)
+        .into_iter()
+        .chain(ring_edges(hole))
+        .chain(other_holes.iter().flat_map(|(_, ring)| ring_edges(ring)))
+        .collect();
+
+    let mut candidates: Vec<(f32, usize, usize)> = polygon
+        .iter()
+        .enumerate()
+        .flat_map(|(polygon_position, &polygon_vertex)| {
+            hole.iter()
+                .enumerate()
+                .map(move |(hole_position, &hole_vertex)| {
+                    (
+                        (vertices[polygon_vertex] - vertices[hole_vertex]).norm_squared(),
+                        polygon_position,
+                        hole_position,
+                    )
+                })
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    candidates
+        .into_iter()
+        .find(|&(_, polygon_position, hole_position)| {
+            let bridge = [
+                vertices[polygon[polygon_position]],
+                vertices[hole[hole_position]],
+            ];
+            edges.iter().all(|edge| {
+                let edge_points = edge.map(|i| vertices[i]);
+                edge_points.contains(&bridge[0])
+                    || edge_points.contains(&bridge[1])
+                    || !segments_intersect(bridge, edge_points)
+            })
+        })
+        .map(|(_, polygon_position, hole_position)| (polygon_position, hole_position))
+}
+
+fn clip_ears(vertices: &[Point], mut polygon: Vec<usize>, triangles: &mut Vec<[usize; 3]>) {
+    let mut since_last_ear = 0;
+    let mut i = 0;
+    while polygon.len() > 3 {
+        if since_last_ear > polygon.len() {
+            if let Some((start, end)) = find_diagonal(vertices, &polygon) {
+                let first = polygon[start..=end].to_vec();
+                let second = polygon[end..]
+                    .iter()
+                    .chain(&polygon[..=start])
+                    .copied()
+                    .collect();
+                clip_ears(vertices, first, triangles);
+                clip_ears(vertices, second, triangles);
+                return;
+            }
+            // Only happens for self-intersecting input, what's left is cut as a fan
+            break;
+        }
+
+        let len = polygon.len();
+        let (previous, current, next) = (
+            polygon[(i + len - 1) % len],
+            polygon[i % len],
+            polygon[(i + 1) % len],
+        );
+        let (a, b, c) = (vertices[previous], vertices[current], vertices[next]);
+        let turn = orient2d(a, b, c);
+
+        if turn == 0.0 {
+            // Collinear vertices don't make a triangle, they can be dropped
+            polygon.remove(i % len);
+            since_last_ear = 0;
+        } else if turn > 0.0
+            && !polygon.iter().any(|&other| {
+                let point = vertices[other];
+                point != a
+                    && point != b
+                    && point != c
+                    && orient2d(a, b, point) >= 0.0
+                    && orient2d(b, c, point) >= 0.0
+                    && orient2d(c, a, point) >= 0.0
+            })
+        {
+            triangles.push([previous, current, next]);
+            polygon.remove(i % len);
+            since_last_ear = 0;
+        } else {
+            i += 1;
+            since_last_ear += 1;
+        }
+        i %= polygon.len();
+    }
+
+    for window in polygon.windows(2).skip(1) {
+        if orient2d(
+            vertices[polygon[0]],
+            vertices[window[0]],
+            vertices[window[1]],
+        ) > 0.0
+        {
+            triangles.push([polygon[0], window[0], window[1]]);
+        }
+    }
+}
+
+/// Two positions in the ring, the first one lower, whose vertices can be connected without
+/// leaving the ring or crossing any of its edges
+fn find_diagonal(vertices: &[Point], polygon: &[usize]) -> Option<(usize, usize)> {
+    let len = polygon.len();
+    let point_at = |position: usize| vertices[polygon[position % len]];
+
+    (0..len)
+        .flat_map(|start| (start + 2..len).map(move |end| (start, end)))
+        .filter(|&(start, end)| !(start == 0 && end == len - 1))
+        .find(|&(start, end)| {
+            let diagonal = [point_at(start), point_at(end)];
+            diagonal[0] != diagonal[1]
+                && locally_inside(
+                    [point_at(start + len - 1), diagonal[0], point_at(start + 1)],
+                    diagonal[1],
+                )
+                && locally_inside(
+                    [point_at(end + len - 1), diagonal[1], point_at(end + 1)],
+                    diagonal[0],
+                )
+                && (0..len).all(|edge| {
+                    let edge_points = [point_at(edge), point_at(edge + 1)];
+                    edge_points.contains(&diagonal[0])
+                        || edge_points.contains(&diagonal[1])
+                        || !segments_intersect(diagonal, edge_points)
+                })
+                && contains_point(
+                    &polygon.iter().map(|&i| vertices[i]).collect::<Vec<_>>(),
+                    (diagonal[0] + diagonal[1]) / 2.0,
+                )
+        })
+}
+
+/// Whether the direction from the middle of the corner towards the target points into the
+/// counter-clockwise ring the corner belongs to
+fn locally_inside([previous, corner, next]: [Point; 3], target: Point) -> bool {
+    if orient2d(previous, corner, next) > 0.0 {
+        orient2d(corner, target, next) <= 0.0 && orient2d(corner, previous, target) <= 0.0
+    } else {
+        orient2d(corner, target, previous) > 0.0 || orient2d(corner, next, target) > 0.0
+    }
+}
+
+/// Even-odd test of a point against a ring
+pub(crate) fn contains_point(ring: &[Point], point: Point) -> bool {
+    let mut inside = false;
+    for i in 0..ring.len() {
+        let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod test {
+    use crate::street_graph::face_area;
+    use crate::tensor_field::Point;
+
+    use super::triangulate;
+
+    #[test]
+    fn concave_polygon_is_covered_exactly() {
+        let outline = vec![
+            Point::new(0.0, 0.0),
+            Point::new(4.0, 0.0),
+            Point::new(4.0, 4.0),
+            Point::new(2.0, 1.0),
+            Point::new(0.0, 4.0),
+        ];
+
+        let triangulation = triangulate(&outline, &[]);
+
+        assert_eq!(triangulation.triangles.len(), 3);
+        for triangle in triangulation.triangle_points() {
+            assert!(face_area(&triangle) > 0.0);
+        }
+        assert!((triangulation.area() - face_area(&outline)).abs() < 0.0001);
+    }
+
+    #[test]
+    fn clockwise_polygon_with_a_hole() {
+        let outline = vec![
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 10.0),
+            Point::new(10.0, 10.0),
+            Point::new(10.0, 0.0),
+        ];
+        let hole = vec![
+            Point::new(3.0, 3.0),
+            Point::new(7.0, 3.0),
+            Point::new(7.0, 7.0),
+            Point::new(3.0, 7.0),
+        ];
+
+        let triangulation = triangulate(&outline, &[hole]);
+
+        assert_eq!(triangulation.vertices.len(), 8);
+        assert_eq!(triangulation.triangles.len(), 8);
+        assert!((triangulation.area() - 84.0).abs() < 0.0001);
+        assert!(triangulation.contains(Point::new(1.0, 1.0)));
+        assert!(!triangulation.contains(Point::new(5.0, 5.0)));
+        let centroid = triangulation.centroid().unwrap();
+        assert!((centroid - Point::new(5.0, 5.0)).norm() < 0.0001);
+    }
+
+    #[test]
+    fn hole_without_a_bridge_is_recorded() {
+        let outline = vec![
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(10.0, 10.0),
+            Point::new(0.0, 10.0),
+        ];
+        // The first hole sits inside the second one and touches its right edge, so every bridge
+        // to the outline runs into the second hole
+        let enclosed = vec![
+            Point::new(4.0, 4.0),
+            Point::new(8.0, 5.0),
+            Point::new(4.0, 6.0),
+        ];
+        let enclosing = vec![
+            Point::new(2.0, 2.0),
+            Point::new(8.0, 2.0),
+            Point::new(8.0, 8.0),
+            Point::new(2.0, 8.0),
+        ];
+
+        let triangulation = triangulate(&outline, &[enclosed, enclosing]);
+
+        assert_eq!(triangulation.unbridged_holes, vec![0]);
+        assert!((triangulation.area() - 64.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn dead_end_spike_is_left_uncovered() {
+        // A street ending inside the block leaves the face boundary running out and back
+        let outline = vec![
+            Point::new(0.0, 0.0),
+            Point::new(4.0, 0.0),
+            Point::new(4.0, 4.0),
+            Point::new(2.0, 4.0),
+            Point::new(2.0, 2.0),
+            Point::new(2.0, 4.0),
+            Point::new(0.0, 4.0),
+        ];
+
+        let triangulation = triangulate(&outline, &[]);
+
+        assert!((triangulation.area() - 16.0).abs() < 0.0001);
+        for triangle in triangulation.triangle_points() {
+            assert!(face_area(&triangle) > 0.0);
+        }
+        let centroid = triangulation.centroid().unwrap();
+        assert!((centroid - Point::new(2.0, 2.0)).norm() < 0.0001);
+    }
+
+    #[test]
+    fn empty_polygon_has_no_centroid() {
+        let triangulation = triangulate(&[Point::new(0.0, 0.0), Point::new(1.0, 1.0)], &[]);
+
+        assert!(triangulation.triangles.is_empty());
+        assert_eq!(triangulation.centroid(), None);
+    }
+}
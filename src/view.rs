@@ -0,0 +1,228 @@
+use nalgebra::Matrix4;
+
+use crate::tensor_field::{GRID_SIZE, Point};
+
+/// Closest the camera can zoom in, as a multiple of the whole grid filling the window
+const MAX_ZOOM: f32 = 64.0;
+/// Zoom factor of one step of the mouse wheel
+const ZOOM_STEP: f32 = 1.2;
+
+/// A 2D camera over the tensor field grid. At a zoom of 1 the whole grid fills the window, which
+/// is how the viewer draws without a camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2D {
+    /// Grid position in the middle of the window
+    pub center: Point,
+    pub zoom: f32,
+}
+
+impl Default for Camera2D {
+    fn default() -> Self {
+        Self {
+            center: Point::new(GRID_SIZE as f32, GRID_SIZE as f32) / 2.0,
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Camera2D {
+    /// Normalized device coordinates of a grid position, with y up in both
+    pub fn to_ndc(&self, point: Point) -> Point {
+        (point - self.center) * (2.0 * self.zoom / GRID_SIZE as f32)
+    }
+
+    /// The same transform as `to_ndc`, for the vertex shaders
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        let scale = 2.0 * self.zoom / GRID_SIZE as f32;
+        Matrix4::new(
+            scale,
+            0.0,
+            0.0,
+            -self.center.x * scale,
+            0.0,
+            scale,
+            0.0,
+            -self.center.y * scale,
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+        )
+    }
+
+    /// The grid position under a window pixel, with pixels counted from the top left corner
+    pub fn screen_to_grid(&self, pixel: Point, window_size: Point) -> Point {
+        let ndc = Point::new(
+            2.0 * pixel.x / window_size.x - 1.0,
+            1.0 - 2.0 * pixel.y / window_size.y,
+        );
+        self.center + ndc * (GRID_SIZE as f32 / (2.0 * self.zoom))
+    }
+
+    /// Moves the view along with a mouse drag of `pixels`
+    pub fn pan(&mut self, pixels: Point, window_size: Point) {
+        let cell = GRID_SIZE as f32 / self.zoom;
+        self.center -= Point::new(
+            pixels.x / window_size.x * cell,
+            -pixels.y / window_size.y * cell,
+        );
+    }
+
+    /// Zooms in by `steps` of the mouse wheel, or out for negative steps, keeping the grid position
+    /// under the cursor in place
+    pub fn zoom_at(&mut self, pixel: Point, steps: f32, window_size: Point) {
+        let anchor = self.screen_to_grid(pixel, window_size);
+        self.zoom = (self.zoom * ZOOM_STEP.powf(steps)).clamp(1.0, MAX_ZOOM);
+        self.center += anchor - self.screen_to_grid(pixel, window_size);
+    }
+}
+
+/// The things the viewer draws, each of which can be hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layer {
+    Eigenvectors,
+    DegeneratePoints,
+    MajorNetwork,
+    MinorNetwork,
+    Blocks,
+    Seeds,
+    /// The current step of the generation playback
+    History,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 7] = [
+        Layer::Eigenvectors,
+        Layer::DegeneratePoints,
+        Layer::MajorNetwork,
+        Layer::MinorNetwork,
+        Layer::Blocks,
+        Layer::Seeds,
+        Layer::History,
+    ];
+
+    /// Name of the entity the layer is drawn with in the viewer scene
+    pub fn entity_name(self) -> &'static str {
+        match self {
+            Layer::Eigenvectors => "eigenvectors",
+            Layer::DegeneratePoints => "degenerate_points",
+            Layer::MajorNetwork => "major_network",
+            Layer::MinorNetwork => "minor_network",
+            Layer::Blocks => "blocks",
+            Layer::Seeds => "seeds",
+            Layer::History => "history",
+        }
+    }
+
+    /// The number key that toggles the layer, 1 for the first one in `ALL`
+    pub fn toggle_key(self) -> char {
+        let index = Layer::ALL
+            .iter()
+            .position(|&layer| layer == self)
+            .unwrap_or_default();
+        char::from(b'1' + index as u8)
+    }
+
+    pub fn from_toggle_key(key: char) -> Option<Layer> {
+        Layer::ALL
+            .into_iter()
+            .find(|layer| layer.toggle_key() == key)
+    }
+}
+
+/// Which layers are shown, all of them to begin with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerVisibility {
+    visible: [bool; Layer::ALL.len()],
+}
+
+impl Default for LayerVisibility {
+    fn default() -> Self {
+        Self {
+            visible: [true; Layer::ALL.len()],
+        }
+    }
+}
+
+impl LayerVisibility {
+    pub fn is_visible(&self, layer: Layer) -> bool {
+        self.visible[layer as usize]
+    }
+
+    pub fn toggle(&mut self, layer: Layer) {
+        self.visible[layer as usize] = !self.visible[layer as usize];
+    }
+
+    /// Toggles the layer of a number key, returning it, or `None` for other keys
+    pub fn handle_key(&mut self, key: char) -> Option<Layer> {
+        let layer = Layer::from_toggle_key(key)?;
+        self.toggle(layer);
+        Some(layer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector4;
+
+    use crate::tensor_field::{GRID_SIZE, Point};
+
+    use super::{Camera2D, Layer, LayerVisibility};
+
+    #[test]
+    fn zooming_keeps_the_point_under_the_cursor() {
+        let window = Point::new(1024.0, 768.0);
+        let cursor = Point::new(800.0, 100.0);
+        let mut camera = Camera2D::default();
+        assert_eq!(
+            camera.to_ndc(Point::new(0.0, GRID_SIZE as f32)),
+            Point::new(-1.0, 1.0)
+        );
+
+        let before = camera.screen_to_grid(cursor, window);
+        camera.zoom_at(cursor, 3.0, window);
+        assert!(camera.zoom > 1.0);
+        assert!((camera.screen_to_grid(cursor, window) - before).norm() < 0.001);
+
+        // The view matrix agrees with the point transform
+        let point = Point::new(100.0, 300.0);
+        let transformed = camera.view_matrix() * Vector4::new(point.x, point.y, 0.0, 1.0);
+        assert!((transformed.xy() - camera.to_ndc(point)).norm() < 0.0001);
+
+        camera.zoom_at(cursor, -100.0, window);
+        assert_eq!(camera.zoom, 1.0);
+    }
+
+    #[test]
+    fn panning_follows_the_mouse() {
+        let window = Point::new(512.0, 512.0);
+        let mut camera = Camera2D {
+            zoom: 2.0,
+            ..Camera2D::default()
+        };
+        let grabbed = camera.screen_to_grid(Point::new(100.0, 100.0), window);
+
+        camera.pan(Point::new(50.0, -20.0), window);
+
+        let under_cursor = camera.screen_to_grid(Point::new(150.0, 80.0), window);
+        assert!((under_cursor - grabbed).norm() < 0.001);
+    }
+
+    #[test]
+    fn number_keys_toggle_layers() {
+        let mut visibility = LayerVisibility::default();
+        assert!(Layer::ALL.iter().all(|&layer| visibility.is_visible(layer)));
+
+        assert_eq!(visibility.handle_key('5'), Some(Layer::Blocks));
+        assert!(!visibility.is_visible(Layer::Blocks));
+        assert!(visibility.is_visible(Layer::Seeds));
+        assert_eq!(visibility.handle_key('x'), None);
+        visibility.handle_key('5');
+        assert!(visibility.is_visible(Layer::Blocks));
+        assert_eq!(Layer::Seeds.toggle_key(), '6');
+    }
+}
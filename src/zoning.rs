@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::ZoningConfig;
+use crate::parcels::{FRONTAGE_TOLERANCE, StreetIndex, distance_to_segment};
+use crate::predicates::segments_intersect;
+use crate::street_plan::HermiteCurve;
+use crate::tensor_field::Point;
+use crate::triangulation::{Triangulation, contains_point};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LandUse {
+    Residential,
+    Commercial,
+    Industrial,
+    Park,
+    Civic,
+    WaterAdjacent,
+}
+
+impl LandUse {
+    pub const ALL: [LandUse; 6] = [
+        LandUse::Residential,
+        LandUse::Commercial,
+        LandUse::Industrial,
+        LandUse::Park,
+        LandUse::Civic,
+        LandUse::WaterAdjacent,
+    ];
+
+    /// The name used for the land use in configs and exports
+    pub fn name(self) -> &'static str {
+        match self {
+            LandUse::Residential => "residential",
+            LandUse::Commercial => "commercial",
+            LandUse::Industrial => "industrial",
+            LandUse::Park => "park",
+            LandUse::Civic => "civic",
+            LandUse::WaterAdjacent => "water_adjacent",
+        }
+    }
+}
+
+impl std::fmt::Display for LandUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A polygon painted over the city that forces a land use on the blocks whose centroid it
+/// contains
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ZoningRegion {
+    pub land_use: LandUse,
+    pub polygon: Vec<Point>,
+}
+
+/// What the zoning rules look at for a single block
+#[derive(Debug, Clone, PartialEq)]
+struct BlockMetrics {
+    area: f32,
+    centroid: Point,
+    /// Polsby-Popper score, 1 for a circle and close to 0 for slivers
+    compactness: f32,
+    distance_to_center: f32,
+    /// The lowest street level any of the block's edges faces
+    lowest_street_level: Option<usize>,
+}
+
+/// Assigns a land use to every block, in the same order as `blocks`. The rules are tried in
+/// order and the first one that matches wins:
+///
+/// 1. the last painted region containing the block's centroid
+/// 2. blocks within `water_distance` of a body of water are water adjacent
+/// 3. small blocks and slivers become parks
+/// 4. compact blocks of the right size near the center are civic
+/// 5. blocks close to the center, or on a street of the first level a little further out, are
+///    commercial
+/// 6. large blocks far from the center are industrial
+/// 7. everything else is residential
+///
+/// `street_distance` is how far the block edges are from the street centerlines, which is the
+/// inset distance the blocks were built with.
+pub fn zone_blocks(
+    blocks: &[Vec<Point>],
+    triangulations: &[Triangulation],
+    levels: &[Vec<HermiteCurve>],
+    city_center: Point,
+    street_distance: f32,
+    config: &ZoningConfig,
+) -> Vec<LandUse> {
+    let street_index = StreetIndex::new(levels);
+    let max_distance = street_distance + FRONTAGE_TOLERANCE;
+
+    blocks
+        .iter()
+        .zip(triangulations)
+        .map(|(block, triangulation)| {
+            let area = triangulation.area();
+            let centroid = triangulation
+                .centroid()
+                .unwrap_or_else(|| block.iter().sum::<Point>() / block.len().max(1) as f32);
+            let perimeter: f32 = (0..block.len())
+                .map(|i| (block[(i + 1) % block.len()] - block[i]).norm())
+                .sum();
+            let compactness = if perimeter > 0.0 {
+                4.0 * std::f32::consts::PI * area / (perimeter * perimeter)
+            } else {
+                0.0
+            };
+            let lowest_street_level = (0..block.len())
+                .filter_map(|i| {
+                    street_index.street_facing_edge(
+                        block[i],
+                        block[(i + 1) % block.len()],
+                        max_distance,
+                    )
+                })
+                .map(|street| street.level)
+                .min();
+
+            let metrics = BlockMetrics {
+                area,
+                centroid,
+                compactness,
+                distance_to_center: (centroid - city_center).norm(),
+                lowest_street_level,
+            };
+            classify(block, &metrics, config)
+        })
+        .collect()
+}
+
+fn classify(block: &[Point], metrics: &BlockMetrics, config: &ZoningConfig) -> LandUse {
+    if let Some(region) = config
+        .regions
+        .iter()
+        .rev()
+        .find(|region| contains_point(&region.polygon, metrics.centroid))
+    {
+        return region.land_use;
+    }
+
+    if config
+        .water
+        .iter()
+        .any(|water| distance_to_ring(block, water) <= config.water_distance)
+    {
+        return LandUse::WaterAdjacent;
+    }
+
+    if metrics.area <= config.park_max_area || metrics.compactness < config.park_max_compactness {
+        return LandUse::Park;
+    }
+
+    if metrics.distance_to_center <= config.civic_radius
+        && metrics.compactness >= config.civic_min_compactness
+        && (config.civic_area[0]..=config.civic_area[1]).contains(&metrics.area)
+    {
+        return LandUse::Civic;
+    }
+
+    if metrics.distance_to_center <= config.commercial_radius
+        || metrics.lowest_street_level == Some(0)
+            && metrics.distance_to_center <= config.major_street_commercial_radius
+    {
+        return LandUse::Commercial;
+    }
+
+    if metrics.distance_to_center >= config.industrial_radius
+        && metrics.area >= config.industrial_min_area
+    {
+        return LandUse::Industrial;
+    }
+
+    LandUse::Residential
+}
+
+/// Distance between two rings, 0 when they overlap
+fn distance_to_ring(ring: &[Point], other: &[Point]) -> f32 {
+    let edges = |ring: &[Point]| -> Vec<[Point; 2]> {
+        (0..ring.len())
+            .map(|i| [ring[i], ring[(i + 1) % ring.len()]])
+            .collect()
+    };
+    let (edges, other_edges) = (edges(ring), edges(other));
+
+    if ring
+        .first()
+        .is_some_and(|&point| contains_point(other, point))
+        || other
+            .first()
+            .is_some_and(|&point| contains_point(ring, point))
+        || edges.iter().any(|&edge| {
+            other_edges
+                .iter()
+                .any(|&other_edge| segments_intersect(edge, other_edge))
+        })
+    {
+        return 0.0;
+    }
+
+    // Without crossings the closest pair of points is always a vertex and an edge
+    let closest = |points: &[Point], edges: &[[Point; 2]]| {
+        points
+            .iter()
+            .flat_map(|&point| {
+                edges
+                    .iter()
+                    .map(move |&edge| distance_to_segment(point, edge))
+            })
+            .fold(f32::MAX, f32::min)
+    };
+    closest(ring, &other_edges).min(closest(other, &edges))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::ZoningConfig;
+    use crate::tensor_field::Point;
+    use crate::test_support::rectangle;
+    use crate::triangulation::triangulate;
+
+    use super::{LandUse, ZoningRegion, zone_blocks};
+
+    fn zone(blocks: &[Vec<Point>], config: &ZoningConfig) -> Vec<LandUse> {
+        let triangulations: Vec<_> = blocks.iter().map(|block| triangulate(block, &[])).collect();
+        zone_blocks(
+            blocks,
+            &triangulations,
+            &[],
+            Point::new(0.0, 0.0),
+            1.0,
+            config,
+        )
+    }
+
+    #[test]
+    fn blocks_are_zoned_by_distance_size_and_shape() {
+        let config = ZoningConfig {
+            civic_area: [0.0, 0.0],
+            ..ZoningConfig::default()
+        };
+        let blocks = vec![
+            rectangle(Point::new(0.0, 0.0), Point::new(20.0, 20.0)),
+            rectangle(Point::new(200.0, 0.0), Point::new(220.0, 20.0)),
+            rectangle(Point::new(1000.0, 0.0), Point::new(1040.0, 40.0)),
+            rectangle(Point::new(300.0, 300.0), Point::new(302.0, 302.0)),
+            vec![
+                Point::new(400.0, 0.0),
+                Point::new(500.0, 0.0),
+                Point::new(500.0, 2.0),
+                Point::new(400.0, 2.0),
+            ],
+        ];
+
+        assert_eq!(
+            zone(&blocks, &config),
+            [
+                LandUse::Commercial,
+                LandUse::Residential,
+                LandUse::Industrial,
+                LandUse::Park,
+                LandUse::Park,
+            ]
+        );
+    }
+
+    #[test]
+    fn painted_regions_and_water_come_first() {
+        let config = ZoningConfig {
+            water: vec![rectangle(Point::new(-50.0, 0.0), Point::new(-10.0, 40.0))],
+            regions: vec![
+                ZoningRegion {
+                    land_use: LandUse::Industrial,
+                    polygon: rectangle(Point::new(-100.0, -100.0), Point::new(100.0, 100.0)),
+                },
+                ZoningRegion {
+                    land_use: LandUse::Park,
+                    polygon: rectangle(Point::new(-5.0, -5.0), Point::new(25.0, 25.0)),
+                },
+            ],
+            ..ZoningConfig::default()
+        };
+        let blocks = vec![
+            rectangle(Point::new(0.0, 0.0), Point::new(20.0, 20.0)),
+            rectangle(Point::new(-20.0, 150.0), Point::new(0.0, 170.0)),
+            rectangle(Point::new(-5.0, 45.0), Point::new(15.0, 65.0)),
+        ];
+
+        assert_eq!(
+            zone(&blocks, &config),
+            [LandUse::Park, LandUse::Residential, LandUse::Industrial]
+        );
+
+        let without_regions = ZoningConfig {
+            regions: Vec::new(),
+            ..config
+        };
+        assert_eq!(zone(&blocks, &without_regions)[2], LandUse::WaterAdjacent);
+    }
+}